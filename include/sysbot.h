@@ -0,0 +1,155 @@
+#ifndef SYSBOT_H
+#define SYSBOT_H
+
+/* Generated with cbindgen from src/ffi.rs; do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded
+ */
+#define SYSBOT_OK 0
+
+/**
+ * A pointer was null, a string was invalid or a value was out of range
+ */
+#define SYSBOT_ERROR_ARGUMENT -1
+
+/**
+ * The console could not be reached or the connection was lost
+ */
+#define SYSBOT_ERROR_CONNECTION -2
+
+/**
+ * The console could not carry out the command
+ */
+#define SYSBOT_ERROR_COMMAND -3
+
+/**
+ * The library panicked; the handle should be closed
+ */
+#define SYSBOT_ERROR_INTERNAL -4
+
+/**
+ * Addresses relative to the start of the heap
+ */
+#define SYSBOT_REGION_HEAP 0
+
+/**
+ * Addresses relative to the start of the main NSO
+ */
+#define SYSBOT_REGION_MAIN 1
+
+/**
+ * Absolute addresses
+ */
+#define SYSBOT_REGION_ABSOLUTE 2
+
+/**
+ * An opaque handle to a connected client
+ */
+typedef struct SysBot SysBot;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Connects to sys-botbase, returning a handle or null on failure.
+ *
+ * # Safety
+ *
+ * `host` must be null or a valid NUL-terminated string.
+ */
+SysBot *sysbot_connect(const char *host, uint16_t port);
+
+/**
+ * Disconnects and frees a handle. Null is ignored.
+ *
+ * # Safety
+ *
+ * `handle` must be null or a handle from [`sysbot_connect`] that has not been closed.
+ */
+void sysbot_close(SysBot *handle);
+
+/**
+ * Returns the message of the last error on this thread, or an empty string.
+ *
+ * The string stays valid until the next failing call on the same thread.
+ */
+const char *sysbot_last_error(void);
+
+/**
+ * Reads `len` bytes at `addr` into `buffer`.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle and `buffer` must be valid for writes of `len` bytes.
+ */
+int32_t sysbot_peek(const SysBot *handle,
+                    int32_t region,
+                    uint64_t addr,
+                    uint8_t *buffer,
+                    size_t len);
+
+/**
+ * Writes `len` bytes from `data` to `addr`.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle and `data` must be valid for reads of `len` bytes.
+ */
+int32_t sysbot_poke(const SysBot *handle,
+                    int32_t region,
+                    uint64_t addr,
+                    const uint8_t *data,
+                    size_t len);
+
+/**
+ * Clicks a button, named as sys-botbase names it, such as `"A"` or `"DUP"`.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle and `button` a valid NUL-terminated string.
+ */
+int32_t sysbot_click(const SysBot *handle, const char *button);
+
+/**
+ * Holds a button down until it is released.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle and `button` a valid NUL-terminated string.
+ */
+int32_t sysbot_press(const SysBot *handle, const char *button);
+
+/**
+ * Releases a held button.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle and `button` a valid NUL-terminated string.
+ */
+int32_t sysbot_release(const SysBot *handle, const char *button);
+
+/**
+ * Follows a pointer chain from main and stores the absolute address it leads to in `out_addr`.
+ *
+ * The jumps are the same as those of a `[[main+X]+Y]+Z` chain: `{X, Y, Z}`.
+ *
+ * # Safety
+ *
+ * `handle` must be a valid handle, `jumps` must be valid for reads of `count` values and
+ * `out_addr` must be valid for a write.
+ */
+int32_t sysbot_pointer(const SysBot *handle, const uint64_t *jumps, size_t count, uint64_t *out_addr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SYSBOT_H */
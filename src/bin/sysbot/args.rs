@@ -0,0 +1,164 @@
+use crate::error::CliError;
+use crate::output::Format;
+use std::collections::BTreeMap;
+
+pub const DEFAULT_PORT: u16 = 6000;
+
+/// The options that come before the command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Args {
+    pub host: Option<String>,
+    pub port: u16,
+    pub format: Format,
+    pub command: Vec<String>,
+}
+
+impl Args {
+    /// Parses the arguments after the program name, falling back to `SYSBOT_HOST`, `SYSBOT_PORT`
+    /// and `SYSBOT_FORMAT` from `env` for flags that are not given
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, CliError> {
+        let mut args = args.into_iter();
+        let mut host = env("SYSBOT_HOST");
+        let mut port = env("SYSBOT_PORT");
+        let mut format = env("SYSBOT_FORMAT");
+        let mut command = vec![];
+        while let Some(arg) = args.next() {
+            let slot = match arg.as_str() {
+                "-H" | "--host" => &mut host,
+                "-p" | "--port" => &mut port,
+                "-f" | "--format" => &mut format,
+                _ => {
+                    command.push(arg);
+                    command.extend(args);
+                    break;
+                }
+            };
+            *slot = Some(
+                args.next()
+                    .ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))?,
+            );
+        }
+        Ok(Self {
+            host,
+            port: port
+                .map(|p| {
+                    p.parse()
+                        .map_err(|_| CliError::Usage(format!("Invalid port {}", p)))
+                })
+                .transpose()?
+                .unwrap_or(DEFAULT_PORT),
+            format: format
+                .map(|f| f.parse())
+                .transpose()?
+                .unwrap_or(Format::Hex),
+            command,
+        })
+    }
+}
+
+/// The positional arguments and `--name value` options of a command
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub positional: Vec<String>,
+    named: BTreeMap<String, String>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))?;
+                    options.named.insert(name.to_string(), value.clone());
+                }
+                None => options.positional.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.named.get(name).map(|v| v.as_str())
+    }
+
+    /// Returns the positional argument at `index`, naming it in the error if it is missing
+    pub fn arg(&self, index: usize, name: &str) -> Result<&str, CliError> {
+        self.positional
+            .get(index)
+            .map(|s| s.as_str())
+            .ok_or_else(|| CliError::Usage(format!("Missing {}", name)))
+    }
+}
+
+/// Parses a number, in hex when it starts with `0x` and in decimal otherwise
+pub fn parse_number(s: &str) -> Result<u64, CliError> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| CliError::Usage(format!("Invalid number {}", s)))
+}
+
+/// Parses hex bytes, with or without a `0x` prefix
+pub fn parse_bytes(s: &str) -> Result<Vec<u8>, CliError> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return Err(CliError::Usage(format!("Invalid hex data {}", s)));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| CliError::Usage(format!("Invalid hex data {}", s)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::args::{parse_bytes, parse_number, Args, Options};
+    use crate::output::Format;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn should_prefer_flags_over_env() {
+        let env = |name: &str| match name {
+            "SYSBOT_HOST" => Some("192.168.0.2".to_string()),
+            "SYSBOT_PORT" => Some("6001".to_string()),
+            _ => None,
+        };
+        let args = Args::parse(
+            strings(&["--host", "10.0.0.5", "-f", "json", "peek", "0x10", "4"]),
+            env,
+        )
+        .unwrap();
+        assert_eq!(Some("10.0.0.5".to_string()), args.host);
+        assert_eq!(6001, args.port);
+        assert_eq!(Format::Json, args.format);
+        assert_eq!(strings(&["peek", "0x10", "4"]), args.command);
+        assert!(Args::parse(strings(&["--port"]), |_| None).is_err());
+        assert!(Args::parse(strings(&["--port", "x"]), |_| None).is_err());
+    }
+
+    #[test]
+    fn should_parse_command_options() {
+        let options = Options::parse(&strings(&["0x10", "--region", "main", "4"])).unwrap();
+        assert_eq!(strings(&["0x10", "4"]), options.positional);
+        assert_eq!(Some("main"), options.get("region"));
+        assert!(options.arg(2, "data").is_err());
+        assert_eq!(0x10, parse_number("0x10").unwrap());
+        assert_eq!(10, parse_number("10").unwrap());
+        assert_eq!(vec![0x0A, 0xFF], parse_bytes("0x0AFF").unwrap());
+        assert!(parse_bytes("ABC").is_err());
+    }
+}
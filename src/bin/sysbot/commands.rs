@@ -0,0 +1,354 @@
+use crate::args::{parse_bytes, parse_number, Options};
+use crate::error::CliError;
+use crate::output::{Field, Output};
+use std::fs;
+use sysbot_rs::dump;
+use sysbot_rs::dump::DumpHeader;
+use sysbot_rs::pointer::PointerChain;
+use sysbot_rs::script::Script;
+use sysbot_rs::types::{Button, ConfigureOption, PeekArgs, PokeArgs, PokeData, Region, Sequence};
+use sysbot_rs::SysBotClient;
+
+pub const USAGE: &str = "\
+Usage: sysbot [--host <ip>] [--port <port>] [--format hex|raw|json] <command> [args]
+
+The host, port and format can also be set with SYSBOT_HOST, SYSBOT_PORT and SYSBOT_FORMAT.
+Numbers are decimal unless they start with 0x, and data is written as hex bytes.
+
+Commands:
+  peek <addr> <size> [--region heap|main|absolute]
+  poke <addr> <data> [--region heap|main|absolute]
+  pointer <chain> [--size <size>]     e.g. [[main+4C1E2A8]+68]+10 or 0x4C1E2A8 0x68 0x10
+  click <button>...
+  seq <script> | seq --file <path>
+  freeze set <addr> <data> | remove <addr> | clear | count | pause | resume
+  configure <option> <value>
+  info
+  screenshot [path]
+  dump <region> <start> <len> <path>
+  restore <path>
+  repl                                an interactive shell that keeps the connection open
+  serve [--bind <addr>]               an HTTP/JSON and WebSocket gateway, on 127.0.0.1:8080 by default
+  proxy [--bind <addr>]               share the console with sys-botbase tools, on 0.0.0.0:6000 by default
+  help
+";
+
+/// The names of every command
+pub const COMMANDS: [&str; 15] = [
+    "peek",
+    "poke",
+    "pointer",
+    "click",
+    "seq",
+    "freeze",
+    "configure",
+    "info",
+    "screenshot",
+    "dump",
+    "restore",
+    "repl",
+    "serve",
+    "proxy",
+    "help",
+];
+
+fn region(options: &Options) -> Result<Region, CliError> {
+    options
+        .get("region")
+        .unwrap_or("heap")
+        .parse()
+        .map_err(|e: &str| CliError::Usage(e.to_string()))
+}
+
+fn header_fields(header: DumpHeader) -> Output {
+    Output::Fields(vec![
+        ("title_id", Field::Hex(header.title_id)),
+        ("build_id", Field::Hex(header.build_id)),
+        ("region", Field::Text(header.region.to_string())),
+        ("base", Field::Hex(header.base)),
+        ("start", Field::Hex(header.start)),
+        ("len", Field::Number(header.len)),
+        ("timestamp", Field::Number(header.timestamp)),
+    ])
+}
+
+/// What `freeze` should do
+#[derive(Clone, Debug, PartialEq)]
+pub enum FreezeCommand {
+    Set(PokeArgs),
+    Remove(u64),
+    Clear,
+    Count,
+    Pause,
+    Resume,
+}
+
+/// A command whose arguments have been checked, so usage errors are found before connecting
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Help,
+    Peek(Region, PeekArgs),
+    Poke(Region, PokeArgs),
+    Pointer {
+        jumps: Vec<u64>,
+        size: Option<usize>,
+    },
+    Click(Vec<Button>),
+    Seq(Sequence),
+    Freeze(FreezeCommand),
+    Configure(ConfigureOption),
+    Info,
+    Screenshot(Option<String>),
+    Dump {
+        region: Region,
+        start: u64,
+        len: usize,
+        path: String,
+    },
+    Restore(String),
+    Repl,
+    Serve(Option<String>),
+    Proxy(Option<String>),
+}
+
+/// Parses a command, where `command` is the command name followed by its arguments
+pub fn parse(command: &[String]) -> Result<Command, CliError> {
+    let Some((name, args)) = command.split_first() else {
+        return Ok(Command::Help);
+    };
+    let options = Options::parse(args)?;
+    Ok(match name.as_str() {
+        "help" => Command::Help,
+        "peek" => {
+            let addr = parse_number(options.arg(0, "address")?)?;
+            let size = parse_number(options.arg(1, "size")?)? as usize;
+            Command::Peek(region(&options)?, PeekArgs { addr, size })
+        }
+        "poke" => {
+            let addr = parse_number(options.arg(0, "address")?)?;
+            let data = PokeData::new(parse_bytes(options.arg(1, "data")?)?);
+            Command::Poke(region(&options)?, PokeArgs { addr, data })
+        }
+        "pointer" => {
+            let jumps = match options.positional.as_slice() {
+                [chain] if chain.contains('[') => {
+                    chain
+                        .parse::<PointerChain>()
+                        .map_err(|e| CliError::Usage(e.to_string()))?
+                        .jumps
+                }
+                [] => return Err(CliError::Usage("Missing pointer chain".to_string())),
+                jumps => jumps
+                    .iter()
+                    .map(|j| parse_number(j))
+                    .collect::<Result<_, _>>()?,
+            };
+            let size = options
+                .get("size")
+                .map(|size| parse_number(size).map(|size| size as usize))
+                .transpose()?;
+            Command::Pointer { jumps, size }
+        }
+        "click" => {
+            if options.positional.is_empty() {
+                return Err(CliError::Usage("Missing button".to_string()));
+            }
+            let buttons = options
+                .positional
+                .iter()
+                .map(|b| b.parse::<Button>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| CliError::Usage(e.to_string()))?;
+            Command::Click(buttons)
+        }
+        "seq" => {
+            let source = match options.get("file") {
+                Some(path) => fs::read_to_string(path)?,
+                None => options.arg(0, "script")?.to_string(),
+            };
+            Command::Seq(Script::parse(&source)?.compile()?)
+        }
+        "freeze" => Command::Freeze(match options.arg(0, "freeze command")? {
+            "set" => {
+                let addr = parse_number(options.arg(1, "address")?)?;
+                let data = PokeData::new(parse_bytes(options.arg(2, "data")?)?);
+                FreezeCommand::Set(PokeArgs { addr, data })
+            }
+            "remove" => FreezeCommand::Remove(parse_number(options.arg(1, "address")?)?),
+            "clear" => FreezeCommand::Clear,
+            "count" => FreezeCommand::Count,
+            "pause" => FreezeCommand::Pause,
+            "resume" => FreezeCommand::Resume,
+            other => return Err(CliError::Usage(format!("Unknown freeze command {}", other))),
+        }),
+        "configure" => Command::Configure(
+            format!("{} {}", options.arg(0, "option")?, options.arg(1, "value")?)
+                .parse::<ConfigureOption>()
+                .map_err(|e| CliError::Usage(e.to_string()))?,
+        ),
+        "info" => Command::Info,
+        "screenshot" => Command::Screenshot(options.positional.first().cloned()),
+        "dump" => Command::Dump {
+            region: options
+                .arg(0, "region")?
+                .parse()
+                .map_err(|e: &str| CliError::Usage(e.to_string()))?,
+            start: parse_number(options.arg(1, "start")?)?,
+            len: parse_number(options.arg(2, "length")?)? as usize,
+            path: options.arg(3, "path")?.to_string(),
+        },
+        "restore" => Command::Restore(options.arg(0, "path")?.to_string()),
+        "repl" => Command::Repl,
+        "serve" => Command::Serve(options.get("bind").map(String::from)),
+        "proxy" => Command::Proxy(options.get("bind").map(String::from)),
+        other => {
+            return Err(CliError::Usage(format!(
+                "Unknown command {}\n\n{}",
+                other, USAGE
+            )))
+        }
+    })
+}
+
+/// Runs a parsed command
+pub fn run(client: &SysBotClient, command: &Command) -> Result<Output, CliError> {
+    match command {
+        Command::Help => Err(CliError::Usage(USAGE.to_string())),
+        Command::Peek(region, args) => {
+            let mut bytes = client.peek_region(*region, args.clone())?;
+            bytes.truncate(args.size);
+            Ok(Output::Bytes(bytes))
+        }
+        Command::Poke(region, args) => {
+            client.poke_region(*region, args.clone())?;
+            Ok(Output::Done)
+        }
+        Command::Pointer { jumps, size } => match size {
+            Some(size) => {
+                let mut bytes = client.pointer_peek(jumps, *size)?;
+                bytes.truncate(*size);
+                Ok(Output::Bytes(bytes))
+            }
+            None => Ok(Output::Address(client.pointer_all(jumps)?)),
+        },
+        Command::Click(buttons) => {
+            for button in buttons {
+                client.click(*button)?;
+            }
+            Ok(Output::Done)
+        }
+        Command::Seq(sequence) => {
+            client.run_sequence(sequence)?;
+            Ok(Output::Done)
+        }
+        Command::Freeze(freeze) => match freeze {
+            FreezeCommand::Set(args) => {
+                client.freeze(args.clone())?;
+                Ok(Output::Done)
+            }
+            FreezeCommand::Remove(addr) => {
+                client.unfreeze(*addr)?;
+                Ok(Output::Done)
+            }
+            FreezeCommand::Clear => Ok(client.freeze_clear().map(|_| Output::Done)?),
+            FreezeCommand::Count => Ok(Output::Fields(vec![(
+                "count",
+                Field::Number(client.freeze_count()? as u64),
+            )])),
+            FreezeCommand::Pause => Ok(client.freeze_pause().map(|_| Output::Done)?),
+            FreezeCommand::Resume => Ok(client.freeze_unpause().map(|_| Output::Done)?),
+        },
+        Command::Configure(option) => {
+            client.configure(*option)?;
+            Ok(Output::Done)
+        }
+        Command::Info => Ok(Output::Fields(vec![
+            ("title_id", Field::Hex(client.get_title_id()?)),
+            ("build_id", Field::Hex(client.get_build_id()?)),
+            (
+                "version",
+                Field::Text(client.get_version()?.trim().to_string()),
+            ),
+            ("heap_base", Field::Hex(client.get_heap_base()?)),
+            ("main_base", Field::Hex(client.get_main_nso_base()?)),
+            (
+                "language",
+                Field::Number(client.get_system_language()? as u64),
+            ),
+        ])),
+        Command::Screenshot(path) => {
+            let jpeg = client.pixel_peek()?;
+            match path {
+                Some(path) => {
+                    fs::write(path, jpeg)?;
+                    Ok(Output::Done)
+                }
+                None => Ok(Output::Bytes(jpeg)),
+            }
+        }
+        Command::Dump {
+            region,
+            start,
+            len,
+            path,
+        } => Ok(header_fields(dump::dump(
+            client, *region, *start, *len, path,
+        )?)),
+        Command::Restore(path) => Ok(header_fields(dump::restore(client, path)?)),
+        Command::Repl => Err(CliError::Usage(
+            "repl cannot be run from the REPL".to_string(),
+        )),
+        Command::Serve(_) => Err(CliError::Usage(
+            "serve cannot be run from the REPL".to_string(),
+        )),
+        Command::Proxy(_) => Err(CliError::Usage(
+            "proxy cannot be run from the REPL".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::commands::{parse, Command};
+    use crate::error::CliError;
+    use sysbot_rs::types::{PeekArgs, Region};
+
+    fn parse_words(command: &str) -> Result<Command, CliError> {
+        parse(
+            &command
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn should_check_arguments_before_connecting() {
+        assert_eq!(
+            Ok(Command::Peek(
+                Region::Main,
+                PeekArgs {
+                    addr: 0x10,
+                    size: 4
+                }
+            )),
+            parse_words("peek 0x10 4 --region main")
+        );
+        assert_eq!(Ok(Command::Help), parse_words(""));
+        for command in [
+            "peek",
+            "peek 0x10",
+            "configure bogus 1",
+            "click START",
+            "freeze thaw",
+            "seq repeat",
+            "bogus",
+        ] {
+            assert!(
+                matches!(parse_words(command), Err(CliError::Usage(_))),
+                "{} should be a usage error",
+                command
+            );
+        }
+    }
+}
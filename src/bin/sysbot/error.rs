@@ -0,0 +1,110 @@
+use std::fmt;
+use std::fmt::Formatter;
+use sysbot_rs::dump::DumpError;
+use sysbot_rs::script::ScriptError;
+use sysbot_rs::SysBotClient;
+
+/// An error that ends the program, grouped by what went wrong so scripts can tell them apart
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CliError {
+    /// The arguments or input were invalid
+    Usage(String),
+    /// The console could not be reached or the connection was lost
+    Connection(&'static str),
+    /// The console could not carry out a command
+    Command(String),
+    /// A local file could not be read or written
+    Io(String),
+    /// A dump is for a different program or build than the one running
+    Mismatch(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => 2,
+            CliError::Connection(_) => 3,
+            CliError::Command(_) => 4,
+            CliError::Io(_) => 5,
+            CliError::Mismatch(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Connection(message) => write!(f, "{}", message),
+            CliError::Command(message) => write!(f, "{}", message),
+            CliError::Io(message) => write!(f, "{}", message),
+            CliError::Mismatch(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<&'static str> for CliError {
+    fn from(message: &'static str) -> Self {
+        match message {
+            message if SysBotClient::is_connection_error(message) => CliError::Connection(message),
+            "Failed to convert ip address" => CliError::Usage(message.to_string()),
+            _ => CliError::Command(message.to_string()),
+        }
+    }
+}
+
+impl From<ScriptError> for CliError {
+    fn from(error: ScriptError) -> Self {
+        match error {
+            ScriptError::Client(message) => message.into(),
+            error => CliError::Usage(error.to_string()),
+        }
+    }
+}
+
+impl From<DumpError> for CliError {
+    fn from(error: DumpError) -> Self {
+        match error {
+            DumpError::Client(message) => message.into(),
+            DumpError::Io(_) | DumpError::Format(_) => CliError::Io(error.to_string()),
+            DumpError::TitleMismatch { .. } | DumpError::BuildMismatch { .. } => {
+                CliError::Mismatch(error.to_string())
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(error: std::io::Error) -> Self {
+        CliError::Io(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::CliError;
+    use sysbot_rs::dump::DumpError;
+    use sysbot_rs::protocol;
+
+    #[test]
+    fn should_classify_errors() {
+        assert_eq!(3, CliError::from(protocol::RECEIVE_FAILED).exit_code());
+        assert_eq!(
+            2,
+            CliError::from("Failed to convert ip address").exit_code()
+        );
+        assert_eq!(
+            4,
+            CliError::from("Failed to parse bytes to u64").exit_code()
+        );
+        let mismatch = DumpError::BuildMismatch {
+            expected: 1,
+            actual: 2,
+        };
+        assert_eq!(6, CliError::from(mismatch).exit_code());
+        assert_eq!(
+            5,
+            CliError::from(DumpError::Format("Missing dump magic")).exit_code()
+        );
+    }
+}
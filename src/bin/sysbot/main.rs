@@ -0,0 +1,78 @@
+//! A command-line client for sys-botbase
+//!
+//! Run `sysbot help` for the list of commands, `sysbot repl` for an interactive shell,
+//! `sysbot serve` to share the connection over HTTP or `sysbot proxy` to share it with other
+//! sys-botbase tools. Errors are printed to stderr and the exit code tells what kind of error it
+//! was: 2 for invalid arguments, 3 for connection problems, 4 when the console could not carry
+//! out the command, 5 for local file problems and 6 when a dump does not match the running
+//! program.
+
+mod args;
+mod commands;
+mod error;
+mod output;
+mod repl;
+
+use crate::args::Args;
+use crate::commands::Command;
+use crate::error::CliError;
+use std::io::Write;
+use std::process::ExitCode;
+use sysbot_rs::gateway::Gateway;
+use sysbot_rs::proxy::Proxy;
+use sysbot_rs::SysBotClient;
+
+/// Where `serve` listens when `--bind` is not given
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+/// Where `proxy` listens when `--bind` is not given, matching sys-botbase itself
+const DEFAULT_PROXY_BIND: &str = "0.0.0.0:6000";
+
+fn run() -> Result<(), CliError> {
+    let args = Args::parse(std::env::args().skip(1), |name| std::env::var(name).ok())?;
+    let command = commands::parse(&args.command)?;
+    if command == Command::Help {
+        print!("{}", commands::USAGE);
+        return Ok(());
+    }
+    let host = args.host.as_deref().ok_or_else(|| {
+        CliError::Usage("No host given, pass --host or set SYSBOT_HOST".to_string())
+    })?;
+    let client = SysBotClient::connect(host, args.port)?;
+    match command {
+        Command::Repl => repl::run(&client, args.format),
+        Command::Serve(bind) => {
+            let gateway = Gateway::start(client, bind.as_deref().unwrap_or(DEFAULT_BIND))?;
+            eprintln!("sysbot: serving on http://{}", gateway.local_addr());
+            gateway.wait();
+            Ok(())
+        }
+        Command::Proxy(bind) => {
+            let proxy = Proxy::start(
+                client,
+                bind.as_deref().unwrap_or(DEFAULT_PROXY_BIND),
+                |event| eprintln!("sysbot: {}", event),
+            )?;
+            eprintln!("sysbot: proxying on {}", proxy.local_addr());
+            proxy.wait();
+            Ok(())
+        }
+        command => {
+            let output = commands::run(&client, &command)?;
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&output.render(args.format))?;
+            stdout.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("sysbot: {}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::error::CliError;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How results are printed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable text, with bytes and addresses in hex
+    Hex,
+    /// Bytes written as they are, and numbers in decimal
+    Raw,
+    /// A single JSON object per command
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Format::Hex),
+            "raw" => Ok(Format::Raw),
+            "json" => Ok(Format::Json),
+            _ => Err(CliError::Usage(format!("Unknown output format {}", s))),
+        }
+    }
+}
+
+/// A named value in the output of a command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    Hex(u64),
+    Number(u64),
+    Text(String),
+}
+
+/// What a command produced
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    Done,
+    Bytes(Vec<u8>),
+    Address(u64),
+    Fields(Vec<(&'static str, Field)>),
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02X}", b);
+        s
+    })
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Formats bytes as a classic hex dump, with 16 bytes per line followed by their ASCII
+pub fn hex_dump(start: u64, bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:010X} ", start + i as u64 * 16);
+        for column in 0..16 {
+            if column == 8 {
+                dump.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(dump, " {:02X}", byte);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        let ascii = line
+            .iter()
+            .map(|b| match b {
+                0x20..=0x7E => *b as char,
+                _ => '.',
+            })
+            .collect::<String>();
+        let _ = writeln!(dump, "  |{}|", ascii);
+    }
+    dump
+}
+
+impl Output {
+    /// Renders the output as the bytes to write to stdout
+    pub fn render(&self, format: Format) -> Vec<u8> {
+        let text = match (self, format) {
+            (Output::Done, Format::Json) => "{\"ok\":true}\n".to_string(),
+            (Output::Done, _) => String::new(),
+            (Output::Bytes(bytes), Format::Raw) => return bytes.clone(),
+            (Output::Bytes(bytes), Format::Hex) => format!("{}\n", hex(bytes)),
+            (Output::Bytes(bytes), Format::Json) => format!("{{\"data\":\"{}\"}}\n", hex(bytes)),
+            (Output::Address(addr), Format::Raw) => format!("{}\n", addr),
+            (Output::Address(addr), Format::Hex) => format!("0x{:X}\n", addr),
+            (Output::Address(addr), Format::Json) => format!("{{\"address\":\"0x{:X}\"}}\n", addr),
+            (Output::Fields(fields), Format::Json) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, field)| {
+                        let value = match field {
+                            Field::Hex(v) => format!("\"0x{:016X}\"", v),
+                            Field::Number(v) => v.to_string(),
+                            Field::Text(s) => json_string(s),
+                        };
+                        format!("\"{}\":{}", name, value)
+                    })
+                    .collect::<Vec<_>>();
+                format!("{{{}}}\n", fields.join(","))
+            }
+            (Output::Fields(fields), format) => fields
+                .iter()
+                .map(|(name, field)| match (field, format) {
+                    (Field::Hex(v), Format::Hex) => format!("{}: {:016X}\n", name, v),
+                    (Field::Hex(v) | Field::Number(v), _) => format!("{}: {}\n", name, v),
+                    (Field::Text(s), _) => format!("{}: {}\n", name, s),
+                })
+                .collect(),
+        };
+        text.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::{hex_dump, Field, Format, Output};
+
+    fn render(output: Output, format: Format) -> String {
+        String::from_utf8(output.render(format)).unwrap()
+    }
+
+    #[test]
+    fn should_render_each_format() {
+        let bytes = Output::Bytes(vec![0x0A, 0xFF]);
+        assert_eq!("0AFF\n", render(bytes.clone(), Format::Hex));
+        assert_eq!(vec![0x0A, 0xFF], bytes.render(Format::Raw));
+        assert_eq!("{\"data\":\"0AFF\"}\n", render(bytes, Format::Json));
+        assert_eq!("4660\n", render(Output::Address(0x1234), Format::Raw));
+        assert_eq!("", render(Output::Done, Format::Hex));
+        let fields = Output::Fields(vec![
+            ("title_id", Field::Hex(0x0100ABCD0000C000)),
+            ("language", Field::Number(2)),
+            ("version", Field::Text("2.4\"".to_string())),
+        ]);
+        assert_eq!(
+            "title_id: 0100ABCD0000C000\nlanguage: 2\nversion: 2.4\"\n",
+            render(fields.clone(), Format::Hex)
+        );
+        assert_eq!(
+            "{\"title_id\":\"0x0100ABCD0000C000\",\"language\":2,\"version\":\"2.4\\\"\"}\n",
+            render(fields, Format::Json)
+        );
+    }
+
+    #[test]
+    fn should_dump_hex_and_ascii() {
+        let bytes = b"Pikachu\0\x19\x00\x00\x00\xFF\xFF\xFF\xFFOK";
+        assert_eq!(
+            "00008A3F70  50 69 6B 61 63 68 75 00  19 00 00 00 FF FF FF FF  |Pikachu.........|\n\
+             00008A3F80  4F 4B                                             |OK|\n",
+            hex_dump(0x8A3F70, bytes)
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use crate::args::parse_number;
+use crate::commands;
+use crate::error::CliError;
+use crate::output::{hex_dump, Format, Output};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use sysbot_rs::types::{Button, ConfigureOption};
+use sysbot_rs::SysBotClient;
+
+const HELP: &str = "\
+Any sysbot command can be run without the connection options, along with:
+  mark <name> <addr>    save an address, then use it as @name or @name+0x10
+  marks                 list saved addresses
+  unmark <name>         forget a saved address
+  exit
+";
+
+/// Commands that only exist in the REPL
+const REPL_COMMANDS: [&str; 4] = ["mark", "marks", "unmark", "exit"];
+
+const REGIONS: [&str; 3] = ["heap", "main", "absolute"];
+
+const FREEZE_COMMANDS: [&str; 6] = ["set", "remove", "clear", "count", "pause", "resume"];
+
+type Bookmarks = BTreeMap<String, u64>;
+
+/// Parses bookmarks saved as `name 0xADDR` lines, skipping lines that do not parse
+fn parse_bookmarks(text: &str) -> Bookmarks {
+    text.lines()
+        .filter_map(|line| {
+            let (name, addr) = line.split_once(' ')?;
+            Some((name.to_string(), parse_number(addr.trim()).ok()?))
+        })
+        .collect()
+}
+
+fn format_bookmarks(bookmarks: &Bookmarks) -> String {
+    bookmarks
+        .iter()
+        .map(|(name, addr)| format!("{} 0x{:X}\n", name, addr))
+        .collect()
+}
+
+/// Replaces `@name` and `@name+offset` with the saved address
+fn expand(word: &str, bookmarks: &Bookmarks) -> Result<String, CliError> {
+    let Some(reference) = word.strip_prefix('@') else {
+        return Ok(word.to_string());
+    };
+    let (name, offset) = match reference.split_once('+') {
+        Some((name, offset)) => (name, parse_number(offset)?),
+        None => (reference, 0),
+    };
+    let addr = bookmarks
+        .get(name)
+        .ok_or_else(|| CliError::Usage(format!("No bookmark named {}", name)))?;
+    let addr = addr
+        .checked_add(offset)
+        .ok_or_else(|| CliError::Usage(format!("Address @{} is out of range", reference)))?;
+    Ok(format!("0x{:X}", addr))
+}
+
+/// Finds the completions for the word ending at the end of `line`, returning where it starts
+fn complete(line: &str, bookmarks: &Bookmarks) -> (usize, Vec<String>) {
+    let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let word = &line[start..];
+    let previous = line[..start].split_whitespace().collect::<Vec<_>>();
+    if let Some(name) = word.strip_prefix('@') {
+        let names = bookmarks
+            .keys()
+            .filter(|n| n.starts_with(name))
+            .map(|n| format!("@{}", n))
+            .collect();
+        return (start, names);
+    }
+    let candidates: Vec<String> = match previous.as_slice() {
+        [] => commands::COMMANDS
+            .iter()
+            .chain(&REPL_COMMANDS)
+            .filter(|c| !matches!(**c, "repl" | "serve" | "proxy"))
+            .map(|c| c.to_string())
+            .collect(),
+        [.., "--region"] | ["dump"] => REGIONS.iter().map(|r| r.to_string()).collect(),
+        ["click", ..] => Button::ALL.iter().map(|b| b.to_string()).collect(),
+        ["configure"] => ConfigureOption::KEYS
+            .iter()
+            .map(|k| k.to_string())
+            .collect(),
+        ["freeze"] => FREEZE_COMMANDS.iter().map(|c| c.to_string()).collect(),
+        ["unmark"] => bookmarks.keys().cloned().collect(),
+        _ => vec![],
+    };
+    let lower = word.to_ascii_lowercase();
+    let matches = candidates
+        .into_iter()
+        .filter(|c| c.to_ascii_lowercase().starts_with(&lower))
+        .collect();
+    (start, matches)
+}
+
+struct ReplHelper {
+    bookmarks: Bookmarks,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&line[..pos], &self.bookmarks))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// What to do after a line has run
+enum Step {
+    Print(Vec<u8>),
+    Exit,
+}
+
+fn execute(
+    client: &SysBotClient,
+    bookmarks: &mut Bookmarks,
+    line: &str,
+    format: Format,
+) -> Result<Step, CliError> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let args = match name {
+        // Scripts keep their spacing, so the rest of the line is passed along as it is
+        "seq" if !line.contains("--file") => vec![line[3..].trim().to_string()],
+        _ => words
+            .map(|w| expand(w, bookmarks))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    match (name, args.as_slice()) {
+        ("exit" | "quit", _) => Ok(Step::Exit),
+        ("help", _) => Ok(Step::Print(
+            format!("{}\n{}", commands::USAGE, HELP).into_bytes(),
+        )),
+        ("mark", [mark, addr]) => {
+            bookmarks.insert(mark.clone(), parse_number(addr)?);
+            Ok(Step::Print(vec![]))
+        }
+        ("mark", _) => Err(CliError::Usage("Usage: mark <name> <addr>".to_string())),
+        ("marks", _) => Ok(Step::Print(format_bookmarks(bookmarks).into_bytes())),
+        ("unmark", [mark]) => match bookmarks.remove(mark) {
+            Some(_) => Ok(Step::Print(vec![])),
+            None => Err(CliError::Usage(format!("No bookmark named {}", mark))),
+        },
+        ("unmark", _) => Err(CliError::Usage("Usage: unmark <name>".to_string())),
+        _ => {
+            let command = [vec![name.to_string()], args.clone()].concat();
+            let output = commands::run(client, &commands::parse(&command)?)?;
+            match (&output, format) {
+                (Output::Bytes(bytes), Format::Hex) => {
+                    let start = match name {
+                        "peek" => args.first().map(|a| parse_number(a)).transpose()?,
+                        _ => None,
+                    };
+                    Ok(Step::Print(
+                        hex_dump(start.unwrap_or_default(), bytes).into_bytes(),
+                    ))
+                }
+                _ => Ok(Step::Print(output.render(format))),
+            }
+        }
+    }
+}
+
+fn home_file(name: &str) -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(name))
+}
+
+/// Reads and runs commands until `exit`, end of input or the connection is lost
+///
+/// History is kept in `~/.sysbot_history` and bookmarks in `~/.sysbot_bookmarks`.
+pub fn run(client: &SysBotClient, format: Format) -> Result<(), CliError> {
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::with_config(config)
+        .map_err(|e| CliError::Io(e.to_string()))?;
+    let history_path = home_file(".sysbot_history");
+    let bookmarks_path = home_file(".sysbot_bookmarks");
+    if let Some(path) = &history_path {
+        let _ = editor.load_history(path);
+    }
+    let bookmarks = bookmarks_path
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|text| parse_bookmarks(&text))
+        .unwrap_or_default();
+    editor.set_helper(Some(ReplHelper { bookmarks }));
+    let result = loop {
+        let line = match editor.readline("sysbot> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break Ok(()),
+            Err(e) => break Err(CliError::Io(e.to_string())),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        let helper = editor.helper_mut().expect("helper is set");
+        match execute(client, &mut helper.bookmarks, line, format) {
+            Ok(Step::Print(bytes)) => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = stdout.write_all(&bytes).and_then(|_| stdout.flush());
+            }
+            Ok(Step::Exit) => break Ok(()),
+            Err(error @ CliError::Connection(_)) => break Err(error),
+            Err(error) => eprintln!("error: {}", error),
+        }
+    };
+    if let Some(path) = &history_path {
+        let _ = editor.save_history(path);
+    }
+    if let (Some(path), Some(helper)) = (&bookmarks_path, editor.helper()) {
+        fs::write(path, format_bookmarks(&helper.bookmarks))?;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::CliError;
+    use crate::repl::{complete, expand, format_bookmarks, parse_bookmarks, Bookmarks};
+
+    fn bookmarks() -> Bookmarks {
+        parse_bookmarks("party 0x8A3F70\nmoney 0x1000\nbroken line\n")
+    }
+
+    #[test]
+    fn should_complete_by_context() {
+        let marks = bookmarks();
+        assert_eq!((0, vec!["peek".to_string()]), complete("pe", &marks));
+        assert_eq!(
+            (
+                8,
+                vec![
+                    "DL".to_string(),
+                    "DU".to_string(),
+                    "DD".to_string(),
+                    "DR".to_string()
+                ]
+            ),
+            complete("click A d", &marks)
+        );
+        assert_eq!(
+            (10, vec!["echoCommands".to_string()]),
+            complete("configure ec", &marks)
+        );
+        assert_eq!(
+            (21, vec!["main".to_string()]),
+            complete("peek 0x10 4 --region m", &marks)
+        );
+        assert_eq!(
+            (5, vec!["@party".to_string()]),
+            complete("peek @pa", &marks)
+        );
+    }
+
+    #[test]
+    fn should_expand_and_save_bookmarks() {
+        let marks = bookmarks();
+        assert_eq!("0x8A3F80", expand("@party+0x10", &marks).unwrap());
+        assert_eq!("0x10", expand("0x10", &marks).unwrap());
+        assert!(expand("@missing", &marks).is_err());
+        assert!(matches!(
+            expand("@party+0xFFFFFFFFFFFFFFFF", &marks),
+            Err(CliError::Usage(_))
+        ));
+        assert_eq!("money 0x1000\nparty 0x8A3F70\n", format_bookmarks(&marks));
+    }
+}
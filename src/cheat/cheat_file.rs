@@ -0,0 +1,146 @@
+use crate::cheat::error::CheatError;
+use std::fs;
+use std::path::Path;
+
+/// A single named cheat and its opcode dwords
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    /// Whether this is the master code, which runs before every other cheat
+    pub master: bool,
+    pub opcodes: Vec<u32>,
+}
+
+/// The cheats for one build of a program
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheatFile {
+    /// The build ID the cheats were written for, taken from the file name when loaded
+    pub build_id: Option<u64>,
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatFile {
+    /// Parses the text of a cheat file
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sysbot_rs::cheat::CheatFile;
+    /// let file = CheatFile::parse("[Max Money]\n04000000 0054D8F0 05F5E0FF").unwrap();
+    /// assert_eq!(vec![0x04000000, 0x0054D8F0, 0x05F5E0FF], file.get("Max Money").unwrap().opcodes);
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, CheatError> {
+        let mut cheats: Vec<Cheat> = vec![];
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let header = match (line.chars().next(), line.chars().last()) {
+                (Some('['), Some(']')) => Some(false),
+                (Some('{'), Some('}')) => Some(true),
+                (Some('[') | Some('{'), _) => {
+                    return Err(CheatError::Parse {
+                        line: line_number,
+                        message: "Unterminated cheat name",
+                    })
+                }
+                _ => None,
+            };
+            if let Some(master) = header {
+                if master && cheats.iter().any(|c| c.master) {
+                    return Err(CheatError::Parse {
+                        line: line_number,
+                        message: "Cheat file has more than one master code",
+                    });
+                }
+                cheats.push(Cheat {
+                    name: line[1..line.len() - 1].trim().to_string(),
+                    master,
+                    opcodes: vec![],
+                });
+                continue;
+            }
+            let cheat = cheats.last_mut().ok_or(CheatError::Parse {
+                line: line_number,
+                message: "Opcodes must follow a cheat name",
+            })?;
+            for word in line.split_whitespace() {
+                let dword = u32::from_str_radix(word, 16)
+                    .ok()
+                    .filter(|_| word.len() == 8)
+                    .ok_or(CheatError::Parse {
+                        line: line_number,
+                        message: "Opcode dwords must be 8 hex digits",
+                    })?;
+                cheat.opcodes.push(dword);
+            }
+        }
+        Ok(Self {
+            build_id: None,
+            cheats,
+        })
+    }
+
+    /// Reads a cheat file, taking the build ID from a file name such as `8BB2C0E09AA48A0D.txt`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CheatError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|e| CheatError::Io(e.kind()))?;
+        let mut file = Self::parse(&source)?;
+        file.build_id = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| stem.len() == 16)
+            .and_then(|stem| u64::from_str_radix(stem, 16).ok());
+        Ok(file)
+    }
+
+    /// Returns the cheat with the given name
+    pub fn get(&self, name: &str) -> Option<&Cheat> {
+        self.cheats.iter().find(|c| c.name == name)
+    }
+
+    pub fn master(&self) -> Option<&Cheat> {
+        self.cheats.iter().find(|c| c.master)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cheat::{CheatError, CheatFile};
+
+    #[test]
+    fn should_parse_sections() {
+        let file = CheatFile::parse(
+            "{Master Code}\n\
+             580F0000 01234567\n\
+             \n\
+             [Infinite HP]\n\
+             04000000 00001000\n\
+             00000063\n\
+             [Empty]\n",
+        )
+        .unwrap();
+        assert_eq!(3, file.cheats.len());
+        assert!(file.master().unwrap().master);
+        assert_eq!(vec![0x580F0000, 0x01234567], file.master().unwrap().opcodes);
+        assert_eq!(
+            vec![0x04000000, 0x00001000, 0x00000063],
+            file.get("Infinite HP").unwrap().opcodes
+        );
+        assert!(file.get("Empty").unwrap().opcodes.is_empty());
+    }
+
+    #[test]
+    fn should_report_parse_errors() {
+        let line = |source| match CheatFile::parse(source) {
+            Err(CheatError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+        assert_eq!(1, line("04000000 00001000"));
+        assert_eq!(2, line("[A]\n0400000G"));
+        assert_eq!(3, line("[A]\n\n[B"));
+        assert_eq!(2, line("{A}\n{B}"));
+    }
+}
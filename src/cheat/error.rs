@@ -0,0 +1,43 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+/// An error raised while parsing, translating or applying cheats
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheatError {
+    /// The cheat file is malformed at the given 1-based line
+    Parse { line: usize, message: &'static str },
+    /// The cheat file could not be read
+    Io(std::io::ErrorKind),
+    /// The opcode starting with the given dword cannot be decoded or translated
+    Unsupported(u32),
+    /// No cheat with the given name exists in the file
+    NotFound(String),
+    /// The cheats were written for a different build of the running program
+    BuildMismatch { expected: u64, actual: u64 },
+    /// A frame of the cheat VM ran more instructions than allowed
+    InstructionLimit,
+    /// The client failed while applying a cheat
+    Client(&'static str),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CheatError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            CheatError::Io(kind) => write!(f, "Failed to read cheat file: {}", kind),
+            CheatError::Unsupported(dword) => write!(f, "Unsupported cheat opcode {:08X}", dword),
+            CheatError::NotFound(name) => write!(f, "No cheat named {}", name),
+            CheatError::BuildMismatch { expected, actual } => write!(
+                f,
+                "Cheats are for build {:016X} but {:016X} is running",
+                expected, actual
+            ),
+            CheatError::InstructionLimit => {
+                write!(f, "Cheat VM frame exceeded its instruction limit")
+            }
+            CheatError::Client(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
@@ -0,0 +1,18 @@
+//! Atmosphère cheat files and applying them through sys-botbase
+//!
+//! Cheat files hold named cheats in `[...]` sections, with an optional master code in a `{...}`
+//! section, each made of opcode dwords written in hex. Cheats built only from static and
+//! pointer writes can be translated into `pokeMain`, `poke` and `pointerPoke` commands, while
+//! the rest can be run on the client side by the [`CheatVm`].
+
+mod cheat_file;
+mod error;
+mod opcode;
+mod vm;
+mod write;
+
+pub use cheat_file::*;
+pub use error::*;
+pub use opcode::*;
+pub use vm::*;
+pub use write::*;
@@ -0,0 +1,680 @@
+use crate::cheat::error::CheatError;
+
+/// The memory an opcode address is relative to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryType {
+    Main,
+    Heap,
+    Alias,
+    Aslr,
+}
+
+impl MemoryType {
+    fn decode(nibble: u32, dword: u32) -> Result<Self, CheatError> {
+        match nibble {
+            0 => Ok(MemoryType::Main),
+            1 => Ok(MemoryType::Heap),
+            2 => Ok(MemoryType::Alias),
+            3 => Ok(MemoryType::Aslr),
+            _ => Err(CheatError::Unsupported(dword)),
+        }
+    }
+}
+
+/// An arithmetic operation, of which the legacy arithmetic opcode supports the first five
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    LeftShift,
+    RightShift,
+    And,
+    Or,
+    Not,
+    Xor,
+    /// Copies the first operand
+    None,
+}
+
+impl ArithmeticOp {
+    fn decode(nibble: u32, dword: u32) -> Result<Self, CheatError> {
+        match nibble {
+            0 => Ok(ArithmeticOp::Add),
+            1 => Ok(ArithmeticOp::Sub),
+            2 => Ok(ArithmeticOp::Mul),
+            3 => Ok(ArithmeticOp::LeftShift),
+            4 => Ok(ArithmeticOp::RightShift),
+            5 => Ok(ArithmeticOp::And),
+            6 => Ok(ArithmeticOp::Or),
+            7 => Ok(ArithmeticOp::Not),
+            8 => Ok(ArithmeticOp::Xor),
+            9 => Ok(ArithmeticOp::None),
+            _ => Err(CheatError::Unsupported(dword)),
+        }
+    }
+
+    fn decode_legacy(nibble: u32, dword: u32) -> Result<Self, CheatError> {
+        match nibble {
+            0..=4 => ArithmeticOp::decode(nibble, dword),
+            _ => Err(CheatError::Unsupported(dword)),
+        }
+    }
+
+    /// Applies the operation to two values
+    pub fn apply(&self, a: u64, b: u64) -> u64 {
+        match self {
+            ArithmeticOp::Add => a.wrapping_add(b),
+            ArithmeticOp::Sub => a.wrapping_sub(b),
+            ArithmeticOp::Mul => a.wrapping_mul(b),
+            ArithmeticOp::LeftShift => a.wrapping_shl(b as u32),
+            ArithmeticOp::RightShift => a.wrapping_shr(b as u32),
+            ArithmeticOp::And => a & b,
+            ArithmeticOp::Or => a | b,
+            ArithmeticOp::Not => !a,
+            ArithmeticOp::Xor => a ^ b,
+            ArithmeticOp::None => a,
+        }
+    }
+}
+
+/// A comparison made by the conditional opcodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Condition {
+    fn decode(nibble: u32, dword: u32) -> Result<Self, CheatError> {
+        match nibble {
+            1 => Ok(Condition::Greater),
+            2 => Ok(Condition::GreaterOrEqual),
+            3 => Ok(Condition::Less),
+            4 => Ok(Condition::LessOrEqual),
+            5 => Ok(Condition::Equal),
+            6 => Ok(Condition::NotEqual),
+            _ => Err(CheatError::Unsupported(dword)),
+        }
+    }
+
+    pub fn holds(&self, a: u64, b: u64) -> bool {
+        match self {
+            Condition::Greater => a > b,
+            Condition::GreaterOrEqual => a >= b,
+            Condition::Less => a < b,
+            Condition::LessOrEqual => a <= b,
+            Condition::Equal => a == b,
+            Condition::NotEqual => a != b,
+        }
+    }
+}
+
+/// The second operand of an arithmetic opcode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithmeticOperand {
+    Register(u8),
+    Static(u64),
+}
+
+/// Where the store register opcode writes to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreTarget {
+    /// The address in the address register
+    Register,
+    /// The address register plus another register
+    OffsetRegister(u8),
+    /// The address register plus a relative address
+    Relative(u64),
+    /// A memory base plus the address register
+    MemoryRegister(MemoryType),
+    /// A memory base plus a relative address
+    MemoryRelative(MemoryType, u64),
+    /// A memory base plus the address register and a relative address
+    MemoryRegisterRelative(MemoryType, u64),
+}
+
+/// The value a register is compared against by the register conditional opcode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionOperand {
+    /// The value at a memory base plus a relative address
+    MemoryRelative(MemoryType, u64),
+    /// The value at a memory base plus a register
+    MemoryOffsetRegister(MemoryType, u8),
+    /// The value at a register plus a relative address
+    RegisterRelative(u8, u64),
+    /// The value at a register plus another register
+    RegisterOffsetRegister(u8, u8),
+    Static(u64),
+    Register(u8),
+}
+
+/// An operation on the saved copies of the registers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveOperation {
+    Restore,
+    Save,
+    ClearSaved,
+    ClearRegister,
+}
+
+impl SaveOperation {
+    fn decode(nibble: u32, dword: u32) -> Result<Self, CheatError> {
+        match nibble {
+            0 => Ok(SaveOperation::Restore),
+            1 => Ok(SaveOperation::Save),
+            2 => Ok(SaveOperation::ClearSaved),
+            3 => Ok(SaveOperation::ClearRegister),
+            _ => Err(CheatError::Unsupported(dword)),
+        }
+    }
+}
+
+/// A decoded cheat VM instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    /// `0TMR00AA AAAAAAAA VVVVVVVV (VVVVVVVV)`: writes a value to `memory + address + register`
+    StoreStatic {
+        width: u8,
+        memory: MemoryType,
+        offset_register: u8,
+        address: u64,
+        value: u64,
+    },
+    /// `400R0000 VVVVVVVV VVVVVVVV`: sets a register to a value
+    LoadRegisterStatic { register: u8, value: u64 },
+    /// `5TMRS0AA AAAAAAAA`: loads a register from `memory + address`, or from
+    /// `register + address` when `from_register` is set
+    LoadRegisterMemory {
+        width: u8,
+        memory: MemoryType,
+        register: u8,
+        from_register: bool,
+        address: u64,
+    },
+    /// `6T0RIor0 VVVVVVVV VVVVVVVV`: writes a value to the address in a register, plus an
+    /// optional offset register, then optionally increments the register by the width
+    StoreStaticToAddress {
+        width: u8,
+        register: u8,
+        increment: bool,
+        offset_register: Option<u8>,
+        value: u64,
+    },
+    /// `7T0RC000 VVVVVVVV`: applies an operation with a value to a register
+    LegacyArithmetic {
+        width: u8,
+        register: u8,
+        op: ArithmeticOp,
+        value: u32,
+    },
+    /// `1TMC00AA AAAAAAAA VVVVVVVV (VVVVVVVV)`: runs the following block if the value at
+    /// `memory + address` satisfies the condition
+    BeginConditional {
+        width: u8,
+        memory: MemoryType,
+        condition: Condition,
+        address: u64,
+        value: u64,
+    },
+    /// `20000000`: ends a conditional block
+    EndConditional,
+    /// `21000000`: runs the following block if the open conditional failed
+    Else,
+    /// `300R0000 VVVVVVVV`: starts a loop running `iterations` times, counted in a register
+    LoopStart { register: u8, iterations: u32 },
+    /// `310R0000`: ends the loop counted in a register
+    LoopEnd { register: u8 },
+    /// `8kkkkkkk`: runs the following block if every key in the mask is held
+    BeginKeypressConditional { keys: u32 },
+    /// `9TCRS0s0` or `9TCRS100 VVVVVVVV (VVVVVVVV)`: sets a register to an operation on a
+    /// register and an operand
+    Arithmetic {
+        width: u8,
+        op: ArithmeticOp,
+        destination: u8,
+        source: u8,
+        operand: ArithmeticOperand,
+    },
+    /// `ATSRIOxa (aaaaaaaa)`: writes a register to an address, then optionally increments the
+    /// address register by the width
+    StoreRegister {
+        width: u8,
+        source: u8,
+        address_register: u8,
+        increment: bool,
+        target: StoreTarget,
+    },
+    /// `C0TcSX..`: runs the following block if a register satisfies the condition
+    BeginRegisterConditional {
+        width: u8,
+        condition: Condition,
+        register: u8,
+        operand: ConditionOperand,
+    },
+    /// `C10D0Sx0`: saves, restores or clears a single register
+    SaveRestoreRegister {
+        destination: u8,
+        source: u8,
+        operation: SaveOperation,
+    },
+    /// `C2x0XXXX`: saves, restores or clears every register in the mask
+    SaveRestoreRegisterMask { operation: SaveOperation, mask: u16 },
+    /// `C3000XXx`: reads static registers below `0x80` into a register, or writes a register to
+    /// static registers from `0x80`
+    ReadWriteStaticRegister { static_register: u8, register: u8 },
+    /// `FF0?????`: pauses the process
+    PauseProcess,
+    /// `FF1?????`: resumes the process
+    ResumeProcess,
+}
+
+impl Opcode {
+    /// Returns true if the opcode opens a block closed by [`Opcode::EndConditional`]
+    pub fn is_conditional(&self) -> bool {
+        matches!(
+            self,
+            Opcode::BeginConditional { .. }
+                | Opcode::BeginKeypressConditional { .. }
+                | Opcode::BeginRegisterConditional { .. }
+        )
+    }
+}
+
+fn nibble(dword: u32, index: u32) -> u32 {
+    (dword >> (28 - index * 4)) & 0xF
+}
+
+fn width(dword: u32) -> Result<u8, CheatError> {
+    match nibble(dword, 1) {
+        w @ (1 | 2 | 4 | 8) => Ok(w as u8),
+        _ => Err(CheatError::Unsupported(dword)),
+    }
+}
+
+fn address(first: u32, second: u32) -> u64 {
+    (((first & 0xFF) as u64) << 32) | second as u64
+}
+
+fn short_address(first: u32, second: u32) -> u64 {
+    (((first & 0xF) as u64) << 32) | second as u64
+}
+
+fn wide(high: u32, low: u32) -> u64 {
+    ((high as u64) << 32) | low as u64
+}
+
+impl Opcode {
+    /// Decodes the instruction at the start of `dwords`, returning it and the number of dwords it
+    /// spans
+    pub fn decode(dwords: &[u32]) -> Result<(Opcode, usize), CheatError> {
+        let first = *dwords.first().ok_or(CheatError::Unsupported(0))?;
+        let arg = |index: usize| {
+            dwords
+                .get(index)
+                .copied()
+                .ok_or(CheatError::Unsupported(first))
+        };
+        match nibble(first, 0) {
+            0x0 => {
+                let width = width(first)?;
+                let (value, length) = if width == 8 {
+                    (wide(arg(2)?, arg(3)?), 4)
+                } else {
+                    (arg(2)? as u64, 3)
+                };
+                Ok((
+                    Opcode::StoreStatic {
+                        width,
+                        memory: MemoryType::decode(nibble(first, 2), first)?,
+                        offset_register: nibble(first, 3) as u8,
+                        address: address(first, arg(1)?),
+                        value,
+                    },
+                    length,
+                ))
+            }
+            0x4 => Ok((
+                Opcode::LoadRegisterStatic {
+                    register: nibble(first, 3) as u8,
+                    value: wide(arg(1)?, arg(2)?),
+                },
+                3,
+            )),
+            0x5 => Ok((
+                Opcode::LoadRegisterMemory {
+                    width: width(first)?,
+                    memory: MemoryType::decode(nibble(first, 2), first)?,
+                    register: nibble(first, 3) as u8,
+                    from_register: match nibble(first, 4) {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(CheatError::Unsupported(first)),
+                    },
+                    address: address(first, arg(1)?),
+                },
+                2,
+            )),
+            0x6 => Ok((
+                Opcode::StoreStaticToAddress {
+                    width: width(first)?,
+                    register: nibble(first, 3) as u8,
+                    increment: nibble(first, 4) != 0,
+                    offset_register: (nibble(first, 5) != 0).then(|| nibble(first, 6) as u8),
+                    value: wide(arg(1)?, arg(2)?),
+                },
+                3,
+            )),
+            0x7 => Ok((
+                Opcode::LegacyArithmetic {
+                    width: width(first)?,
+                    register: nibble(first, 3) as u8,
+                    op: ArithmeticOp::decode_legacy(nibble(first, 4), first)?,
+                    value: arg(1)?,
+                },
+                2,
+            )),
+            0x1 => {
+                let width = width(first)?;
+                let (value, length) = if width == 8 {
+                    (wide(arg(2)?, arg(3)?), 4)
+                } else {
+                    (arg(2)? as u64, 3)
+                };
+                Ok((
+                    Opcode::BeginConditional {
+                        width,
+                        memory: MemoryType::decode(nibble(first, 2), first)?,
+                        condition: Condition::decode(nibble(first, 3), first)?,
+                        address: address(first, arg(1)?),
+                        value,
+                    },
+                    length,
+                ))
+            }
+            0x2 => match nibble(first, 1) {
+                0 => Ok((Opcode::EndConditional, 1)),
+                1 => Ok((Opcode::Else, 1)),
+                _ => Err(CheatError::Unsupported(first)),
+            },
+            0x3 => match nibble(first, 1) {
+                0 => Ok((
+                    Opcode::LoopStart {
+                        register: nibble(first, 3) as u8,
+                        iterations: arg(1)?,
+                    },
+                    2,
+                )),
+                1 => Ok((
+                    Opcode::LoopEnd {
+                        register: nibble(first, 3) as u8,
+                    },
+                    1,
+                )),
+                _ => Err(CheatError::Unsupported(first)),
+            },
+            0x8 => Ok((
+                Opcode::BeginKeypressConditional {
+                    keys: first & 0x0FFFFFFF,
+                },
+                1,
+            )),
+            0x9 => {
+                let width = width(first)?;
+                let (operand, length) = match nibble(first, 5) {
+                    0 => (ArithmeticOperand::Register(nibble(first, 6) as u8), 1),
+                    1 if width == 8 => (ArithmeticOperand::Static(wide(arg(1)?, arg(2)?)), 3),
+                    1 => (ArithmeticOperand::Static(arg(1)? as u64), 2),
+                    _ => return Err(CheatError::Unsupported(first)),
+                };
+                Ok((
+                    Opcode::Arithmetic {
+                        width,
+                        op: ArithmeticOp::decode(nibble(first, 2), first)?,
+                        destination: nibble(first, 3) as u8,
+                        source: nibble(first, 4) as u8,
+                        operand,
+                    },
+                    length,
+                ))
+            }
+            0xA => {
+                let x = nibble(first, 6);
+                let memory = || MemoryType::decode(x, first);
+                let (target, length) = match nibble(first, 5) {
+                    0 => (StoreTarget::Register, 1),
+                    1 => (StoreTarget::OffsetRegister(x as u8), 1),
+                    2 => (StoreTarget::Relative(short_address(first, arg(1)?)), 2),
+                    3 => (StoreTarget::MemoryRegister(memory()?), 1),
+                    4 => (
+                        StoreTarget::MemoryRelative(memory()?, short_address(first, arg(1)?)),
+                        2,
+                    ),
+                    5 => (
+                        StoreTarget::MemoryRegisterRelative(
+                            memory()?,
+                            short_address(first, arg(1)?),
+                        ),
+                        2,
+                    ),
+                    _ => return Err(CheatError::Unsupported(first)),
+                };
+                Ok((
+                    Opcode::StoreRegister {
+                        width: width(first)?,
+                        source: nibble(first, 2) as u8,
+                        address_register: nibble(first, 3) as u8,
+                        increment: nibble(first, 4) != 0,
+                        target,
+                    },
+                    length,
+                ))
+            }
+            0xC => match nibble(first, 1) {
+                0 => {
+                    let width = match nibble(first, 2) {
+                        w @ (1 | 2 | 4 | 8) => w as u8,
+                        _ => return Err(CheatError::Unsupported(first)),
+                    };
+                    let x = nibble(first, 6);
+                    let y = nibble(first, 7);
+                    let (operand, length) = match nibble(first, 5) {
+                        0 => (
+                            ConditionOperand::MemoryRelative(
+                                MemoryType::decode(x, first)?,
+                                short_address(first, arg(1)?),
+                            ),
+                            2,
+                        ),
+                        1 => (
+                            ConditionOperand::MemoryOffsetRegister(
+                                MemoryType::decode(x, first)?,
+                                y as u8,
+                            ),
+                            1,
+                        ),
+                        2 => (
+                            ConditionOperand::RegisterRelative(
+                                x as u8,
+                                short_address(first, arg(1)?),
+                            ),
+                            2,
+                        ),
+                        3 => (
+                            ConditionOperand::RegisterOffsetRegister(x as u8, y as u8),
+                            1,
+                        ),
+                        4 if width == 8 => (ConditionOperand::Static(wide(arg(1)?, arg(2)?)), 3),
+                        4 => (ConditionOperand::Static(arg(1)? as u64), 2),
+                        5 => (ConditionOperand::Register(x as u8), 1),
+                        _ => return Err(CheatError::Unsupported(first)),
+                    };
+                    Ok((
+                        Opcode::BeginRegisterConditional {
+                            width,
+                            condition: Condition::decode(nibble(first, 3), first)?,
+                            register: nibble(first, 4) as u8,
+                            operand,
+                        },
+                        length,
+                    ))
+                }
+                1 => Ok((
+                    Opcode::SaveRestoreRegister {
+                        destination: nibble(first, 3) as u8,
+                        source: nibble(first, 5) as u8,
+                        operation: SaveOperation::decode(nibble(first, 6), first)?,
+                    },
+                    1,
+                )),
+                2 => Ok((
+                    Opcode::SaveRestoreRegisterMask {
+                        operation: SaveOperation::decode(nibble(first, 2), first)?,
+                        mask: (first & 0xFFFF) as u16,
+                    },
+                    1,
+                )),
+                3 => Ok((
+                    Opcode::ReadWriteStaticRegister {
+                        static_register: ((first >> 4) & 0xFF) as u8,
+                        register: nibble(first, 7) as u8,
+                    },
+                    1,
+                )),
+                _ => Err(CheatError::Unsupported(first)),
+            },
+            0xF => match (first >> 20) & 0xFFF {
+                0xFF0 => Ok((Opcode::PauseProcess, 1)),
+                0xFF1 => Ok((Opcode::ResumeProcess, 1)),
+                _ => Err(CheatError::Unsupported(first)),
+            },
+            _ => Err(CheatError::Unsupported(first)),
+        }
+    }
+
+    /// Decodes every instruction in a cheat
+    pub fn decode_all(mut dwords: &[u32]) -> Result<Vec<Opcode>, CheatError> {
+        let mut opcodes = vec![];
+        while !dwords.is_empty() {
+            let (opcode, length) = Opcode::decode(dwords)?;
+            opcodes.push(opcode);
+            dwords = &dwords[length..];
+        }
+        Ok(opcodes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cheat::{
+        ArithmeticOp, ArithmeticOperand, CheatError, Condition, ConditionOperand, MemoryType,
+        Opcode, StoreTarget,
+    };
+
+    #[test]
+    fn should_decode_store_static_widths() {
+        assert_eq!(
+            Ok((
+                Opcode::StoreStatic {
+                    width: 4,
+                    memory: MemoryType::Heap,
+                    offset_register: 2,
+                    address: 0x12_0054D8F0,
+                    value: 0x63,
+                },
+                3
+            )),
+            Opcode::decode(&[0x04120012, 0x0054D8F0, 0x00000063])
+        );
+        assert_eq!(
+            Ok((
+                Opcode::StoreStatic {
+                    width: 8,
+                    memory: MemoryType::Main,
+                    offset_register: 0,
+                    address: 0x100,
+                    value: 0x1_00000002,
+                },
+                4
+            )),
+            Opcode::decode(&[0x08000000, 0x00000100, 0x00000001, 0x00000002])
+        );
+    }
+
+    #[test]
+    fn should_decode_pointer_chain() {
+        let opcodes = Opcode::decode_all(&[
+            0x580F0000, 0x01234567, 0x580F1000, 0x00000020, 0x780F0000, 0x00000010, 0x640F0000,
+            0x00000000, 0x0000270F,
+        ])
+        .unwrap();
+        assert_eq!(4, opcodes.len());
+        assert_eq!(
+            Opcode::LegacyArithmetic {
+                width: 8,
+                register: 0xF,
+                op: ArithmeticOp::Add,
+                value: 0x10
+            },
+            opcodes[2]
+        );
+    }
+
+    #[test]
+    fn should_decode_control_flow() {
+        let opcodes = Opcode::decode_all(&[
+            0x80000011, 0x30010000, 0x00000005, 0xC0421400, 0x00000064, 0x9422F100, 0x00000001,
+            0x31010000, 0x21000000, 0xA4F20200, 0x00000008, 0x20000000,
+        ])
+        .unwrap();
+        assert_eq!(
+            vec![
+                Opcode::BeginKeypressConditional { keys: 0x11 },
+                Opcode::LoopStart {
+                    register: 1,
+                    iterations: 5
+                },
+                Opcode::BeginRegisterConditional {
+                    width: 4,
+                    condition: Condition::GreaterOrEqual,
+                    register: 1,
+                    operand: ConditionOperand::Static(100),
+                },
+                Opcode::Arithmetic {
+                    width: 4,
+                    op: ArithmeticOp::Mul,
+                    destination: 2,
+                    source: 0xF,
+                    operand: ArithmeticOperand::Static(1),
+                },
+                Opcode::LoopEnd { register: 1 },
+                Opcode::Else,
+                Opcode::StoreRegister {
+                    width: 4,
+                    source: 0xF,
+                    address_register: 2,
+                    increment: false,
+                    target: StoreTarget::Relative(8),
+                },
+                Opcode::EndConditional,
+            ],
+            opcodes
+        );
+    }
+
+    #[test]
+    fn should_reject_truncated_and_unknown() {
+        assert_eq!(
+            Err(CheatError::Unsupported(0x04000000)),
+            Opcode::decode(&[0x04000000, 0x00001000])
+        );
+        assert_eq!(
+            Err(CheatError::Unsupported(0xB0000000)),
+            Opcode::decode(&[0xB0000000])
+        );
+    }
+}
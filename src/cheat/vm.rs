@@ -0,0 +1,669 @@
+use crate::cheat::cheat_file::CheatFile;
+use crate::cheat::error::CheatError;
+use crate::cheat::opcode::{
+    ArithmeticOperand, ConditionOperand, MemoryType, Opcode, SaveOperation, StoreTarget,
+};
+use crate::types::{Button, PeekArgs, PokeArgs, PokeData, Stick};
+use crate::SysBotClient;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The memory a [`CheatVm`] reads and writes
+///
+/// Addresses are absolute, with [`base`] giving the start of each memory type.
+///
+/// [`base`]: CheatMemory::base
+pub trait CheatMemory {
+    fn base(&mut self, memory: MemoryType) -> Result<u64, CheatError>;
+    fn read(&mut self, address: u64, size: usize) -> Result<Vec<u8>, CheatError>;
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), CheatError>;
+}
+
+/// Console memory accessed through a [`SysBotClient`], with the main and heap bases fetched once
+///
+/// [`SysBotClient`]: struct@crate::SysBotClient
+pub struct ClientMemory<'a> {
+    client: &'a SysBotClient,
+    main_base: Option<u64>,
+    heap_base: Option<u64>,
+}
+
+impl<'a> ClientMemory<'a> {
+    pub fn new(client: &'a SysBotClient) -> Self {
+        Self {
+            client,
+            main_base: None,
+            heap_base: None,
+        }
+    }
+}
+
+impl CheatMemory for ClientMemory<'_> {
+    fn base(&mut self, memory: MemoryType) -> Result<u64, CheatError> {
+        let cached = match memory {
+            MemoryType::Main => &mut self.main_base,
+            MemoryType::Heap => &mut self.heap_base,
+            MemoryType::Alias | MemoryType::Aslr => {
+                return Err(CheatError::Client(
+                    "Alias and ASLR memory are not available through sys-botbase",
+                ))
+            }
+        };
+        if let Some(base) = cached {
+            return Ok(*base);
+        }
+        let base = match memory {
+            MemoryType::Main => self.client.get_main_nso_base(),
+            _ => self.client.get_heap_base(),
+        }
+        .map_err(CheatError::Client)?;
+        *cached = Some(base);
+        Ok(base)
+    }
+
+    fn read(&mut self, address: u64, size: usize) -> Result<Vec<u8>, CheatError> {
+        self.client
+            .peek_absolute(PeekArgs {
+                addr: address,
+                size,
+            })
+            .map_err(CheatError::Client)
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), CheatError> {
+        self.client
+            .poke_absolute(PokeArgs {
+                addr: address,
+                data: PokeData::new(data.to_vec()),
+            })
+            .map_err(CheatError::Client)
+    }
+}
+
+/// Sparse memory held by the client, where unwritten bytes read as zero
+#[derive(Clone, Debug, Default)]
+pub struct InMemory {
+    bases: [u64; 4],
+    bytes: HashMap<u64, u8>,
+}
+
+impl InMemory {
+    pub fn new(main_base: u64, heap_base: u64) -> Self {
+        let mut memory = Self::default();
+        memory.bases[MemoryType::Main as usize] = main_base;
+        memory.bases[MemoryType::Heap as usize] = heap_base;
+        memory
+    }
+
+    pub fn set(&mut self, address: u64, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            self.bytes.insert(address.wrapping_add(i as u64), *byte);
+        }
+    }
+
+    pub fn get(&self, address: u64, size: usize) -> Vec<u8> {
+        (0..size as u64)
+            .map(|i| {
+                self.bytes
+                    .get(&address.wrapping_add(i))
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+impl CheatMemory for InMemory {
+    fn base(&mut self, memory: MemoryType) -> Result<u64, CheatError> {
+        Ok(self.bases[memory as usize])
+    }
+
+    fn read(&mut self, address: u64, size: usize) -> Result<Vec<u8>, CheatError> {
+        Ok(self.get(address, size))
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), CheatError> {
+        self.set(address, data);
+        Ok(())
+    }
+}
+
+/// Returns the bit of a button in the cheat VM key mask, if it has one
+fn key_bit(button: Button) -> Option<u32> {
+    let bit = match button {
+        Button::A => 0,
+        Button::B => 1,
+        Button::X => 2,
+        Button::Y => 3,
+        Button::STICK(Stick::LEFT) => 4,
+        Button::STICK(Stick::RIGHT) => 5,
+        Button::L => 6,
+        Button::R => 7,
+        Button::ZL => 8,
+        Button::ZR => 9,
+        Button::PLUS => 10,
+        Button::MINUS => 11,
+        Button::DLEFT => 12,
+        Button::DUP => 13,
+        Button::DRIGHT => 14,
+        Button::DDOWN => 15,
+        Button::HOME | Button::CAPTURE => return None,
+    };
+    Some(1 << bit)
+}
+
+fn mask(value: u64, width: u8) -> u64 {
+    match width {
+        8 => value,
+        w => value & ((1 << (w as u64 * 8)) - 1),
+    }
+}
+
+fn to_value(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len().min(8)].copy_from_slice(&bytes[..bytes.len().min(8)]);
+    u64::from_le_bytes(buf)
+}
+
+struct LoadedCheat {
+    name: String,
+    master: bool,
+    opcodes: Vec<Opcode>,
+    enabled: bool,
+}
+
+/// An interpreter for the Atmosphère cheat VM, running cheats from the client side
+///
+/// Each frame runs the master code followed by every enabled cheat, starting with cleared
+/// registers as Atmosphère does. Static registers and held keys persist between frames. Pausing
+/// and resuming the process are ignored, as sys-botbase cannot do either.
+///
+/// # Example
+///
+/// ```
+/// use sysbot_rs::cheat::{CheatFile, CheatVm, InMemory};
+/// use sysbot_rs::types::Button;
+/// let file = CheatFile::parse("[Hold A for 99 HP]\n80000001\n04000000 00000010 00000063\n20000000").unwrap();
+/// let mut vm = CheatVm::new(&file).unwrap();
+/// vm.enable("Hold A for 99 HP").unwrap();
+/// let mut memory = InMemory::new(0x8000000, 0x4000000);
+/// vm.set_keys([Button::A]);
+/// vm.step(&mut memory).unwrap();
+/// assert_eq!(vec![99, 0, 0, 0], memory.get(0x8000010, 4));
+/// ```
+pub struct CheatVm {
+    cheats: Vec<LoadedCheat>,
+    program: Vec<Opcode>,
+    registers: [u64; 16],
+    saved: [u64; 16],
+    static_registers: [u64; 256],
+    loop_tops: [usize; 16],
+    keys: u32,
+    max_instructions: usize,
+}
+
+impl CheatVm {
+    /// The default number of instructions a frame may execute before it is aborted
+    pub const DEFAULT_MAX_INSTRUCTIONS: usize = 1_000_000;
+
+    /// Decodes every cheat in the file, with only the master code enabled
+    pub fn new(file: &CheatFile) -> Result<Self, CheatError> {
+        let cheats = file
+            .cheats
+            .iter()
+            .map(|c| {
+                Ok(LoadedCheat {
+                    name: c.name.clone(),
+                    master: c.master,
+                    opcodes: Opcode::decode_all(&c.opcodes)?,
+                    enabled: c.master,
+                })
+            })
+            .collect::<Result<Vec<_>, CheatError>>()?;
+        let mut vm = Self {
+            cheats,
+            program: vec![],
+            registers: [0; 16],
+            saved: [0; 16],
+            static_registers: [0; 256],
+            loop_tops: [0; 16],
+            keys: 0,
+            max_instructions: Self::DEFAULT_MAX_INSTRUCTIONS,
+        };
+        vm.rebuild();
+        Ok(vm)
+    }
+
+    /// Sets how many instructions a frame may execute before failing, guarding against loops
+    /// that never end
+    pub fn with_max_instructions(mut self, max_instructions: usize) -> Self {
+        self.max_instructions = max_instructions;
+        self
+    }
+
+    fn rebuild(&mut self) {
+        let (master, rest): (Vec<_>, Vec<_>) = self
+            .cheats
+            .iter()
+            .filter(|c| c.enabled)
+            .partition(|c| c.master);
+        self.program = master
+            .into_iter()
+            .chain(rest)
+            .flat_map(|c| c.opcodes.iter().copied())
+            .collect();
+    }
+
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), CheatError> {
+        let cheat = self
+            .cheats
+            .iter_mut()
+            .find(|c| c.name == name)
+            .ok_or_else(|| CheatError::NotFound(name.to_string()))?;
+        cheat.enabled = enabled;
+        self.rebuild();
+        Ok(())
+    }
+
+    pub fn enable(&mut self, name: &str) -> Result<(), CheatError> {
+        self.set_enabled(name, true)
+    }
+
+    pub fn disable(&mut self, name: &str) -> Result<(), CheatError> {
+        self.set_enabled(name, false)
+    }
+
+    /// Sets the buttons held for keypress conditionals
+    pub fn set_keys(&mut self, buttons: impl IntoIterator<Item = Button>) {
+        self.keys = buttons
+            .into_iter()
+            .filter_map(key_bit)
+            .fold(0, |a, b| a | b);
+    }
+
+    /// The registers as left by the last frame
+    pub fn registers(&self) -> &[u64; 16] {
+        &self.registers
+    }
+
+    pub fn static_register(&self, index: u8) -> u64 {
+        self.static_registers[index as usize]
+    }
+
+    pub fn set_static_register(&mut self, index: u8, value: u64) {
+        self.static_registers[index as usize] = value;
+    }
+
+    /// Skips past the end of the block starting at `pc`, stopping after an else at the same depth
+    /// when `stop_at_else` is set
+    fn skip_block(&self, mut pc: usize, stop_at_else: bool) -> usize {
+        let mut depth = 0;
+        while pc < self.program.len() {
+            let opcode = self.program[pc];
+            pc += 1;
+            match opcode {
+                o if o.is_conditional() => depth += 1,
+                Opcode::Else if depth == 0 && stop_at_else => return pc,
+                Opcode::EndConditional if depth == 0 => return pc,
+                Opcode::EndConditional => depth -= 1,
+                _ => {}
+            }
+        }
+        pc
+    }
+
+    fn read(memory: &mut dyn CheatMemory, address: u64, width: u8) -> Result<u64, CheatError> {
+        Ok(to_value(&memory.read(address, width as usize)?))
+    }
+
+    fn write(
+        memory: &mut dyn CheatMemory,
+        address: u64,
+        value: u64,
+        width: u8,
+    ) -> Result<(), CheatError> {
+        memory.write(address, &value.to_le_bytes()[..width as usize])
+    }
+
+    /// Runs one frame of the master code and the enabled cheats
+    pub fn step(&mut self, memory: &mut dyn CheatMemory) -> Result<(), CheatError> {
+        self.registers = [0; 16];
+        self.saved = [0; 16];
+        self.loop_tops = [0; 16];
+        let mut pc = 0;
+        let mut executed = 0;
+        while pc < self.program.len() {
+            executed += 1;
+            if executed > self.max_instructions {
+                return Err(CheatError::InstructionLimit);
+            }
+            let opcode = self.program[pc];
+            pc += 1;
+            let r = &mut self.registers;
+            match opcode {
+                Opcode::StoreStatic {
+                    width,
+                    memory: memory_type,
+                    offset_register,
+                    address,
+                    value,
+                } => {
+                    let address = memory
+                        .base(memory_type)?
+                        .wrapping_add(address)
+                        .wrapping_add(r[offset_register as usize]);
+                    Self::write(memory, address, value, width)?;
+                }
+                Opcode::BeginConditional {
+                    width,
+                    memory: memory_type,
+                    condition,
+                    address,
+                    value,
+                } => {
+                    let address = memory.base(memory_type)?.wrapping_add(address);
+                    let actual = Self::read(memory, address, width)?;
+                    if !condition.holds(actual, mask(value, width)) {
+                        pc = self.skip_block(pc, true);
+                    }
+                }
+                Opcode::EndConditional => {}
+                Opcode::Else => pc = self.skip_block(pc, false),
+                Opcode::LoopStart {
+                    register,
+                    iterations,
+                } => {
+                    r[register as usize] = iterations as u64;
+                    self.loop_tops[register as usize] = pc;
+                }
+                Opcode::LoopEnd { register } => {
+                    let counter = &mut r[register as usize];
+                    *counter = counter.wrapping_sub(1);
+                    if *counter != 0 {
+                        pc = self.loop_tops[register as usize];
+                    }
+                }
+                Opcode::LoadRegisterStatic { register, value } => r[register as usize] = value,
+                Opcode::LoadRegisterMemory {
+                    width,
+                    memory: memory_type,
+                    register,
+                    from_register,
+                    address,
+                } => {
+                    let address = if from_register {
+                        r[register as usize].wrapping_add(address)
+                    } else {
+                        memory.base(memory_type)?.wrapping_add(address)
+                    };
+                    self.registers[register as usize] = Self::read(memory, address, width)?;
+                }
+                Opcode::StoreStaticToAddress {
+                    width,
+                    register,
+                    increment,
+                    offset_register,
+                    value,
+                } => {
+                    let offset = offset_register.map(|o| r[o as usize]).unwrap_or_default();
+                    let address = r[register as usize].wrapping_add(offset);
+                    if increment {
+                        r[register as usize] = r[register as usize].wrapping_add(width as u64);
+                    }
+                    Self::write(memory, address, value, width)?;
+                }
+                Opcode::LegacyArithmetic {
+                    width,
+                    register,
+                    op,
+                    value,
+                } => {
+                    let result = op.apply(r[register as usize], value as u64);
+                    r[register as usize] = mask(result, width);
+                }
+                Opcode::BeginKeypressConditional { keys } => {
+                    if self.keys & keys != keys {
+                        pc = self.skip_block(pc, true);
+                    }
+                }
+                Opcode::Arithmetic {
+                    width,
+                    op,
+                    destination,
+                    source,
+                    operand,
+                } => {
+                    let operand = match operand {
+                        ArithmeticOperand::Register(o) => r[o as usize],
+                        ArithmeticOperand::Static(v) => v,
+                    };
+                    let result = op.apply(r[source as usize], operand);
+                    r[destination as usize] = mask(result, width);
+                }
+                Opcode::StoreRegister {
+                    width,
+                    source,
+                    address_register,
+                    increment,
+                    target,
+                } => {
+                    let value = r[source as usize];
+                    let register = r[address_register as usize];
+                    let address = match target {
+                        StoreTarget::Register => register,
+                        StoreTarget::OffsetRegister(o) => register.wrapping_add(r[o as usize]),
+                        StoreTarget::Relative(a) => register.wrapping_add(a),
+                        StoreTarget::MemoryRegister(m) => memory.base(m)?.wrapping_add(register),
+                        StoreTarget::MemoryRelative(m, a) => memory.base(m)?.wrapping_add(a),
+                        StoreTarget::MemoryRegisterRelative(m, a) => {
+                            memory.base(m)?.wrapping_add(register).wrapping_add(a)
+                        }
+                    };
+                    if increment {
+                        self.registers[address_register as usize] =
+                            register.wrapping_add(width as u64);
+                    }
+                    Self::write(memory, address, value, width)?;
+                }
+                Opcode::BeginRegisterConditional {
+                    width,
+                    condition,
+                    register,
+                    operand,
+                } => {
+                    let operand = match operand {
+                        ConditionOperand::MemoryRelative(m, a) => {
+                            let address = memory.base(m)?.wrapping_add(a);
+                            Self::read(memory, address, width)?
+                        }
+                        ConditionOperand::MemoryOffsetRegister(m, o) => {
+                            let address = memory.base(m)?.wrapping_add(r[o as usize]);
+                            Self::read(memory, address, width)?
+                        }
+                        ConditionOperand::RegisterRelative(a, offset) => {
+                            let address = r[a as usize].wrapping_add(offset);
+                            Self::read(memory, address, width)?
+                        }
+                        ConditionOperand::RegisterOffsetRegister(a, o) => {
+                            let address = r[a as usize].wrapping_add(r[o as usize]);
+                            Self::read(memory, address, width)?
+                        }
+                        ConditionOperand::Static(v) => mask(v, width),
+                        ConditionOperand::Register(o) => mask(r[o as usize], width),
+                    };
+                    let value = mask(self.registers[register as usize], width);
+                    if !condition.holds(value, operand) {
+                        pc = self.skip_block(pc, true);
+                    }
+                }
+                Opcode::SaveRestoreRegister {
+                    destination,
+                    source,
+                    operation,
+                } => self.save_restore(operation, destination as usize, source as usize),
+                Opcode::SaveRestoreRegisterMask { operation, mask } => {
+                    for i in (0..16).filter(|i| mask & (1 << i) != 0) {
+                        self.save_restore(operation, i, i);
+                    }
+                }
+                Opcode::ReadWriteStaticRegister {
+                    static_register,
+                    register,
+                } => {
+                    if static_register < 0x80 {
+                        r[register as usize] = self.static_registers[static_register as usize];
+                    } else {
+                        self.static_registers[static_register as usize] = r[register as usize];
+                    }
+                }
+                Opcode::PauseProcess | Opcode::ResumeProcess => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn save_restore(&mut self, operation: SaveOperation, destination: usize, source: usize) {
+        match operation {
+            SaveOperation::Restore => self.registers[destination] = self.saved[source],
+            SaveOperation::Save => self.saved[destination] = self.registers[source],
+            SaveOperation::ClearSaved => self.saved[destination] = 0,
+            SaveOperation::ClearRegister => self.registers[destination] = 0,
+        }
+    }
+
+    /// Runs a frame every `tick` until `keep_running` returns false
+    ///
+    /// `keep_running` is called before each frame, and can update the held keys or static
+    /// registers.
+    pub fn run(
+        &mut self,
+        memory: &mut dyn CheatMemory,
+        tick: Duration,
+        mut keep_running: impl FnMut(&mut CheatVm) -> bool,
+    ) -> Result<(), CheatError> {
+        let mut next = Instant::now();
+        while keep_running(self) {
+            self.step(memory)?;
+            next += tick;
+            let now = Instant::now();
+            if next > now {
+                thread::sleep(next - now);
+            } else {
+                next = now;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cheat::{CheatError, CheatFile, CheatVm, InMemory};
+    use crate::types::Button;
+    use std::time::Duration;
+
+    const MAIN: u64 = 0x80000000;
+    const HEAP: u64 = 0x40000000;
+
+    fn vm(source: &str) -> CheatVm {
+        let file = CheatFile::parse(source).unwrap();
+        let mut vm = CheatVm::new(&file).unwrap();
+        for cheat in &file.cheats {
+            vm.enable(&cheat.name).unwrap();
+        }
+        vm
+    }
+
+    #[test]
+    fn should_follow_pointer_and_write() {
+        let mut memory = InMemory::new(MAIN, HEAP);
+        memory.set(MAIN + 0x100, &(HEAP + 0x2000).to_le_bytes());
+        let mut vm = vm("[HP]\n\
+             580F0000 00000100\n\
+             780F0000 00000010\n\
+             640F0000 00000000 000003E7\n");
+        vm.step(&mut memory).unwrap();
+        assert_eq!(vec![0xE7, 0x03, 0, 0], memory.get(HEAP + 0x2010, 4));
+    }
+
+    #[test]
+    fn should_branch_on_memory_conditions() {
+        let mut memory = InMemory::new(MAIN, HEAP);
+        let mut vm = vm("[Cap]\n\
+             14110000 00000010 00000064\n\
+             04100000 00000010 00000064\n\
+             21000000\n\
+             02100000 00000012 00000001\n\
+             20000000\n");
+        memory.set(HEAP + 0x10, &500u32.to_le_bytes());
+        vm.step(&mut memory).unwrap();
+        assert_eq!(100u32.to_le_bytes().to_vec(), memory.get(HEAP + 0x10, 4));
+        assert_eq!(vec![0, 0], memory.get(HEAP + 0x12, 2));
+        vm.step(&mut memory).unwrap();
+        assert_eq!(vec![1, 0], memory.get(HEAP + 0x12, 2));
+    }
+
+    #[test]
+    fn should_run_loops_with_arithmetic() {
+        let mut memory = InMemory::new(MAIN, HEAP);
+        let mut vm = vm("[Fill]\n\
+             400E0000 00000000 40000100\n\
+             30000000 00000003\n\
+             94011100 00000001\n\
+             A41E1000\n\
+             31000000\n");
+        vm.step(&mut memory).unwrap();
+        assert_eq!(
+            vec![1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0],
+            memory.get(HEAP + 0x100, 12)
+        );
+    }
+
+    #[test]
+    fn should_respect_held_keys() {
+        let mut memory = InMemory::new(MAIN, HEAP);
+        let mut vm = vm("[Combo]\n\
+             80000041\n\
+             01000000 00000020 00000007\n\
+             20000000\n");
+        vm.set_keys([Button::A]);
+        vm.step(&mut memory).unwrap();
+        assert_eq!(vec![0], memory.get(MAIN + 0x20, 1));
+        vm.set_keys([Button::A, Button::L, Button::HOME]);
+        vm.step(&mut memory).unwrap();
+        assert_eq!(vec![7], memory.get(MAIN + 0x20, 1));
+    }
+
+    #[test]
+    fn should_persist_static_registers() {
+        let mut memory = InMemory::new(MAIN, HEAP);
+        let mut vm = vm("[Counter]\n\
+             C3000012\n\
+             94022100 00000001\n\
+             C3000802\n");
+        vm.set_static_register(0x01, 41);
+        vm.step(&mut memory).unwrap();
+        assert_eq!(42, vm.static_register(0x80));
+    }
+
+    #[test]
+    fn should_stop_runaway_loops_and_run_on_tick() {
+        let mut memory = InMemory::new(MAIN, HEAP);
+        let mut forever =
+            vm("[Forever]\n30000000 00000000\n31000000\n").with_max_instructions(1000);
+        assert_eq!(Err(CheatError::InstructionLimit), forever.step(&mut memory));
+
+        let mut vm = vm("[Count]\n\
+             54000000 00000000\n\
+             94000100 00000001\n\
+             A4000400 00000000\n");
+        let mut frames = 0;
+        vm.run(&mut memory, Duration::from_millis(1), |_| {
+            frames += 1;
+            frames <= 5
+        })
+        .unwrap();
+        assert_eq!(5u32.to_le_bytes().to_vec(), memory.get(MAIN, 4));
+    }
+}
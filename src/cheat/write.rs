@@ -0,0 +1,285 @@
+use crate::cheat::cheat_file::{Cheat, CheatFile};
+use crate::cheat::error::CheatError;
+use crate::cheat::opcode::{ArithmeticOp, MemoryType, Opcode};
+use crate::types::{PokeArgs, PokeData};
+use crate::SysBotClient;
+
+/// A memory write that a cheat translates to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheatWrite {
+    /// Written with `pokeMain` at an offset from the main NSO
+    Main { offset: u64, data: Vec<u8> },
+    /// Written with `poke` at an offset from the heap
+    Heap { offset: u64, data: Vec<u8> },
+    /// Written with `pointerPoke` at the end of a pointer chain from main
+    Pointer { jumps: Vec<u64>, data: Vec<u8> },
+}
+
+/// What is known about a register while translating a cheat
+#[derive(Clone, Debug)]
+enum Register {
+    Value(u64),
+    /// The value at the end of `jumps` followed from main, plus `offset`
+    Pointer {
+        jumps: Vec<u64>,
+        offset: u64,
+    },
+    Unknown,
+}
+
+fn data(value: u64, width: u8) -> Vec<u8> {
+    value.to_le_bytes()[..width as usize].to_vec()
+}
+
+impl Cheat {
+    /// Translates the cheat into memory writes
+    ///
+    /// Only static writes to main or heap, and pointer chains loaded from main and written through
+    /// a register, can be translated. Conditionals, loops and the remaining opcodes fail with
+    /// [`CheatError::Unsupported`].
+    pub fn writes(&self) -> Result<Vec<CheatWrite>, CheatError> {
+        let mut registers = vec![Register::Value(0); 16];
+        let mut writes = vec![];
+        let mut dwords = self.opcodes.as_slice();
+        while !dwords.is_empty() {
+            let first = dwords[0];
+            let unsupported = || CheatError::Unsupported(first);
+            let (opcode, length) = Opcode::decode(dwords)?;
+            dwords = &dwords[length..];
+            match opcode {
+                Opcode::StoreStatic {
+                    width,
+                    memory,
+                    offset_register,
+                    address,
+                    value,
+                } => {
+                    let Register::Value(base) = registers[offset_register as usize] else {
+                        return Err(unsupported());
+                    };
+                    let offset = address.wrapping_add(base);
+                    let data = data(value, width);
+                    writes.push(match memory {
+                        MemoryType::Main => CheatWrite::Main { offset, data },
+                        MemoryType::Heap => CheatWrite::Heap { offset, data },
+                        _ => return Err(unsupported()),
+                    });
+                }
+                Opcode::LoadRegisterStatic { register, value } => {
+                    registers[register as usize] = Register::Value(value);
+                }
+                Opcode::LoadRegisterMemory {
+                    width,
+                    memory,
+                    register,
+                    from_register,
+                    address,
+                } => {
+                    let loaded = match (&registers[register as usize], from_register) {
+                        _ if width != 8 => Register::Unknown,
+                        (_, false) if memory == MemoryType::Main => Register::Pointer {
+                            jumps: vec![address],
+                            offset: 0,
+                        },
+                        (Register::Pointer { jumps, offset }, true) => {
+                            let mut jumps = jumps.clone();
+                            jumps.push(offset.wrapping_add(address));
+                            Register::Pointer { jumps, offset: 0 }
+                        }
+                        _ => Register::Unknown,
+                    };
+                    registers[register as usize] = loaded;
+                }
+                Opcode::StoreStaticToAddress {
+                    width,
+                    register,
+                    increment,
+                    offset_register,
+                    value,
+                } => {
+                    let extra = match offset_register.map(|r| &registers[r as usize]) {
+                        None => 0,
+                        Some(Register::Value(v)) => *v,
+                        Some(_) => return Err(unsupported()),
+                    };
+                    let Register::Pointer { jumps, offset } = &mut registers[register as usize]
+                    else {
+                        return Err(unsupported());
+                    };
+                    let mut chain = jumps.clone();
+                    chain.push(offset.wrapping_add(extra));
+                    writes.push(CheatWrite::Pointer {
+                        jumps: chain,
+                        data: data(value, width),
+                    });
+                    if increment {
+                        *offset = offset.wrapping_add(width as u64);
+                    }
+                }
+                Opcode::LegacyArithmetic {
+                    register,
+                    op,
+                    value,
+                    ..
+                } => {
+                    let value = value as u64;
+                    let updated = match (&registers[register as usize], op) {
+                        (Register::Value(v), op) => Register::Value(op.apply(*v, value)),
+                        (Register::Pointer { jumps, offset }, ArithmeticOp::Add) => {
+                            Register::Pointer {
+                                jumps: jumps.clone(),
+                                offset: offset.wrapping_add(value),
+                            }
+                        }
+                        (Register::Pointer { jumps, offset }, ArithmeticOp::Sub) => {
+                            Register::Pointer {
+                                jumps: jumps.clone(),
+                                offset: offset.wrapping_sub(value),
+                            }
+                        }
+                        _ => Register::Unknown,
+                    };
+                    registers[register as usize] = updated;
+                }
+                _ => return Err(unsupported()),
+            }
+        }
+        Ok(writes)
+    }
+}
+
+impl CheatFile {
+    /// Applies the master code, if any, and then the named cheat.
+    ///
+    /// When the file has a build ID it is checked against the running program before anything is
+    /// written, and every cheat is translated before the first write is sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client to write memory with
+    /// * `name` - The name of the cheat to apply
+    pub fn apply(&self, client: &SysBotClient, name: &str) -> Result<(), CheatError> {
+        let cheat = self
+            .get(name)
+            .ok_or_else(|| CheatError::NotFound(name.to_string()))?;
+        if let Some(expected) = self.build_id {
+            let actual = client.get_build_id().map_err(CheatError::Client)?;
+            if expected != actual {
+                return Err(CheatError::BuildMismatch { expected, actual });
+            }
+        }
+        let mut writes = match self.master() {
+            Some(master) if !cheat.master => master.writes()?,
+            _ => vec![],
+        };
+        writes.extend(cheat.writes()?);
+        for write in writes {
+            match write {
+                CheatWrite::Main { offset, data } => client.poke_main(PokeArgs {
+                    addr: offset,
+                    data: PokeData::new(data),
+                }),
+                CheatWrite::Heap { offset, data } => client.poke(PokeArgs {
+                    addr: offset,
+                    data: PokeData::new(data),
+                }),
+                CheatWrite::Pointer { jumps, data } => {
+                    client.pointer_poke(&jumps, PokeData::new(data))
+                }
+            }
+            .map_err(CheatError::Client)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cheat::{Cheat, CheatError, CheatFile, CheatWrite};
+    use crate::test_server::TestServer;
+    use crate::SysBotClient;
+
+    const SOURCE: &str = "[Money]\n\
+        04000000 0054D8F0 0098967F\n\
+        [Pointer HP]\n\
+        580F0000 01234567\n\
+        580F1000 00000020\n\
+        780F0000 00000010\n\
+        620F1000 00000000 000003E7\n\
+        620F0000 00000000 000003E7\n\
+        [Conditional]\n\
+        14000000 0054D8F0 00000001\n\
+        20000000\n";
+
+    fn cheat(name: &str) -> Cheat {
+        CheatFile::parse(SOURCE).unwrap().get(name).unwrap().clone()
+    }
+
+    #[test]
+    fn should_translate_static_write() {
+        assert_eq!(
+            Ok(vec![CheatWrite::Main {
+                offset: 0x54D8F0,
+                data: vec![0x7F, 0x96, 0x98, 0x00]
+            }]),
+            cheat("Money").writes()
+        );
+    }
+
+    #[test]
+    fn should_translate_pointer_write() {
+        assert_eq!(
+            Ok(vec![
+                CheatWrite::Pointer {
+                    jumps: vec![0x1234567, 0x20, 0x10],
+                    data: vec![0xE7, 0x03]
+                },
+                CheatWrite::Pointer {
+                    jumps: vec![0x1234567, 0x20, 0x12],
+                    data: vec![0xE7, 0x03]
+                },
+            ]),
+            cheat("Pointer HP").writes()
+        );
+    }
+
+    #[test]
+    fn should_reject_conditionals() {
+        assert_eq!(
+            Err(CheatError::Unsupported(0x14000000)),
+            cheat("Conditional").writes()
+        );
+    }
+
+    #[test]
+    fn should_apply_for_matching_build() {
+        let server = TestServer::start(|command| {
+            (command == "getBuildID").then(|| "8BB2C0E09AA48A0D\n".to_string())
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let mut file = CheatFile::parse(SOURCE).unwrap();
+        file.build_id = Some(0x8BB2C0E09AA48A0D);
+        file.apply(&client, "Money").unwrap();
+        file.apply(&client, "Pointer HP").unwrap();
+        file.build_id = Some(0x0123456789ABCDEF);
+        assert_eq!(
+            Err(CheatError::BuildMismatch {
+                expected: 0x0123456789ABCDEF,
+                actual: 0x8BB2C0E09AA48A0D
+            }),
+            file.apply(&client, "Money")
+        );
+        drop(client);
+        assert_eq!(
+            vec![
+                "getBuildID",
+                "pokeMain 0x54D8F0 0x7F969800",
+                "getBuildID",
+                "pointerPoke 0xE703 0x1234567 0x20 0x10",
+                "pointerPoke 0xE703 0x1234567 0x20 0x12",
+                "getBuildID"
+            ],
+            server.finish()
+        );
+    }
+}
@@ -1,15 +1,19 @@
-use crate::types::thread_message::ThreadMessage;
+use crate::protocol;
+use crate::protocol::Capabilities;
+use crate::types::thread_message::{ThreadMessage, READ_LINE};
 use crate::types::{
-    Button, ConfigureOption, PeekArgs, PokeArgs, PokeData, SeqParam, Stick, StickMovement,
+    Button, ConfigureOption, ControllerState, MemoryValue, PeekArgs, PokeArgs, PokeData, Region,
+    RunningProgram, SeqParam, Sequence, Stick, StickMovement,
 };
 use std::io::{Read, Write};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
 use std::str::FromStr;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::sync::{Mutex, MutexGuard};
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// A client that sends and receives data from a sys-botbase server
 ///
@@ -21,9 +25,16 @@ pub struct SysBotClient {
     sender: SyncSender<ThreadMessage>,
     receiver: Receiver<Vec<u8>>,
     worker: Option<JoinHandle<()>>,
+    controller: Mutex<ControllerState>,
+    capabilities: Option<Capabilities>,
 }
 
 impl SysBotClient {
+    /// How long [`connect_negotiated_with`] waits for the answer to `getVersion`
+    ///
+    /// [`connect_negotiated_with`]: fn@crate::SysBotClient::connect_negotiated_with
+    pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
     /// Creates and connects a SysBotClient to a TcpStream in a concurrent thread.
     ///
     /// # Arguments
@@ -45,14 +56,87 @@ impl SysBotClient {
     /// }
     /// ```
     pub fn connect(addr: &str, port: u16) -> Result<Self, &'static str> {
+        Ok(SysBotClient::start(SysBotClient::open(addr, port)?, None))
+    }
+
+    /// Connects like [`connect`], then asks sys-botbase for its version so that commands the
+    /// version does not support, going by `versions`, fail straight away with
+    /// [`protocol::UNSUPPORTED`] instead of waiting for a response that never comes.
+    ///
+    /// Connecting fails if sys-botbase does not answer `getVersion` with a whole line within
+    /// [`HANDSHAKE_TIMEOUT`](Self::HANDSHAKE_TIMEOUT), since a late answer would be read as the
+    /// response to the next command. It also fails if `versions` is empty or the version cannot
+    /// be parsed, as described in [`Capabilities::with_versions`]. Use [`connect`] for a
+    /// sys-botbase too old to have `getVersion`.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - A string slice representing an IPv4 address
+    /// * `port` - A port number for the specified address
+    /// * `versions` - The `(major, minor)` version that each command first appeared in, which
+    ///   must not be empty
+    ///
+    /// [`connect`]: fn@crate::SysBotClient::connect
+    pub fn connect_negotiated_with(
+        addr: &str,
+        port: u16,
+        versions: &[(&'static str, (u32, u32))],
+    ) -> Result<Self, &'static str> {
+        if versions.is_empty() {
+            return Err(protocol::NO_COMMAND_VERSIONS);
+        }
+        let mut tcp_stream = SysBotClient::open(addr, port)?;
+        let version = SysBotClient::negotiate(&mut tcp_stream, SysBotClient::HANDSHAKE_TIMEOUT)?;
+        let capabilities = Capabilities::with_versions(&version, versions)?;
+        Ok(SysBotClient::start(tcp_stream, Some(capabilities)))
+    }
+
+    /// Returns the capabilities found by [`connect_negotiated_with`], or `None` if the client was
+    /// connected without negotiating.
+    ///
+    /// [`connect_negotiated_with`]: fn@crate::SysBotClient::connect_negotiated_with
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
+    }
+
+    fn open(addr: &str, port: u16) -> Result<TcpStream, &'static str> {
         let socket_addr = SocketAddr::new(
             IpAddr::V4(Ipv4Addr::from_str(addr).map_err(|_| "Failed to convert ip address")?),
             port,
         );
+        TcpStream::connect_timeout(&socket_addr, Duration::from_secs(5))
+            .map_err(|_| protocol::CONNECT_FAILED)
+    }
+
+    /// Asks for the version on the stream before the worker owns it, so that the read can time
+    /// out. A stream that times out still has the request outstanding, so it must be dropped.
+    fn negotiate(tcp_stream: &mut TcpStream, timeout: Duration) -> Result<String, &'static str> {
+        tcp_stream
+            .set_read_timeout(Some(timeout))
+            .map_err(|_| protocol::CONNECT_FAILED)?;
+        tcp_stream
+            .write_all(b"getVersion\r\n")
+            .and_then(|_| tcp_stream.flush())
+            .map_err(|_| protocol::SEND_FAILED)?;
+        let mut buf = vec![];
+        let mut chunk = [0; 64];
+        while buf.last() != Some(&b'\n') {
+            match tcp_stream.read(&mut chunk) {
+                Ok(0) => return Err(protocol::RECEIVE_FAILED),
+                Ok(read) => buf.extend_from_slice(&chunk[..read]),
+                Err(_) => return Err(protocol::RECEIVE_FAILED),
+            }
+        }
+        tcp_stream
+            .set_read_timeout(None)
+            .map_err(|_| protocol::CONNECT_FAILED)?;
+        String::from_utf8(buf).map_err(|_| "Failed to parse response to string")
+    }
+
+    fn start(tcp_stream: TcpStream, capabilities: Option<Capabilities>) -> Self {
         let (sender_in, receiver_in): (SyncSender<ThreadMessage>, Receiver<ThreadMessage>) =
             mpsc::sync_channel(0);
         let (sender_out, receiver_out): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
-        let tcp_stream = TcpStream::connect_timeout(&socket_addr, Duration::from_secs(5)).map_err(|_| "Failed to connect to TcpStream")?;
         let worker = Some(thread::spawn(move || {
             let mut tcp_stream = tcp_stream;
             let sender_out = sender_out;
@@ -69,9 +153,26 @@ impl SysBotClient {
                     if message.returns {
                         if message.size == 0 {
                             let mut buf = vec![0; 100];
-                            tcp_stream
+                            let read = tcp_stream
                                 .read(&mut buf)
                                 .expect("Failed to read from stream");
+                            buf.truncate(read);
+                            sender_out
+                                .clone()
+                                .send(buf)
+                                .expect("Failed to send response over channel");
+                        } else if message.size == READ_LINE {
+                            let mut buf = vec![];
+                            let mut chunk = [0; 4096];
+                            while buf.last() != Some(&b'\n') {
+                                let read = tcp_stream
+                                    .read(&mut chunk)
+                                    .expect("Failed to read from stream");
+                                if read == 0 {
+                                    break;
+                                }
+                                buf.extend_from_slice(&chunk[..read]);
+                            }
                             sender_out
                                 .clone()
                                 .send(buf)
@@ -91,27 +192,43 @@ impl SysBotClient {
             }
         }));
 
-        Ok(Self {
+        Self {
             sender: sender_in,
             receiver: receiver_out,
             worker,
-        })
+            controller: Mutex::new(ControllerState::default()),
+            capabilities,
+        }
+    }
+
+    /// Returns whether an error returned by the client means the connection is unusable, rather
+    /// than the console failing a single command.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - An error returned by one of the client's methods
+    pub fn is_connection_error(error: &str) -> bool {
+        protocol::CONNECTION_ERRORS.contains(&error)
     }
 
     fn receive(&self) -> Result<Vec<u8>, &'static str> {
-        self.receiver
-            .recv()
-            .map_err(|_| "Failed to receive a response")
+        self.receiver.recv().map_err(|_| protocol::RECEIVE_FAILED)
     }
 
     fn check_connected(&self) -> Result<(), &'static str> {
         if self.worker.is_none() {
-            Err("SysBotClient not connected")
+            Err(protocol::NOT_CONNECTED)
         } else {
             Ok(())
         }
     }
 
+    fn controller(&self) -> MutexGuard<'_, ControllerState> {
+        self.controller
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
     fn send(
         &self,
         command: String,
@@ -119,6 +236,11 @@ impl SysBotClient {
         close: bool,
         size: usize,
     ) -> Result<(), &'static str> {
+        if let Some(capabilities) = &self.capabilities {
+            if !capabilities.supports(&command) {
+                return Err(protocol::UNSUPPORTED);
+            }
+        }
         self.sender
             .send(ThreadMessage {
                 message: command + "\r\n",
@@ -126,7 +248,7 @@ impl SysBotClient {
                 close,
                 size,
             })
-            .map_err(|_| "Failed to send command")
+            .map_err(|_| protocol::SEND_FAILED)
     }
 
     fn hex_string_to_vec(string_bytes: Vec<u8>) -> Vec<u8> {
@@ -213,21 +335,135 @@ impl SysBotClient {
         Ok(SysBotClient::hex_string_to_vec(self.receive()?))
     }
 
+    /// Peeks at a heap address until its bytes satisfy `predicate`, returning the final bytes.
+    ///
+    /// Fails if the predicate is still unsatisfied once `timeout` has passed.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The address and number of bytes to read
+    /// * `predicate` - Checks the bytes read on each poll
+    /// * `timeout` - How long to keep polling for, where [`Duration::MAX`] polls forever
+    /// * `poll_interval` - How long to wait between polls
+    pub fn wait_until(
+        &self,
+        args: PeekArgs,
+        mut predicate: impl FnMut(&[u8]) -> bool,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<Vec<u8>, &'static str> {
+        // A timeout too long to add to the current time never runs out
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let mut bytes = self.peek(args.clone())?;
+            bytes.truncate(args.size);
+            if predicate(&bytes) {
+                return Ok(bytes);
+            }
+            let now = Instant::now();
+            match deadline {
+                Some(deadline) if now >= deadline => return Err("Timed out waiting for memory"),
+                Some(deadline) => thread::sleep(poll_interval.min(deadline - now)),
+                None => thread::sleep(poll_interval),
+            }
+        }
+    }
+
+    /// Peeks at a heap address until the value there satisfies `predicate`, returning the final
+    /// value.
+    pub fn wait_until_value<T: MemoryValue>(
+        &self,
+        addr: u64,
+        mut predicate: impl FnMut(T) -> bool,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<T, &'static str> {
+        let bytes = self.wait_until(
+            PeekArgs {
+                addr,
+                size: T::SIZE,
+            },
+            |bytes| T::from_le_slice(bytes).is_some_and(&mut predicate),
+            timeout,
+            poll_interval,
+        )?;
+        T::from_le_slice(&bytes).ok_or("Failed to parse bytes to value")
+    }
+
+    /// Peeks at a heap address until it holds `expected`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use sysbot_rs::SysBotClient;
+    /// let client = SysBotClient::connect("0.0.0.0", 6000).unwrap();
+    /// client
+    ///     .wait_for_value::<u32>(0x8A3F7C, 1, Duration::from_secs(10), Duration::from_millis(100))
+    ///     .expect("battle never started");
+    /// ```
+    pub fn wait_for_value<T: MemoryValue + PartialEq>(
+        &self,
+        addr: u64,
+        expected: T,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<T, &'static str> {
+        self.wait_until_value(addr, |value| value == expected, timeout, poll_interval)
+    }
+
+    /// Peeks at an address relative to the given [`Region`].
+    ///
+    /// [`Region`]: enum@crate::types::Region
+    pub fn peek_region(&self, region: Region, args: PeekArgs) -> Result<Vec<u8>, &'static str> {
+        match region {
+            Region::Heap => self.peek(args),
+            Region::Main => self.peek_main(args),
+            Region::Absolute => self.peek_absolute(args),
+        }
+    }
+
+    /// Peeks at several addresses relative to the given [`Region`] with a single command.
+    ///
+    /// [`Region`]: enum@crate::types::Region
+    pub fn peek_region_multi(
+        &self,
+        region: Region,
+        args: Vec<PeekArgs>,
+    ) -> Result<Vec<u8>, &'static str> {
+        match region {
+            Region::Heap => self.peek_multi(args),
+            Region::Main => self.peek_main_multi(args),
+            Region::Absolute => self.peek_absolute_multi(args),
+        }
+    }
+
+    /// Pokes an address relative to the given [`Region`].
+    ///
+    /// [`Region`]: enum@crate::types::Region
+    pub fn poke_region(&self, region: Region, args: PokeArgs) -> Result<(), &'static str> {
+        match region {
+            Region::Heap => self.poke(args),
+            Region::Main => self.poke_main(args),
+            Region::Absolute => self.poke_absolute(args),
+        }
+    }
+
     pub fn poke(&self, args: PokeArgs) -> Result<(), &'static str> {
         self.check_connected()?;
-        let command = format!("poke 0x{:X} {}", args.addr, args.data.to_string());
+        let command = format!("poke 0x{:X} {}", args.addr, args.data);
         self.send(command, false, false, 0)
     }
 
     pub fn poke_absolute(&self, args: PokeArgs) -> Result<(), &'static str> {
         self.check_connected()?;
-        let command = format!("pokeAbsolute 0x{:X} {}", args.addr, args.data.to_string());
+        let command = format!("pokeAbsolute 0x{:X} {}", args.addr, args.data);
         self.send(command, false, false, 0)
     }
 
     pub fn poke_main(&self, args: PokeArgs) -> Result<(), &'static str> {
         self.check_connected()?;
-        let command = format!("pokeMain 0x{:X} {}", args.addr, args.data.to_string());
+        let command = format!("pokeMain 0x{:X} {}", args.addr, args.data);
         self.send(command, false, false, 0)
     }
 
@@ -239,47 +475,175 @@ impl SysBotClient {
 
     pub fn click_seq(&self, args: Vec<SeqParam>) -> Result<(), &'static str> {
         self.check_connected()?;
+        let mut state = self.controller().clone();
         let args = args
             .into_iter()
-            .map(|a| a.to_string())
+            .map(|a| {
+                state.update(&a);
+                a.to_string()
+            })
             .collect::<Vec<String>>()
             .join(",");
         let command = format!("clickSeq {}", args);
-        self.send(command, false, false, 0)
+        self.send(command, false, false, 0)?;
+        *self.controller() = state;
+        Ok(())
     }
 
+    /// Sends a [`Sequence`] as one or more `clickSeq` commands.
+    ///
+    /// `clickSeq` runs asynchronously on the console, so when the sequence is split the client
+    /// waits for each chunk's estimated duration (at the default `buttonClickSleepTime`) before
+    /// sending the next one.
+    ///
+    /// [`Sequence`]: struct@crate::types::Sequence
+    pub fn run_sequence(&self, sequence: &Sequence) -> Result<(), &'static str> {
+        self.check_connected()?;
+        let mut chunks = sequence.chunks()?.into_iter().peekable();
+        while let Some(chunk) = chunks.next() {
+            let duration = chunk
+                .iter()
+                .fold(Sequence::new(), |sequence, param| sequence.then(*param))
+                .estimated_duration(Sequence::DEFAULT_CLICK_SLEEP_TIME);
+            self.click_seq(chunk)?;
+            if chunks.peek().is_some() {
+                thread::sleep(duration);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a [`Sequence`] with individual commands, waiting on the client side, for servers
+    /// without `clickSeq`.
+    ///
+    /// [`Sequence`]: struct@crate::types::Sequence
+    pub fn run_sequence_individually(&self, sequence: &Sequence) -> Result<(), &'static str> {
+        self.check_connected()?;
+        sequence.validate()?;
+        for param in sequence.params() {
+            match *param {
+                SeqParam::Click(b) => self.click(b)?,
+                SeqParam::Press(b) => self.press(b)?,
+                SeqParam::Release(b) => self.release(b)?,
+                SeqParam::MoveLeft(mv) => self.set_stick(Stick::LEFT, mv)?,
+                SeqParam::MoveRight(mv) => self.set_stick(Stick::RIGHT, mv)?,
+                SeqParam::Wait(t) => thread::sleep(Duration::from_millis(t as u64)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops a running `clickSeq`.
+    ///
+    /// The tracked controller state is reset to neutral, because how much of the sequence ran
+    /// is unknown. Use [`release_all`] to put the controller itself back in a known state.
+    ///
+    /// [`release_all`]: fn@crate::SysBotClient::release_all
     pub fn click_cancel(&self) -> Result<(), &'static str> {
         self.check_connected()?;
         let command = "clickCancel".to_string();
-        self.send(command, false, false, 0)
+        self.send(command, false, false, 0)?;
+        *self.controller() = ControllerState::default();
+        Ok(())
     }
 
     pub fn press(&self, button: Button) -> Result<(), &'static str> {
         self.check_connected()?;
         let command = format!("press {}", button);
-        self.send(command, false, false, 0)
+        self.send(command, false, false, 0)?;
+        self.controller().hold(button);
+        Ok(())
     }
 
     pub fn release(&self, button: Button) -> Result<(), &'static str> {
         self.check_connected()?;
         let command = format!("release {}", button);
-        self.send(command, false, false, 0)
+        self.send(command, false, false, 0)?;
+        self.controller().release(button);
+        Ok(())
     }
 
-    pub fn set_stick(&self, stick: Stick, movement: StickMovement) -> Result<(), &'static str> {
+    pub fn set_stick(
+        &self,
+        stick: Stick,
+        movement: impl Into<StickMovement>,
+    ) -> Result<(), &'static str> {
         self.check_connected()?;
+        let movement = movement.into();
         let command = format!(
             "setStick {} {}",
             stick,
             movement.to_string().replace(',', " ")
         );
-        self.send(command, false, false, 0)
+        self.send(command, false, false, 0)?;
+        self.controller().set_stick(stick, movement);
+        Ok(())
+    }
+
+    /// Tilts a stick for a duration and then centres it again.
+    ///
+    /// # Arguments
+    ///
+    /// * `stick` - The stick to tilt
+    /// * `movement` - The position to hold the stick at, such as a [`Direction`]
+    /// * `duration` - How long to hold the stick before centring it
+    ///
+    /// [`Direction`]: enum@crate::types::Direction
+    pub fn tilt(
+        &self,
+        stick: Stick,
+        movement: impl Into<StickMovement>,
+        duration: Duration,
+    ) -> Result<(), &'static str> {
+        self.set_stick(stick, movement)?;
+        thread::sleep(duration);
+        self.set_stick(stick, StickMovement::CENTER)
+    }
+
+    /// Returns the controller state the client has put the controller in.
+    pub fn controller_state(&self) -> ControllerState {
+        self.controller().clone()
+    }
+
+    /// Moves the controller to `state` by sending only the presses, releases and stick movements
+    /// that differ from the tracked state.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The target state of the controller
+    pub fn apply(&self, state: &ControllerState) -> Result<(), &'static str> {
+        self.check_connected()?;
+        let commands = self.controller().diff(state);
+        for command in commands {
+            match command {
+                SeqParam::Press(b) => self.press(b)?,
+                SeqParam::Release(b) => self.release(b)?,
+                SeqParam::MoveLeft(mv) => self.set_stick(Stick::LEFT, mv)?,
+                SeqParam::MoveRight(mv) => self.set_stick(Stick::RIGHT, mv)?,
+                SeqParam::Click(_) | SeqParam::Wait(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Releases every button and centres both sticks, regardless of the tracked state.
+    ///
+    /// Useful for recovering a controller left mid-hold by a previous client.
+    pub fn release_all(&self) -> Result<(), &'static str> {
+        self.check_connected()?;
+        for button in Button::ALL {
+            self.release(button)?;
+        }
+        self.set_stick(Stick::LEFT, StickMovement::CENTER)?;
+        self.set_stick(Stick::RIGHT, StickMovement::CENTER)
     }
 
     pub fn detach_controller(&self) -> Result<(), &'static str> {
         self.check_connected()?;
         let command = "detachController".to_string();
-        self.send(command, false, false, 0)
+        self.send(command, false, false, 0)?;
+        *self.controller() = ControllerState::default();
+        Ok(())
     }
 
     pub fn configure(&self, option: ConfigureOption) -> Result<(), &'static str> {
@@ -345,12 +709,28 @@ impl SysBotClient {
         ))
     }
 
-    pub fn is_program_running(&self) -> Result<bool, &'static str> {
+    /// Checks whether the program with the given title ID is currently running.
+    ///
+    /// # Arguments
+    ///
+    /// * `title_id` - The title ID of the program to check for
+    pub fn is_program_running(&self, title_id: u64) -> Result<bool, &'static str> {
         self.check_connected()?;
-        let command = "getHeapBase".to_string();
+        let command = format!("isProgramRunning 0x{:016X}", title_id);
         self.send(command, true, false, 2)?;
-        let string_bytes = self.receive()?;
-        Ok(string_bytes[0] != 0)
+        match self.receive()?.first() {
+            Some(b'0') => Ok(false),
+            Some(b'1') => Ok(true),
+            _ => Err("Failed to parse response to bool"),
+        }
+    }
+
+    /// Returns the title ID and build ID of the program currently running.
+    pub fn running_title(&self) -> Result<RunningProgram, &'static str> {
+        Ok(RunningProgram {
+            title_id: self.get_title_id()?,
+            build_id: self.get_build_id()?,
+        })
     }
 
     pub fn get_version(&self) -> Result<String, &'static str> {
@@ -396,7 +776,7 @@ impl SysBotClient {
 
     pub fn pointer_relative(&self, jumps: &[u64]) -> Result<u64, &'static str> {
         self.check_connected()?;
-        let mut command = "pointerAll".to_string();
+        let mut command = "pointerRelative".to_string();
         for jump in jumps {
             command = format!("{} 0x{:X}", command, jump)
         }
@@ -421,7 +801,7 @@ impl SysBotClient {
 
     pub fn pointer_poke(&self, jumps: &[u64], data: PokeData) -> Result<(), &'static str> {
         self.check_connected()?;
-        let mut command = format!("pointerPoke {}", data.to_string());
+        let mut command = format!("pointerPoke {}", data);
         for jump in jumps {
             command = format!("{} 0x{:X}", command, jump);
         }
@@ -430,7 +810,7 @@ impl SysBotClient {
 
     pub fn freeze(&self, args: PokeArgs) -> Result<(), &'static str> {
         self.check_connected()?;
-        let command = format!("freeze 0x{:X} {}", args.addr, args.data.to_string());
+        let command = format!("freeze 0x{:X} {}", args.addr, args.data);
         self.send(command, false, false, 0)
     }
 
@@ -446,6 +826,16 @@ impl SysBotClient {
         self.send(command, false, false, 0)
     }
 
+    /// Returns the number of addresses the server is currently freezing.
+    pub fn freeze_count(&self) -> Result<u8, &'static str> {
+        self.check_connected()?;
+        let command = "freezeCount".to_string();
+        self.send(command, true, false, 0)?;
+        let string = String::from_utf8(self.receive()?).map_err(|_| "Failed to parse response")?;
+        let string = string.replace('\u{0000}', "");
+        u8::from_str(string.trim()).map_err(|_| "Failed to parse string to u8")
+    }
+
     pub fn freeze_pause(&self) -> Result<(), &'static str> {
         self.check_connected()?;
         let command = "freezePause".to_string();
@@ -457,6 +847,34 @@ impl SysBotClient {
         let command = "freezeUnpause".to_string();
         self.send(command, false, false, 0)
     }
+
+    /// Sends a command line as it is, returning the response line when the command has one.
+    ///
+    /// The controller state tracked by the client is not updated by raw commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - A sys-botbase command, without the trailing `\r\n`
+    pub fn raw(&self, command: &str) -> Result<Option<Vec<u8>>, &'static str> {
+        self.check_connected()?;
+        let responds = protocol::responds(command);
+        self.send(command.trim_end().to_string(), responds, false, READ_LINE)?;
+        if responds {
+            self.receive().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Takes a screenshot, returning it as JPEG bytes.
+    pub fn pixel_peek(&self) -> Result<Vec<u8>, &'static str> {
+        self.check_connected()?;
+        let command = "pixelPeek".to_string();
+        self.send(command, true, false, READ_LINE)?;
+        let mut bytes = SysBotClient::hex_string_to_vec(self.receive()?);
+        bytes.pop();
+        Ok(bytes)
+    }
 }
 
 impl Drop for SysBotClient {
@@ -466,3 +884,333 @@ impl Drop for SysBotClient {
         self.worker.take().unwrap().join().unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::protocol;
+    use crate::test_server::TestServer;
+    use crate::types::{
+        Button, ControllerState, Direction, RunningProgram, SeqParam, Sequence, Stick,
+        StickMovement,
+    };
+    use crate::types::{PeekArgs, PokeData};
+    use crate::SysBotClient;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const TITLE_ID: u64 = 0x0100ABF008968000;
+    const BUILD_ID: u64 = 0x8BB2C0E09AA48A0D;
+
+    fn respond(command: &str) -> Option<String> {
+        match command {
+            "getTitleID" => Some(format!("{:016X}\n", TITLE_ID)),
+            "getBuildID" => Some(format!("{:016X}\n", BUILD_ID)),
+            _ => command
+                .strip_prefix("isProgramRunning ")
+                .map(|id| u64::from_str_radix(id.trim_start_matches("0x"), 16).unwrap())
+                .map(|id| format!("{}\n", (id == TITLE_ID) as u8)),
+        }
+    }
+
+    #[test]
+    fn should_fail_fast_on_unsupported_commands() {
+        let server = TestServer::start(|command| match command {
+            "getVersion" => Some("1.8\n".to_string()),
+            "pointerAll 0x10" => Some("0000000000001234\n".to_string()),
+            _ => None,
+        });
+        let versions = [("pixelPeek", (1, 9)), ("charge", (2, 2))];
+        let client =
+            SysBotClient::connect_negotiated_with("127.0.0.1", server.port, &versions).unwrap();
+        assert_eq!("1.8", client.capabilities().unwrap().version());
+        assert_eq!(Ok(0x1234), client.pointer_all(&[0x10]));
+        assert_eq!(Err(protocol::UNSUPPORTED), client.pixel_peek());
+        assert_eq!(Err(protocol::UNSUPPORTED), client.raw("charge"));
+        client.click(Button::A).unwrap();
+        drop(client);
+        assert_eq!(
+            vec!["getVersion", "pointerAll 0x10", "click A"],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_fail_to_negotiate_unknown_versions() {
+        let server =
+            TestServer::start(|command| (command == "getVersion").then(|| "custom\n".to_string()));
+        let versions = [("pixelPeek", (1, 9))];
+        assert_eq!(
+            Some(protocol::UNKNOWN_VERSION),
+            SysBotClient::connect_negotiated_with("127.0.0.1", server.port, &versions).err()
+        );
+        assert_eq!(
+            Some(protocol::NO_COMMAND_VERSIONS),
+            SysBotClient::connect_negotiated_with("127.0.0.1", server.port, &[]).err()
+        );
+        assert_eq!(vec!["getVersion"], server.finish());
+    }
+
+    #[test]
+    fn should_fail_to_negotiate_late_or_partial_versions() {
+        for version in ["2.4\n", "2."] {
+            let late = version.ends_with('\n');
+            let server = TestServer::start(move |command| {
+                if late {
+                    thread::sleep(Duration::from_millis(200));
+                }
+                (command == "getVersion").then(|| version.to_string())
+            });
+            let mut stream = SysBotClient::open("127.0.0.1", server.port).unwrap();
+            assert_eq!(
+                Err(protocol::RECEIVE_FAILED),
+                SysBotClient::negotiate(&mut stream, Duration::from_millis(50))
+            );
+            drop(stream);
+            assert_eq!(vec!["getVersion"], server.finish());
+        }
+    }
+
+    #[test]
+    fn should_send_pointer_relative() {
+        let server = TestServer::start(|command| {
+            (command == "pointerRelative 0x4C1DC58 0x18").then(|| "0000000012ABCDEF\n".to_string())
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        assert_eq!(Ok(0x12ABCDEF), client.pointer_relative(&[0x4C1DC58, 0x18]));
+        drop(client);
+        assert_eq!(vec!["pointerRelative 0x4C1DC58 0x18"], server.finish());
+    }
+
+    #[test]
+    fn should_report_running_program() {
+        let server = TestServer::start(respond);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        assert_eq!(Ok(true), client.is_program_running(TITLE_ID));
+        assert_eq!(Ok(false), client.is_program_running(0x01008DB008C2C000));
+        drop(client);
+        assert_eq!(
+            vec![
+                "isProgramRunning 0x0100ABF008968000",
+                "isProgramRunning 0x01008DB008C2C000"
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_combine_title_and_build() {
+        let server = TestServer::start(respond);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        assert_eq!(
+            Ok(RunningProgram {
+                title_id: TITLE_ID,
+                build_id: BUILD_ID
+            }),
+            client.running_title()
+        );
+        drop(client);
+        assert_eq!(vec!["getTitleID", "getBuildID"], server.finish());
+    }
+
+    #[test]
+    fn should_apply_only_differences() {
+        let server = TestServer::start(|_| None);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        client.press(Button::A).unwrap();
+        client.press(Button::L).unwrap();
+        let target = ControllerState::new()
+            .with_button(Button::L)
+            .with_button(Button::B)
+            .with_stick(Stick::LEFT, StickMovement(0, 0x7FFF));
+        client.apply(&target).unwrap();
+        assert_eq!(target, client.controller_state());
+        drop(client);
+        assert_eq!(
+            vec![
+                "press A",
+                "press L",
+                "release A",
+                "press B",
+                "setStick LSTICK 0 32767"
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_track_click_seq() {
+        let server = TestServer::start(|_| None);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        client
+            .click_seq(vec![
+                SeqParam::Press(Button::ZR),
+                SeqParam::Wait(100),
+                SeqParam::MoveRight(StickMovement(-300, 0)),
+            ])
+            .unwrap();
+        let state = client.controller_state();
+        assert!(state.is_held(Button::ZR));
+        assert_eq!(StickMovement(-300, 0), state.stick(Stick::RIGHT));
+        client.click_cancel().unwrap();
+        assert!(client.controller_state().is_neutral());
+    }
+
+    #[test]
+    fn should_release_everything() {
+        let server = TestServer::start(|_| None);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        client.press(Button::X).unwrap();
+        client.release_all().unwrap();
+        assert!(client.controller_state().is_neutral());
+        drop(client);
+        let commands = server.finish();
+        assert_eq!(1 + Button::ALL.len() + 2, commands.len());
+        assert_eq!("release A", commands[1]);
+        assert_eq!("setStick RSTICK 0 0", commands[commands.len() - 1]);
+    }
+
+    #[test]
+    fn should_tilt_and_recentre() {
+        let server = TestServer::start(|_| None);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        client
+            .tilt(Stick::RIGHT, Direction::Down, Duration::from_millis(10))
+            .unwrap();
+        client
+            .click_seq(SeqParam::tilt(Stick::LEFT, Direction::Right, 250).to_vec())
+            .unwrap();
+        assert!(client.controller_state().is_neutral());
+        drop(client);
+        assert_eq!(
+            vec![
+                "setStick RSTICK 0 -32767",
+                "setStick RSTICK 0 0",
+                "clickSeq %32767,0,W250,%0,0"
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_run_sequences_both_ways() {
+        let server = TestServer::start(|_| None);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let sequence = Sequence::new()
+            .click(Button::A)
+            .hold(Button::B, 5)
+            .with_buffer_size(20);
+        client.run_sequence(&sequence).unwrap();
+        client.run_sequence_individually(&sequence).unwrap();
+        drop(client);
+        assert_eq!(
+            vec![
+                "clickSeq A,+B,W5",
+                "clickSeq -B",
+                "click A",
+                "press B",
+                "release B"
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_wait_for_each_chunk_before_sending_the_next() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let server = TestServer::start(move |_| {
+            tx.lock().unwrap().send(Instant::now()).unwrap();
+            None
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let sequence = Sequence::new()
+            .click(Button::A)
+            .wait(200)
+            .click(Button::B)
+            .with_buffer_size(18);
+        client.run_sequence(&sequence).unwrap();
+        drop(client);
+        assert_eq!(vec!["clickSeq A,W200", "clickSeq B"], server.finish());
+        let first = rx.recv().unwrap();
+        let second = rx.recv().unwrap();
+        assert!(second - first >= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn should_wait_for_value() {
+        let polls = AtomicUsize::new(0);
+        let server = TestServer::start(move |command| {
+            let size = usize::from_str_radix(command.rsplit("0x").next().unwrap(), 16).unwrap();
+            let value: u64 = if polls.fetch_add(1, Ordering::SeqCst) < 2 {
+                0
+            } else {
+                5
+            };
+            let data = PokeData::new(value.to_le_bytes()[..size].to_vec()).to_string();
+            Some(format!("{}\n", &data[2..]))
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let timeout = Duration::from_secs(5);
+        let interval = Duration::from_millis(1);
+        assert_eq!(
+            Ok(5),
+            client.wait_for_value::<u32>(0x10, 5, timeout, interval)
+        );
+        assert_eq!(
+            Ok(vec![5, 0]),
+            client.wait_until(
+                PeekArgs {
+                    addr: 0x10,
+                    size: 2
+                },
+                |b| b[0] > 0,
+                Duration::MAX,
+                interval
+            )
+        );
+        assert_eq!(
+            Err("Timed out waiting for memory"),
+            client.wait_for_value::<u32>(0x10, 6, Duration::from_millis(20), interval)
+        );
+        drop(client);
+        assert_eq!(
+            Some("peek 0x10 0x4"),
+            server.finish().first().map(|c| c.as_str())
+        );
+    }
+
+    #[test]
+    fn should_read_screenshot_line() {
+        let server = TestServer::start(|command| {
+            (command == "pixelPeek").then(|| format!("FFD8{}FFD9\n", "00".repeat(5000)))
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let jpeg = client.pixel_peek().unwrap();
+        assert_eq!(5004, jpeg.len());
+        assert_eq!([0xFF, 0xD8], jpeg[..2]);
+        assert_eq!([0xFF, 0xD9], jpeg[5002..]);
+        drop(client);
+        assert_eq!(vec!["pixelPeek"], server.finish());
+    }
+
+    #[test]
+    fn should_classify_connection_errors() {
+        for error in protocol::CONNECTION_ERRORS {
+            assert!(SysBotClient::is_connection_error(error), "{}", error);
+        }
+        assert!(!SysBotClient::is_connection_error(protocol::UNSUPPORTED));
+        assert!(!SysBotClient::is_connection_error(
+            "Failed to parse bytes to u64"
+        ));
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let error = SysBotClient::connect("127.0.0.1", port).err().unwrap();
+        assert_eq!(protocol::CONNECT_FAILED, error);
+    }
+}
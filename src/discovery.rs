@@ -0,0 +1,316 @@
+//! Finding consoles running sys-botbase on the local network
+//!
+//! [`Discovery::scan`] tries every address of a [`Cidr`] range, a few at a time, and asks
+//! whatever answers on the sys-botbase port for its version. Addresses that answer like
+//! sys-botbase are asked which program is running and returned as [`DiscoveredConsole`]s.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::discovery::Discovery;
+//!
+//! let range = "192.168.0.0/24".parse().unwrap();
+//! for console in Discovery::new().scan(&range) {
+//!     println!("{} sys-botbase {} running {}", console.addr, console.version, console.program);
+//! }
+//! ```
+
+use crate::types::RunningProgram;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// The port sys-botbase listens on
+pub const DEFAULT_PORT: u16 = 6000;
+
+/// A range of IPv4 addresses, such as `192.168.0.0/24`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: Ipv4Addr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Creates the range of addresses sharing the first `prefix` bits of `addr`
+    pub fn new(addr: Ipv4Addr, prefix: u8) -> Result<Self, &'static str> {
+        if prefix > 32 {
+            return Err("CIDR prefix must be at most 32");
+        }
+        let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+        Ok(Self {
+            addr: Ipv4Addr::from(u32::from(addr) & mask),
+            prefix,
+        })
+    }
+
+    /// Returns the addresses of the hosts in the range, leaving out the network and broadcast
+    /// addresses of ranges that have them
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let first = u32::from(self.addr) as u64;
+        let last = first + (1u64 << (32 - self.prefix as u32)) - 1;
+        let (first, last) = if self.prefix < 31 {
+            (first + 1, last - 1)
+        } else {
+            (first, last)
+        };
+        (first..=last).map(|addr| Ipv4Addr::from(addr as u32))
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = &'static str;
+
+    /// Parses an address followed by a prefix length, or a single address
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.trim().split_once('/').unwrap_or((s.trim(), "32"));
+        let addr = addr.parse().map_err(|_| "Invalid address in CIDR range")?;
+        let prefix = prefix.parse().map_err(|_| "Invalid CIDR prefix")?;
+        Cidr::new(addr, prefix)
+    }
+}
+
+/// A console that answered like sys-botbase
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveredConsole {
+    pub addr: SocketAddr,
+    /// The version reported by `getVersion`
+    pub version: String,
+    pub program: RunningProgram,
+}
+
+/// Sends a command and reads its response line
+fn request(reader: &mut BufReader<TcpStream>, command: &str) -> Option<String> {
+    reader
+        .get_mut()
+        .write_all(format!("{}\r\n", command).as_bytes())
+        .ok()?;
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    Some(line.trim().to_string())
+}
+
+fn is_version(version: &str) -> bool {
+    version.chars().any(|c| c.is_ascii_digit())
+        && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Asks whatever is listening at `addr` for its version and running program
+fn handshake(addr: SocketAddr, timeout: Duration) -> Option<DiscoveredConsole> {
+    let stream = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    let mut reader = BufReader::new(stream);
+    let version = request(&mut reader, "getVersion").filter(|v| is_version(v))?;
+    let mut id = |command| u64::from_str_radix(&request(&mut reader, command)?, 16).ok();
+    let program = RunningProgram {
+        title_id: id("getTitleID")?,
+        build_id: id("getBuildID")?,
+    };
+    Some(DiscoveredConsole {
+        addr,
+        version,
+        program,
+    })
+}
+
+/// Scans addresses for consoles running sys-botbase
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Discovery {
+    port: u16,
+    concurrency: usize,
+    timeout: Duration,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            concurrency: 64,
+            timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the port to look for sys-botbase on
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets how many addresses are tried at once
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets how long to wait for an address to connect, and then for each response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Tries every host in `range`, returning the consoles found in address order
+    pub fn scan(&self, range: &Cidr) -> Vec<DiscoveredConsole> {
+        self.scan_hosts(range.hosts())
+    }
+
+    /// Tries each of `hosts`, returning the consoles found in address order
+    pub fn scan_hosts(
+        &self,
+        hosts: impl IntoIterator<Item = Ipv4Addr, IntoIter: Send>,
+    ) -> Vec<DiscoveredConsole> {
+        let port = self.port;
+        self.scan_addrs(
+            hosts
+                .into_iter()
+                .map(move |host| SocketAddr::new(host.into(), port)),
+        )
+    }
+
+    /// Tries each of `addrs`, ignoring the port set with [`Discovery::with_port`], and returns the
+    /// consoles found in address order
+    pub fn scan_addrs(
+        &self,
+        addrs: impl IntoIterator<Item = SocketAddr, IntoIter: Send>,
+    ) -> Vec<DiscoveredConsole> {
+        let addrs = Mutex::new(addrs.into_iter());
+        let (found, results) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.concurrency {
+                let found = found.clone();
+                let addrs = &addrs;
+                scope.spawn(move || loop {
+                    let Some(addr) = addrs.lock().unwrap().next() else {
+                        break;
+                    };
+                    if let Some(console) = handshake(addr, self.timeout) {
+                        let _ = found.send(console);
+                    }
+                });
+            }
+        });
+        drop(found);
+        let mut consoles = results.into_iter().collect::<Vec<_>>();
+        consoles.sort_by_key(|console| console.addr);
+        consoles
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discovery::{Cidr, Discovery};
+    use crate::types::RunningProgram;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Serves connections on a free port of `host`, answering each line with `respond`
+    fn mock(host: Ipv4Addr, respond: fn(&str) -> Option<&'static str>) -> u16 {
+        let listener = TcpListener::bind((host, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let mut writer = stream.try_clone().unwrap();
+                for line in BufReader::new(stream).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if let Some(response) = respond(line.trim()) {
+                        let _ = writer.write_all(response.as_bytes());
+                    }
+                }
+            }
+        });
+        port
+    }
+
+    fn sys_botbase(command: &str) -> Option<&'static str> {
+        match command {
+            "getVersion" => Some("2.4\n"),
+            "getTitleID" => Some("0100ABCD00000000\n"),
+            "getBuildID" => Some("00000000DEADBEEF\n"),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn should_parse_ranges() {
+        let range: Cidr = "192.168.1.77/24".parse().unwrap();
+        assert_eq!("192.168.1.0/24", range.to_string());
+        let hosts = range.hosts().collect::<Vec<_>>();
+        assert_eq!(254, hosts.len());
+        assert_eq!(Ipv4Addr::new(192, 168, 1, 1), hosts[0]);
+        assert_eq!(Ipv4Addr::new(192, 168, 1, 254), hosts[253]);
+        let single: Cidr = "10.0.0.5".parse().unwrap();
+        assert_eq!(
+            vec![Ipv4Addr::new(10, 0, 0, 5)],
+            single.hosts().collect::<Vec<_>>()
+        );
+        assert_eq!(2, "10.0.0.4/31".parse::<Cidr>().unwrap().hosts().count());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn should_find_consoles_on_loopback() {
+        let host = Ipv4Addr::LOCALHOST;
+        let first = mock(host, sys_botbase);
+        let second = mock(host, sys_botbase);
+        let old = mock(host, |command| match command {
+            "getVersion" => Some("1.9\n"),
+            "getTitleID" => Some("0000000000000000\n"),
+            "getBuildID" => Some("0000000000000000\n"),
+            _ => None,
+        });
+        // Something else listening, and something that never answers
+        let http = mock(host, |_| Some("HTTP/1.1 400 Bad Request\r\n"));
+        let silent = mock(host, |_| None);
+
+        let addrs =
+            [first, second, old, http, silent].map(|port| SocketAddr::new(host.into(), port));
+        let consoles = Discovery::new()
+            .with_concurrency(2)
+            .with_timeout(Duration::from_millis(200))
+            .scan_addrs(addrs);
+        let mut expected = vec![addrs[0], addrs[1], addrs[2]];
+        expected.sort();
+        assert_eq!(
+            expected,
+            consoles
+                .iter()
+                .map(|console| console.addr)
+                .collect::<Vec<_>>()
+        );
+        let console = |addr| consoles.iter().find(|c| c.addr == addr).unwrap();
+        assert_eq!("2.4", console(addrs[0]).version);
+        assert_eq!(
+            RunningProgram {
+                title_id: 0x0100ABCD00000000,
+                build_id: 0xDEADBEEF,
+            },
+            console(addrs[0]).program
+        );
+        assert_eq!("1.9", console(addrs[2]).version);
+    }
+}
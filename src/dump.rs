@@ -0,0 +1,393 @@
+//! Saving memory to files and writing it back
+//!
+//! A dump file starts with a [`DumpHeader`] recording which program and region the memory came
+//! from, followed by the raw bytes. [`dump`] streams a region into a file one peek at a time, and
+//! [`restore`] pokes it back after checking that the same build of the same program is running.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::dump::{dump, restore};
+//! use sysbot_rs::types::Region;
+//! use sysbot_rs::SysBotClient;
+//! let client = SysBotClient::connect("0.0.0.0", 6000).unwrap();
+//! dump(&client, Region::Heap, 0x8A0000, 0x10000, "party.dmp").unwrap();
+//! // Lose a battle...
+//! restore(&client, "party.dmp").unwrap();
+//! ```
+
+use crate::types::{PeekArgs, PokeArgs, PokeData, Region};
+use crate::SysBotClient;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The bytes every dump file starts with
+pub const DUMP_MAGIC: [u8; 8] = *b"SBRSDUMP";
+
+/// The number of bytes read by each peek while dumping
+pub const DUMP_PEEK_SIZE: usize = 0x1000;
+
+/// The number of bytes written by each poke while restoring, small enough to fit the command
+/// in sys-botbase's receive buffer
+pub const DUMP_POKE_SIZE: usize = 0x400;
+
+const VERSION: u32 = 1;
+
+/// An error raised while dumping or restoring memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DumpError {
+    /// The dump file could not be read or written
+    Io(std::io::ErrorKind),
+    /// The dump file is not in the expected format
+    Format(&'static str),
+    /// The dump was taken from a different program than the one running
+    TitleMismatch { expected: u64, actual: u64 },
+    /// The dump was taken from a different build of the running program
+    BuildMismatch { expected: u64, actual: u64 },
+    /// The client failed while reading or writing memory
+    Client(&'static str),
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::Io(kind) => write!(f, "Failed to access dump file: {}", kind),
+            DumpError::Format(message) => write!(f, "Invalid dump file: {}", message),
+            DumpError::TitleMismatch { expected, actual } => write!(
+                f,
+                "Dump is from title {:016X} but {:016X} is running",
+                expected, actual
+            ),
+            DumpError::BuildMismatch { expected, actual } => write!(
+                f,
+                "Dump is from build {:016X} but {:016X} is running",
+                expected, actual
+            ),
+            DumpError::Client(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+impl From<std::io::Error> for DumpError {
+    fn from(error: std::io::Error) -> Self {
+        DumpError::Io(error.kind())
+    }
+}
+
+/// Describes the memory held in a dump file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DumpHeader {
+    pub title_id: u64,
+    pub build_id: u64,
+    pub region: Region,
+    /// The absolute address of the region when the dump was taken, or 0 for absolute dumps
+    pub base: u64,
+    /// The address of the first byte, relative to the region
+    pub start: u64,
+    pub len: u64,
+    /// Seconds since the Unix epoch when the dump was taken
+    pub timestamp: u64,
+}
+
+impl DumpHeader {
+    /// The number of bytes the header takes up in a file
+    pub const SIZE: usize = 64;
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<(), DumpError> {
+        let region = match self.region {
+            Region::Heap => 0u32,
+            Region::Main => 1,
+            Region::Absolute => 2,
+        };
+        writer.write_all(&DUMP_MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&region.to_le_bytes())?;
+        for field in [
+            self.title_id,
+            self.build_id,
+            self.base,
+            self.start,
+            self.len,
+            self.timestamp,
+        ] {
+            writer.write_all(&field.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self, DumpError> {
+        let mut bytes = [0; Self::SIZE];
+        reader.read_exact(&mut bytes).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => DumpError::Format("Header is truncated"),
+            kind => DumpError::Io(kind),
+        })?;
+        if bytes[..8] != DUMP_MAGIC {
+            return Err(DumpError::Format("Missing dump magic"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        if u32_at(8) != VERSION {
+            return Err(DumpError::Format("Unsupported dump version"));
+        }
+        let region = match u32_at(12) {
+            0 => Region::Heap,
+            1 => Region::Main,
+            2 => Region::Absolute,
+            _ => return Err(DumpError::Format("Unknown memory region")),
+        };
+        Ok(Self {
+            title_id: u64_at(16),
+            build_id: u64_at(24),
+            region,
+            base: u64_at(32),
+            start: u64_at(40),
+            len: u64_at(48),
+            timestamp: u64_at(56),
+        })
+    }
+}
+
+/// Reads a whole dump file
+pub fn read_dump(path: impl AsRef<Path>) -> Result<(DumpHeader, Vec<u8>), DumpError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let header = DumpHeader::read(&mut reader)?;
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    if data.len() as u64 != header.len {
+        return Err(DumpError::Format("Data length does not match header"));
+    }
+    Ok((header, data))
+}
+
+/// Streams memory into a dump file, returning the header that was written.
+///
+/// # Arguments
+///
+/// * `region` - The region `start` is relative to
+/// * `start` - The address of the first byte to dump
+/// * `len` - The number of bytes to dump
+/// * `path` - The file to create
+pub fn dump(
+    client: &SysBotClient,
+    region: Region,
+    start: u64,
+    len: usize,
+    path: impl AsRef<Path>,
+) -> Result<DumpHeader, DumpError> {
+    let program = client.running_title().map_err(DumpError::Client)?;
+    let base = match region {
+        Region::Heap => client.get_heap_base(),
+        Region::Main => client.get_main_nso_base(),
+        Region::Absolute => Ok(0),
+    }
+    .map_err(DumpError::Client)?;
+    let header = DumpHeader {
+        title_id: program.title_id,
+        build_id: program.build_id,
+        region,
+        base,
+        start,
+        len: len as u64,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    header.write(&mut writer)?;
+    let mut written = 0;
+    while written < len {
+        let size = DUMP_PEEK_SIZE.min(len - written);
+        let chunk = client
+            .peek_region(
+                region,
+                PeekArgs {
+                    addr: start + written as u64,
+                    size,
+                },
+            )
+            .map_err(DumpError::Client)?;
+        let chunk = chunk
+            .get(..size)
+            .ok_or(DumpError::Client("Failed to read dumped memory"))?;
+        writer.write_all(chunk)?;
+        written += size;
+    }
+    writer.flush()?;
+    Ok(header)
+}
+
+/// Streams a dump file back into memory one poke at a time, returning its header.
+///
+/// Nothing is written unless the file is the length its header records and the running program
+/// and build match the ones the dump was taken from.
+pub fn restore(client: &SysBotClient, path: impl AsRef<Path>) -> Result<DumpHeader, DumpError> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let header = DumpHeader::read(&mut reader)?;
+    if file_len.checked_sub(DumpHeader::SIZE as u64) != Some(header.len) {
+        return Err(DumpError::Format("Data length does not match header"));
+    }
+    let program = client.running_title().map_err(DumpError::Client)?;
+    if program.title_id != header.title_id {
+        return Err(DumpError::TitleMismatch {
+            expected: header.title_id,
+            actual: program.title_id,
+        });
+    }
+    if program.build_id != header.build_id {
+        return Err(DumpError::BuildMismatch {
+            expected: header.build_id,
+            actual: program.build_id,
+        });
+    }
+    let mut chunk = vec![0; DUMP_POKE_SIZE];
+    let mut restored = 0;
+    while restored < header.len {
+        let size = (DUMP_POKE_SIZE as u64).min(header.len - restored) as usize;
+        reader
+            .read_exact(&mut chunk[..size])
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => {
+                    DumpError::Format("Data length does not match header")
+                }
+                kind => DumpError::Io(kind),
+            })?;
+        client
+            .poke_region(
+                header.region,
+                PokeArgs {
+                    addr: header.start + restored,
+                    data: PokeData::new(chunk[..size].to_vec()),
+                },
+            )
+            .map_err(DumpError::Client)?;
+        restored += size as u64;
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dump::{dump, read_dump, restore, DumpError, DumpHeader, DUMP_POKE_SIZE};
+    use crate::test_server::TestServer;
+    use crate::types::Region;
+    use crate::SysBotClient;
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sysbot_rs_{}_{}.dmp", name, std::process::id()))
+    }
+
+    fn server(build_id: &'static str) -> TestServer {
+        TestServer::start(move |command| match command.split(' ').next() {
+            Some("getTitleID") => Some("0100ABCD0000C000\n".to_string()),
+            Some("getBuildID") => Some(format!("{}\n", build_id)),
+            Some("getMainNsoBase") => Some("0000000080000000\n".to_string()),
+            Some("peekMain") => {
+                let size = usize::from_str_radix(command.rsplit("0x").next().unwrap(), 16);
+                Some(format!("{}\n", "AB".repeat(size.unwrap())))
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn should_dump_and_restore() {
+        let path = path("restore");
+        let server = server("8BB2C0E09AA48A0D");
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let len = DUMP_POKE_SIZE + 0x10;
+        let header = dump(&client, Region::Main, 0x100, len, &path).unwrap();
+        assert_eq!(
+            DumpHeader {
+                title_id: 0x0100ABCD0000C000,
+                build_id: 0x8BB2C0E09AA48A0D,
+                region: Region::Main,
+                base: 0x80000000,
+                start: 0x100,
+                len: len as u64,
+                timestamp: header.timestamp
+            },
+            header
+        );
+        assert_eq!(Ok((header, vec![0xAB; len])), read_dump(&path));
+        assert_eq!(Ok(header), restore(&client, &path));
+        drop(client);
+        let commands = server.finish();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            vec![
+                "getTitleID",
+                "getBuildID",
+                "getMainNsoBase",
+                "peekMain 0x100 0x410",
+                "getTitleID",
+                "getBuildID"
+            ],
+            commands[..6]
+        );
+        assert!(commands[6].starts_with("pokeMain 0x100 0xABAB"));
+        assert_eq!(
+            "pokeMain 0x500 0xABABABABABABABABABABABABABABABAB",
+            commands[7]
+        );
+        assert_eq!(8, commands.len());
+    }
+
+    #[test]
+    fn should_refuse_other_builds() {
+        let path = path("mismatch");
+        let first = server("8BB2C0E09AA48A0D");
+        let client = SysBotClient::connect("127.0.0.1", first.port).unwrap();
+        dump(&client, Region::Main, 0, 4, &path).unwrap();
+        drop(client);
+        first.finish();
+        let second = server("0123456789ABCDEF");
+        let client = SysBotClient::connect("127.0.0.1", second.port).unwrap();
+        let result = restore(&client, &path);
+        drop(client);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            Err(DumpError::BuildMismatch {
+                expected: 0x8BB2C0E09AA48A0D,
+                actual: 0x0123456789ABCDEF
+            }),
+            result
+        );
+        assert_eq!(vec!["getTitleID", "getBuildID"], second.finish());
+    }
+
+    #[test]
+    fn should_reject_malformed_files() {
+        let path = path("malformed");
+        std::fs::write(&path, b"not a dump").unwrap();
+        let result = read_dump(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Err(DumpError::Format("Header is truncated")), result);
+    }
+
+    #[test]
+    fn should_restore_nothing_from_truncated_files() {
+        let path = path("truncated");
+        let server = server("8BB2C0E09AA48A0D");
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        dump(&client, Region::Main, 0, DUMP_POKE_SIZE * 2, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let result = restore(&client, &path);
+        drop(client);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            Err(DumpError::Format("Data length does not match header")),
+            result
+        );
+        assert!(!server.finish().iter().any(|c| c.starts_with("poke")));
+    }
+}
@@ -0,0 +1,261 @@
+//! A C API around [`SysBotClient`], built with the `ffi` feature
+//!
+//! A client is an opaque [`SysBot`] handle created by [`sysbot_connect`] and freed by
+//! [`sysbot_close`]. Every other function returns [`SYSBOT_OK`] or a negative error code, and
+//! the message of the last error on the calling thread is available from [`sysbot_last_error`].
+//! The C declarations are in `include/sysbot.h`, which is generated from this file with
+//! `cbindgen --config cbindgen.toml --output include/sysbot.h`.
+
+use crate::types::{Button, PeekArgs, PokeArgs, PokeData, Region};
+use crate::SysBotClient;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::slice;
+
+/// The call succeeded
+pub const SYSBOT_OK: i32 = 0;
+/// A pointer was null, a string was invalid or a value was out of range
+pub const SYSBOT_ERROR_ARGUMENT: i32 = -1;
+/// The console could not be reached or the connection was lost
+pub const SYSBOT_ERROR_CONNECTION: i32 = -2;
+/// The console could not carry out the command
+pub const SYSBOT_ERROR_COMMAND: i32 = -3;
+/// The library panicked; the handle should be closed
+pub const SYSBOT_ERROR_INTERNAL: i32 = -4;
+
+/// Addresses relative to the start of the heap
+pub const SYSBOT_REGION_HEAP: i32 = 0;
+/// Addresses relative to the start of the main NSO
+pub const SYSBOT_REGION_MAIN: i32 = 1;
+/// Absolute addresses
+pub const SYSBOT_REGION_ABSOLUTE: i32 = 2;
+
+/// An opaque handle to a connected client
+pub struct SysBot {
+    client: SysBotClient,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// An error to report to the caller, with its code
+struct Error(i32, String);
+
+impl From<&'static str> for Error {
+    fn from(message: &'static str) -> Self {
+        if SysBotClient::is_connection_error(message) {
+            Error(SYSBOT_ERROR_CONNECTION, message.to_string())
+        } else {
+            Error(SYSBOT_ERROR_COMMAND, message.to_string())
+        }
+    }
+}
+
+fn argument(message: &str) -> Error {
+    Error(SYSBOT_ERROR_ARGUMENT, message.to_string())
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Runs `f`, recording its error and turning the result, or a panic, into a return code
+fn call(f: impl FnOnce() -> Result<(), Error>) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => SYSBOT_OK,
+        Ok(Err(Error(code, message))) => {
+            set_last_error(message);
+            code
+        }
+        Err(_) => {
+            set_last_error("Internal error in sysbot_rs".to_string());
+            SYSBOT_ERROR_INTERNAL
+        }
+    }
+}
+
+unsafe fn client<'a>(handle: *const SysBot) -> Result<&'a SysBotClient, Error> {
+    handle
+        .as_ref()
+        .map(|handle| &handle.client)
+        .ok_or_else(|| argument("The handle is null"))
+}
+
+unsafe fn string<'a>(s: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(argument(&format!("{} is null", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| argument(&format!("{} is not UTF-8", name)))
+}
+
+fn parse_region(region: i32) -> Result<Region, Error> {
+    match region {
+        SYSBOT_REGION_HEAP => Ok(Region::Heap),
+        SYSBOT_REGION_MAIN => Ok(Region::Main),
+        SYSBOT_REGION_ABSOLUTE => Ok(Region::Absolute),
+        _ => Err(argument("Unknown memory region")),
+    }
+}
+
+unsafe fn parse_button(name: *const c_char) -> Result<Button, Error> {
+    string(name, "The button")?
+        .parse()
+        .map_err(|e: &str| argument(e))
+}
+
+/// Connects to sys-botbase, returning a handle or null on failure.
+///
+/// # Safety
+///
+/// `host` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_connect(host: *const c_char, port: u16) -> *mut SysBot {
+    let mut handle = ptr::null_mut();
+    call(|| {
+        let client =
+            SysBotClient::connect(string(host, "The host")?, port).map_err(|e| match e {
+                "Failed to convert ip address" => argument(e),
+                e => Error::from(e),
+            })?;
+        handle = Box::into_raw(Box::new(SysBot { client }));
+        Ok(())
+    });
+    handle
+}
+
+/// Disconnects and frees a handle. Null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle from [`sysbot_connect`] that has not been closed.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_close(handle: *mut SysBot) {
+    if !handle.is_null() {
+        // Disconnecting can panic, which must not unwind into C
+        call(|| {
+            drop(Box::from_raw(handle));
+            Ok(())
+        });
+    }
+}
+
+/// Returns the message of the last error on this thread, or an empty string.
+///
+/// The string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn sysbot_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Reads `len` bytes at `addr` into `buffer`.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `buffer` must be valid for writes of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_peek(
+    handle: *const SysBot,
+    region: i32,
+    addr: u64,
+    buffer: *mut u8,
+    len: usize,
+) -> i32 {
+    call(|| {
+        let client = client(handle)?;
+        if buffer.is_null() || len == 0 {
+            return Err(argument("The buffer is null or empty"));
+        }
+        let bytes = client.peek_region(parse_region(region)?, PeekArgs { addr, size: len })?;
+        let bytes = bytes
+            .get(..len)
+            .ok_or_else(|| Error(SYSBOT_ERROR_COMMAND, "Short peek response".to_string()))?;
+        ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, len);
+        Ok(())
+    })
+}
+
+/// Writes `len` bytes from `data` to `addr`.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `data` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_poke(
+    handle: *const SysBot,
+    region: i32,
+    addr: u64,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    call(|| {
+        let client = client(handle)?;
+        if data.is_null() || len == 0 {
+            return Err(argument("The data is null or empty"));
+        }
+        let data = PokeData::new(slice::from_raw_parts(data, len).to_vec());
+        client.poke_region(parse_region(region)?, PokeArgs { addr, data })?;
+        Ok(())
+    })
+}
+
+/// Clicks a button, named as sys-botbase names it, such as `"A"` or `"DUP"`.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `button` a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_click(handle: *const SysBot, button: *const c_char) -> i32 {
+    call(|| Ok(client(handle)?.click(parse_button(button)?)?))
+}
+
+/// Holds a button down until it is released.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `button` a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_press(handle: *const SysBot, button: *const c_char) -> i32 {
+    call(|| Ok(client(handle)?.press(parse_button(button)?)?))
+}
+
+/// Releases a held button.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle and `button` a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_release(handle: *const SysBot, button: *const c_char) -> i32 {
+    call(|| Ok(client(handle)?.release(parse_button(button)?)?))
+}
+
+/// Follows a pointer chain from main and stores the absolute address it leads to in `out_addr`.
+///
+/// The jumps are the same as those of a `[[main+X]+Y]+Z` chain: `{X, Y, Z}`.
+///
+/// # Safety
+///
+/// `handle` must be a valid handle, `jumps` must be valid for reads of `count` values and
+/// `out_addr` must be valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn sysbot_pointer(
+    handle: *const SysBot,
+    jumps: *const u64,
+    count: usize,
+    out_addr: *mut u64,
+) -> i32 {
+    call(|| {
+        let client = client(handle)?;
+        if jumps.is_null() || count == 0 || out_addr.is_null() {
+            return Err(argument("The jumps or output are null or empty"));
+        }
+        *out_addr = client.pointer_all(slice::from_raw_parts(jumps, count))?;
+        Ok(())
+    })
+}
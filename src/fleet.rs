@@ -0,0 +1,456 @@
+//! Managing many consoles at once
+//!
+//! A [`Fleet`] owns any number of [`SysBotClient`]s by name. Each console has its own worker
+//! thread and work queue, so a console that stops responding only holds up its own work. Work is
+//! a closure run on the console's client; submitting it returns a [`Ticket`] straight away, and
+//! [`Fleet::broadcast`] submits the same work to a subset of the consoles. The outcome of every
+//! piece of work is recorded in the console's [`Health`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::fleet::Fleet;
+//! use sysbot_rs::types::Button;
+//! use std::time::Duration;
+//!
+//! let mut fleet = Fleet::new();
+//! fleet.connect("left", "192.168.0.2", 6000).unwrap();
+//! fleet.connect("right", "192.168.0.3", 6000).unwrap();
+//! let tickets = fleet.broadcast_all(|client| client.click(Button::A));
+//! for (name, ticket) in tickets {
+//!     if let Err(e) = ticket.wait_timeout(Duration::from_secs(5)) {
+//!         eprintln!("{}: {}", name, e);
+//!     }
+//! }
+//! ```
+
+use crate::types::RunningProgram;
+use crate::SysBotClient;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// An error raised while managing or running work on a fleet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FleetError {
+    /// No console in the fleet has the given name
+    UnknownConsole(String),
+    /// A console with the given name is already in the fleet
+    DuplicateName(String),
+    /// The console's client failed while running the work
+    Client(&'static str),
+    /// The work did not finish in time, and is still queued or running
+    Timeout,
+    /// The work panicked, or the console's worker stopped before the work finished
+    Stopped,
+}
+
+impl fmt::Display for FleetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FleetError::UnknownConsole(name) => write!(f, "Unknown console {}", name),
+            FleetError::DuplicateName(name) => write!(f, "Console {} already exists", name),
+            FleetError::Client(message) => write!(f, "{}", message),
+            FleetError::Timeout => write!(f, "Timed out waiting for the console"),
+            FleetError::Stopped => write!(f, "The console stopped before the work finished"),
+        }
+    }
+}
+
+impl std::error::Error for FleetError {}
+
+/// What is known about how a console has been responding
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    /// When a piece of work last succeeded
+    pub last_success: Option<Instant>,
+    /// How long the last successful piece of work took
+    pub latency: Option<Duration>,
+    /// The program found running by the last [`Fleet::check`]
+    pub title: Option<RunningProgram>,
+    /// The error of the last piece of work, if it failed
+    pub last_error: Option<&'static str>,
+    /// How many pieces of work are queued or running
+    pub pending: usize,
+    /// When the piece of work currently running started
+    pub busy_since: Option<Instant>,
+}
+
+impl Health {
+    /// Returns whether the last piece of work succeeded and nothing has been running for longer
+    /// than `stuck_after`
+    pub fn is_healthy(&self, stuck_after: Duration) -> bool {
+        self.last_error.is_none()
+            && self
+                .busy_since
+                .is_none_or(|since| since.elapsed() <= stuck_after)
+    }
+}
+
+/// The result of a piece of work, which arrives once the console has run it
+pub struct Ticket<T> {
+    receiver: Receiver<Result<T, FleetError>>,
+}
+
+impl<T> Ticket<T> {
+    /// Waits for the work to finish, however long the console takes
+    pub fn wait(self) -> Result<T, FleetError> {
+        self.receiver.recv().unwrap_or(Err(FleetError::Stopped))
+    }
+
+    /// Waits up to `timeout` for the work to finish, after which it can be waited for again
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<T, FleetError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(FleetError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(FleetError::Stopped),
+        }
+    }
+}
+
+/// The error recorded in [`Health::last_error`] for a piece of work that panicked
+pub const PANICKED: &str = "The work panicked";
+
+type Job = Box<dyn FnOnce(&SysBotClient) -> Result<(), &'static str> + Send>;
+
+fn lock(health: &Mutex<Health>) -> MutexGuard<'_, Health> {
+    health
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs a console's queued work in order, recording the outcome of each piece
+///
+/// A piece of work that panics is recorded as failing with [`PANICKED`], and the worker carries
+/// on with the rest of the queue.
+fn work(client: SysBotClient, queue: Receiver<Job>, health: Arc<Mutex<Health>>) -> SysBotClient {
+    for job in queue {
+        let started = Instant::now();
+        lock(&health).busy_since = Some(started);
+        let result =
+            panic::catch_unwind(AssertUnwindSafe(|| job(&client))).unwrap_or(Err(PANICKED));
+        let mut health = lock(&health);
+        health.busy_since = None;
+        health.pending -= 1;
+        match result {
+            Ok(()) => {
+                health.last_success = Some(Instant::now());
+                health.latency = Some(started.elapsed());
+                health.last_error = None;
+            }
+            Err(e) => health.last_error = Some(e),
+        }
+    }
+    client
+}
+
+struct Console {
+    jobs: Sender<Job>,
+    health: Arc<Mutex<Health>>,
+    worker: JoinHandle<SysBotClient>,
+}
+
+impl Console {
+    fn start(client: SysBotClient) -> Self {
+        let (jobs, queue) = mpsc::channel();
+        let health = Arc::new(Mutex::new(Health::default()));
+        let state = health.clone();
+        let worker = thread::spawn(move || work(client, queue, state));
+        Self {
+            jobs,
+            health,
+            worker,
+        }
+    }
+
+    fn submit<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&SysBotClient) -> Result<T, &'static str> + Send + 'static,
+    ) -> Ticket<T> {
+        let (reply, receiver) = mpsc::channel();
+        let job: Job = Box::new(move |client| {
+            let result = work(client);
+            let outcome = result.as_ref().map(|_| ()).map_err(|e| *e);
+            let _ = reply.send(result.map_err(FleetError::Client));
+            outcome
+        });
+        lock(&self.health).pending += 1;
+        if self.jobs.send(job).is_err() {
+            // The reply was dropped with the job, so the ticket reports that the worker stopped
+            lock(&self.health).pending -= 1;
+        }
+        Ticket { receiver }
+    }
+}
+
+/// Named consoles, each with its own work queue
+///
+/// Dropping the fleet does not wait for it: each console finishes its queued work in the
+/// background and then disconnects.
+#[derive(Default)]
+pub struct Fleet {
+    consoles: BTreeMap<String, Console>,
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a connected client under `name`.
+    pub fn add(&mut self, name: &str, client: SysBotClient) -> Result<(), FleetError> {
+        if self.consoles.contains_key(name) {
+            return Err(FleetError::DuplicateName(name.to_string()));
+        }
+        self.consoles
+            .insert(name.to_string(), Console::start(client));
+        Ok(())
+    }
+
+    /// Connects to a console and adds it under `name`.
+    pub fn connect(&mut self, name: &str, addr: &str, port: u16) -> Result<(), FleetError> {
+        if self.consoles.contains_key(name) {
+            return Err(FleetError::DuplicateName(name.to_string()));
+        }
+        let client = SysBotClient::connect(addr, port).map_err(FleetError::Client)?;
+        self.add(name, client)
+    }
+
+    /// Removes a console, waiting for its queued work to finish, and returns its client.
+    pub fn remove(&mut self, name: &str) -> Option<SysBotClient> {
+        let console = self.consoles.remove(name)?;
+        drop(console.jobs);
+        console.worker.join().ok()
+    }
+
+    /// Returns the names of the consoles in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.consoles.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.consoles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.consoles.is_empty()
+    }
+
+    fn console(&self, name: &str) -> Result<&Console, FleetError> {
+        self.consoles
+            .get(name)
+            .ok_or_else(|| FleetError::UnknownConsole(name.to_string()))
+    }
+
+    /// Returns the health of a console
+    pub fn health(&self, name: &str) -> Option<Health> {
+        self.consoles
+            .get(name)
+            .map(|console| lock(&console.health).clone())
+    }
+
+    /// Returns the health of every console
+    pub fn health_all(&self) -> BTreeMap<String, Health> {
+        self.consoles
+            .iter()
+            .map(|(name, console)| (name.clone(), lock(&console.health).clone()))
+            .collect()
+    }
+
+    /// Returns the names of the consoles whose name and health match `predicate`, such as the
+    /// healthy ones or those running a given title.
+    pub fn select(&self, predicate: impl Fn(&str, &Health) -> bool) -> Vec<String> {
+        self.consoles
+            .iter()
+            .filter(|(name, console)| predicate(name, &lock(&console.health)))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Queues work on a console and returns a ticket for its result.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The console to run the work on
+    /// * `work` - Called with the console's client once the work before it has finished
+    pub fn submit<T: Send + 'static>(
+        &self,
+        name: &str,
+        work: impl FnOnce(&SysBotClient) -> Result<T, &'static str> + Send + 'static,
+    ) -> Result<Ticket<T>, FleetError> {
+        Ok(self.console(name)?.submit(work))
+    }
+
+    /// Queues work on a console and waits for its result.
+    pub fn run<T: Send + 'static>(
+        &self,
+        name: &str,
+        work: impl FnOnce(&SysBotClient) -> Result<T, &'static str> + Send + 'static,
+    ) -> Result<T, FleetError> {
+        self.submit(name, work)?.wait()
+    }
+
+    /// Queues the same work on each of the named consoles and returns a ticket for each.
+    ///
+    /// Nothing is queued if any of the names is unknown.
+    pub fn broadcast<T: Send + 'static>(
+        &self,
+        names: &[impl AsRef<str>],
+        work: impl Fn(&SysBotClient) -> Result<T, &'static str> + Send + Sync + 'static,
+    ) -> Result<BTreeMap<String, Ticket<T>>, FleetError> {
+        let consoles = names
+            .iter()
+            .map(|name| Ok((name.as_ref(), self.console(name.as_ref())?)))
+            .collect::<Result<Vec<_>, FleetError>>()?;
+        let work = Arc::new(work);
+        Ok(consoles
+            .into_iter()
+            .map(|(name, console)| {
+                let work = work.clone();
+                (name.to_string(), console.submit(move |client| work(client)))
+            })
+            .collect())
+    }
+
+    /// Queues the same work on every console and returns a ticket for each.
+    pub fn broadcast_all<T: Send + 'static>(
+        &self,
+        work: impl Fn(&SysBotClient) -> Result<T, &'static str> + Send + Sync + 'static,
+    ) -> BTreeMap<String, Ticket<T>> {
+        let names = self.names().map(str::to_string).collect::<Vec<_>>();
+        self.broadcast(&names, work).unwrap_or_default()
+    }
+
+    /// Queues a check of which program a console is running, recording it in its health.
+    pub fn check(&self, name: &str) -> Result<Ticket<RunningProgram>, FleetError> {
+        let console = self.console(name)?;
+        let health = console.health.clone();
+        Ok(console.submit(move |client| {
+            let title = client.running_title()?;
+            lock(&health).title = Some(title);
+            Ok(title)
+        }))
+    }
+
+    /// Queues a check of which program every console is running.
+    pub fn check_all(&self) -> BTreeMap<String, Ticket<RunningProgram>> {
+        self.consoles
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.check(name).ok()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fleet::{Fleet, FleetError, PANICKED};
+    use crate::test_server::TestServer;
+    use crate::types::{Button, RunningProgram};
+    use crate::SysBotClient;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn should_not_block_on_slow_console() {
+        let fast = TestServer::start(|command| match command {
+            "getTitleID" => Some("0100ABCD00000000\n".to_string()),
+            "getBuildID" => Some("00000000DEADBEEF\n".to_string()),
+            _ => None,
+        });
+        let (release, gate) = mpsc::channel::<()>();
+        let slow = TestServer::start(move |command| match command {
+            "getVersion" => {
+                gate.recv().unwrap();
+                Some("2.4\n".to_string())
+            }
+            _ => None,
+        });
+        let mut fleet = Fleet::new();
+        fleet
+            .add(
+                "fast",
+                SysBotClient::connect("127.0.0.1", fast.port).unwrap(),
+            )
+            .unwrap();
+        fleet
+            .add(
+                "slow",
+                SysBotClient::connect("127.0.0.1", slow.port).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            Err(FleetError::DuplicateName("fast".to_string())),
+            fleet.connect("fast", "127.0.0.1", fast.port)
+        );
+        assert!(fleet.broadcast(&["fast", "missing"], |_| Ok(())).is_err());
+
+        let version = fleet.submit("slow", |client| client.get_version()).unwrap();
+        let clicks = fleet.broadcast_all(|client| client.click(Button::A));
+        assert_eq!(Ok(()), clicks["fast"].wait_timeout(Duration::from_secs(5)));
+        let title = fleet.check("fast").unwrap().wait().unwrap();
+        assert_eq!(
+            Err(FleetError::Timeout),
+            clicks["slow"].wait_timeout(Duration::from_millis(10))
+        );
+        let health = fleet.health("slow").unwrap();
+        assert_eq!(2, health.pending);
+        assert!(!health.is_healthy(Duration::ZERO));
+        release.send(()).unwrap();
+
+        assert_eq!(Ok("2.4".to_string()), version.wait());
+        assert_eq!(Ok(()), clicks["slow"].wait_timeout(Duration::from_secs(5)));
+        let expected = RunningProgram {
+            title_id: 0x0100ABCD00000000,
+            build_id: 0xDEADBEEF,
+        };
+        assert_eq!(expected, title);
+        let health = fleet.health_all();
+        assert_eq!(Some(expected), health["fast"].title);
+        assert!(health["fast"].last_success.is_some());
+        assert_eq!(
+            vec!["fast".to_string()],
+            fleet.select(|_, health| health.title.is_some())
+        );
+        assert!(fleet.remove("slow").is_some());
+        assert_eq!(vec!["fast"], fleet.names().collect::<Vec<_>>());
+        drop(fleet);
+        assert_eq!(vec!["click A", "getTitleID", "getBuildID"], fast.finish());
+        assert_eq!(vec!["getVersion", "click A"], slow.finish());
+    }
+
+    #[test]
+    fn should_keep_working_after_panic() {
+        let server = TestServer::start(|_| None);
+        let mut fleet = Fleet::new();
+        fleet.connect("console", "127.0.0.1", server.port).unwrap();
+        let panicking = fleet
+            .submit("console", |_| -> Result<(), &'static str> {
+                panic!("Bad work")
+            })
+            .unwrap();
+        assert_eq!(Err(FleetError::Stopped), panicking.wait());
+        // The ticket hears of the panic before the worker records it
+        while fleet.health("console").unwrap().pending > 0 {
+            thread::yield_now();
+        }
+        let health = fleet.health("console").unwrap();
+        assert_eq!(Some(PANICKED), health.last_error);
+        assert_eq!(None, health.busy_since);
+        assert_eq!(
+            Ok(()),
+            fleet.run("console", |client| client.click(Button::A))
+        );
+        assert_eq!(None, fleet.health("console").unwrap().last_error);
+        drop(fleet);
+        assert_eq!(vec!["click A"], server.finish());
+    }
+}
@@ -0,0 +1,194 @@
+//! Tracking the addresses a client has frozen
+
+use crate::types::{PokeArgs, PokeData};
+use crate::SysBotClient;
+use std::collections::BTreeMap;
+
+/// A value the server keeps writing to a heap address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Freeze {
+    pub label: String,
+    pub addr: u64,
+    pub data: PokeData,
+}
+
+/// Owns a [`SysBotClient`] and remembers what it has frozen
+///
+/// Freezes are tracked by address so the same address is never frozen twice, and are reapplied
+/// when the manager is given a new client. Unless [`set_clear_on_drop`] is turned off, every
+/// freeze is cleared from the server when the manager is dropped.
+///
+/// [`SysBotClient`]: struct@crate::SysBotClient
+/// [`set_clear_on_drop`]: FreezeManager::set_clear_on_drop
+pub struct FreezeManager {
+    client: SysBotClient,
+    freezes: BTreeMap<u64, Freeze>,
+    clear_on_drop: bool,
+}
+
+impl FreezeManager {
+    pub fn new(client: SysBotClient) -> Self {
+        Self {
+            client,
+            freezes: BTreeMap::new(),
+            clear_on_drop: true,
+        }
+    }
+
+    pub fn client(&self) -> &SysBotClient {
+        &self.client
+    }
+
+    /// Sets whether the freezes are cleared from the server when the manager is dropped
+    pub fn set_clear_on_drop(&mut self, clear_on_drop: bool) {
+        self.clear_on_drop = clear_on_drop;
+    }
+
+    /// Freezes a heap address to a value.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - A name to list the freeze under
+    /// * `args` - The address to freeze and the value to keep writing to it
+    pub fn freeze(&mut self, label: &str, args: PokeArgs) -> Result<(), &'static str> {
+        if self.freezes.contains_key(&args.addr) {
+            return Err("Address is already frozen");
+        }
+        self.client.freeze(args.clone())?;
+        self.freezes.insert(
+            args.addr,
+            Freeze {
+                label: label.to_string(),
+                addr: args.addr,
+                data: args.data,
+            },
+        );
+        Ok(())
+    }
+
+    /// Stops freezing an address, returning the freeze that was removed
+    pub fn unfreeze(&mut self, addr: u64) -> Result<Freeze, &'static str> {
+        if !self.freezes.contains_key(&addr) {
+            return Err("Address is not frozen");
+        }
+        self.client.unfreeze(addr)?;
+        Ok(self.freezes.remove(&addr).unwrap())
+    }
+
+    /// Stops freezing every address, including any the manager did not freeze
+    pub fn clear(&mut self) -> Result<(), &'static str> {
+        self.client.freeze_clear()?;
+        self.freezes.clear();
+        Ok(())
+    }
+
+    pub fn get(&self, addr: u64) -> Option<&Freeze> {
+        self.freezes.get(&addr)
+    }
+
+    /// Lists the active freezes in address order
+    pub fn list(&self) -> impl Iterator<Item = &Freeze> {
+        self.freezes.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.freezes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.freezes.is_empty()
+    }
+
+    /// Clears the server's freezes and sends every tracked freeze again
+    pub fn reapply(&self) -> Result<(), &'static str> {
+        self.client.freeze_clear()?;
+        for freeze in self.freezes.values() {
+            self.client.freeze(PokeArgs {
+                addr: freeze.addr,
+                data: freeze.data.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the client with a newly connected one and reapplies every freeze to it
+    pub fn reconnect(&mut self, client: SysBotClient) -> Result<(), &'static str> {
+        self.client = client;
+        self.reapply()
+    }
+
+    /// Checks that the server is freezing as many addresses as the manager tracks
+    pub fn is_in_sync(&self) -> Result<bool, &'static str> {
+        Ok(self.client.freeze_count()? as usize == self.freezes.len())
+    }
+}
+
+impl Drop for FreezeManager {
+    fn drop(&mut self) {
+        if self.clear_on_drop && !self.freezes.is_empty() {
+            let _ = self.client.freeze_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::freeze::FreezeManager;
+    use crate::test_server::TestServer;
+    use crate::types::{PokeArgs, PokeData};
+    use crate::SysBotClient;
+
+    fn args(addr: u64, value: u8) -> PokeArgs {
+        PokeArgs {
+            addr,
+            data: PokeData::new(vec![value]),
+        }
+    }
+
+    #[test]
+    fn should_track_and_clear_on_drop() {
+        let server =
+            TestServer::start(|command| (command == "freezeCount").then(|| "1\n".to_string()));
+        let mut manager =
+            FreezeManager::new(SysBotClient::connect("127.0.0.1", server.port).unwrap());
+        manager.freeze("hp", args(0x20, 0x63)).unwrap();
+        manager.freeze("pp", args(0x10, 0x0A)).unwrap();
+        assert!(manager.freeze("hp again", args(0x20, 0x01)).is_err());
+        assert_eq!(
+            vec!["pp", "hp"],
+            manager.list().map(|f| f.label.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(Ok(false), manager.is_in_sync());
+        assert_eq!("pp", manager.unfreeze(0x10).unwrap().label);
+        assert!(manager.unfreeze(0x10).is_err());
+        assert_eq!(Ok(true), manager.is_in_sync());
+        drop(manager);
+        assert_eq!(
+            vec![
+                "freeze 0x20 0x63",
+                "freeze 0x10 0x0A",
+                "freezeCount",
+                "unFreeze 0x10",
+                "freezeCount",
+                "freezeClear"
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_reapply_after_reconnect() {
+        let first = TestServer::start(|_| None);
+        let second = TestServer::start(|_| None);
+        let mut manager =
+            FreezeManager::new(SysBotClient::connect("127.0.0.1", first.port).unwrap());
+        manager.freeze("hp", args(0x20, 0x63)).unwrap();
+        manager
+            .reconnect(SysBotClient::connect("127.0.0.1", second.port).unwrap())
+            .unwrap();
+        manager.set_clear_on_drop(false);
+        drop(manager);
+        assert_eq!(vec!["freeze 0x20 0x63"], first.finish());
+        assert_eq!(vec!["freezeClear", "freeze 0x20 0x63"], second.finish());
+    }
+}
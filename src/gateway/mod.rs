@@ -0,0 +1,807 @@
+//! An HTTP server that shares one sys-botbase connection between many services
+//!
+//! The [`Gateway`] owns a [`SysBotClient`] and runs every request on a single worker thread, one
+//! at a time and in the order they arrive, so commands from different callers never interleave on
+//! the connection. Request and response bodies are JSON. Addresses may be numbers or `0x` hex
+//! strings, are returned as `0x` hex strings, and memory is written as a hex string.
+//!
+//! | Request         | Body                                                   | Response                                                                   |
+//! |-----------------|--------------------------------------------------------|----------------------------------------------------------------------------|
+//! | `GET /info`     |                                                        | `{"title_id", "build_id", "version", "heap_base", "main_base", "language"}` |
+//! | `POST /peek`    | `{"addr", "size", "region"?}`                          | `{"data"}`                                                                 |
+//! | `POST /poke`    | `{"addr", "data", "region"?}`                          | `{"ok": true}`                                                             |
+//! | `POST /pointer` | `{"chain"}` or `{"jumps"}`, with `"size"` to peek      | `{"address"}`, or `{"data"}` when a size is given                          |
+//! | `POST /input`   | `{"click"}`, `{"press"}`, `{"release"}`, `{"stick", "x", "y"}` or `{"script"}` | `{"ok": true}`                                     |
+//! | `GET /stream`   | A WebSocket upgrade                                    | A live session, described below                                            |
+//!
+//! The region is `heap` (the default), `main` or `absolute`, a chain is written as
+//! `[[main+X]+Y]+Z`, a size is at most [`MAX_PEEK_SIZE`] and a script uses the
+//! [`script`](crate::script) language. Failures are
+//! returned as `{"error": {"kind", "message"}}` with a status matching the [`GatewayError`].
+//!
+//! # Live sessions
+//!
+//! Any number of viewers can hold a WebSocket open on `/stream`. Each text message is a JSON
+//! object and gets one reply, which echoes back a `"ref"` field when the message has one:
+//!
+//! * `{"watch": {"addr", "size", "region"?, "interval_ms"?}}` starts a [`watch`](crate::watch)
+//!   and replies `{"watching": id}`, polling no more often than every 50ms
+//! * `{"unwatch": id}` stops one of the viewer's watches
+//! * anything accepted by `POST /input` moves the controller and replies `{"ok": true}`
+//!
+//! Every change to a watched address is pushed to the viewer that watches it as
+//! `{"event": {"watch", "region", "addr", "old", "new"}}`. Watches are read on the same worker
+//! as every other request, between them, so input is never held up behind a slow poll.
+
+use crate::json::Json;
+use crate::listener::{Connections, Listener};
+use crate::pointer::PointerChain;
+use crate::script::{Script, ScriptError};
+use crate::types::{Button, PeekArgs, PokeArgs, PokeData, Region, Sequence, Stick, StickMovement};
+use crate::watch::{WatchId, Watcher};
+use crate::SysBotClient;
+use std::fmt;
+use std::fmt::{Formatter, Write as _};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+mod stream;
+mod websocket;
+
+/// The largest request body the gateway accepts
+pub const MAX_BODY_SIZE: usize = 0x10000;
+
+/// The most bytes one peek, pointer peek or watch may read
+pub const MAX_PEEK_SIZE: usize = 0x10000;
+
+/// How long a connection may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the worker waits for a request before checking whether a watch is due
+const WATCH_TICK: Duration = Duration::from_millis(10);
+
+/// Why a request failed, which decides the HTTP status and `kind` of the error response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GatewayError {
+    /// The request was malformed or had invalid fields
+    BadRequest(String),
+    /// There is no endpoint at the path
+    NotFound(String),
+    /// The endpoint does not accept the method
+    MethodNotAllowed(String),
+    /// The console could not carry out the command
+    Console(&'static str),
+    /// The connection to the console is lost
+    Connection(&'static str),
+}
+
+impl GatewayError {
+    pub fn status(&self) -> u16 {
+        match self {
+            GatewayError::BadRequest(_) => 400,
+            GatewayError::NotFound(_) => 404,
+            GatewayError::MethodNotAllowed(_) => 405,
+            GatewayError::Console(_) => 502,
+            GatewayError::Connection(_) => 503,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GatewayError::BadRequest(_) => "bad_request",
+            GatewayError::NotFound(_) => "not_found",
+            GatewayError::MethodNotAllowed(_) => "method_not_allowed",
+            GatewayError::Console(_) => "console",
+            GatewayError::Connection(_) => "connection",
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        Json::object([(
+            "error",
+            Json::object([
+                ("kind", Json::string(self.kind())),
+                ("message", Json::string(self.to_string())),
+            ]),
+        )])
+    }
+}
+
+impl fmt::Display for GatewayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GatewayError::BadRequest(message)
+            | GatewayError::NotFound(message)
+            | GatewayError::MethodNotAllowed(message) => write!(f, "{}", message),
+            GatewayError::Console(message) | GatewayError::Connection(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GatewayError {}
+
+impl From<&'static str> for GatewayError {
+    fn from(message: &'static str) -> Self {
+        if SysBotClient::is_connection_error(message) {
+            GatewayError::Connection(message)
+        } else {
+            GatewayError::Console(message)
+        }
+    }
+}
+
+impl From<ScriptError> for GatewayError {
+    fn from(error: ScriptError) -> Self {
+        match error {
+            ScriptError::Client(message) => message.into(),
+            error => GatewayError::BadRequest(error.to_string()),
+        }
+    }
+}
+
+/// A request that has been parsed and is waiting for the client
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Request {
+    Info,
+    Peek(Region, PeekArgs),
+    Poke(Region, PokeArgs),
+    Pointer(Vec<u64>, Option<usize>),
+    Click(Button),
+    Press(Button),
+    Release(Button),
+    Stick(Stick, StickMovement),
+    Sequence(Sequence),
+}
+
+/// Work for the thread that owns the client
+enum Job {
+    Run(Request, Sender<Result<Json, GatewayError>>),
+    Watch {
+        region: Region,
+        addr: u64,
+        size: usize,
+        interval: Duration,
+        events: Sender<websocket::Frame>,
+        reply: Sender<WatchId>,
+    },
+    Unwatch(WatchId),
+}
+
+fn field<'a>(body: &'a Json, name: &str) -> Result<&'a Json, GatewayError> {
+    body.get(name)
+        .ok_or_else(|| GatewayError::BadRequest(format!("Missing field {}", name)))
+}
+
+fn number(body: &Json, name: &str) -> Result<u64, GatewayError> {
+    field(body, name)?
+        .as_u64()
+        .ok_or_else(|| GatewayError::BadRequest(format!("Field {} must be a number", name)))
+}
+
+fn text<'a>(body: &'a Json, name: &str) -> Result<&'a str, GatewayError> {
+    field(body, name)?
+        .as_str()
+        .ok_or_else(|| GatewayError::BadRequest(format!("Field {} must be a string", name)))
+}
+
+fn size(body: &Json) -> Result<usize, GatewayError> {
+    match number(body, "size")? {
+        0 => Err(GatewayError::BadRequest(
+            "Field size must be at least 1".to_string(),
+        )),
+        size if size > MAX_PEEK_SIZE as u64 => Err(GatewayError::BadRequest(format!(
+            "Field size must be at most 0x{:X}",
+            MAX_PEEK_SIZE
+        ))),
+        size => Ok(size as usize),
+    }
+}
+
+fn region(body: &Json) -> Result<Region, GatewayError> {
+    match body.get("region") {
+        Some(_) => text(body, "region")?
+            .parse()
+            .map_err(|e: &str| GatewayError::BadRequest(e.to_string())),
+        None => Ok(Region::Heap),
+    }
+}
+
+fn bytes(body: &Json, name: &str) -> Result<Vec<u8>, GatewayError> {
+    let value = text(body, name)?;
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    let invalid = || GatewayError::BadRequest(format!("Field {} must be hex bytes", name));
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02X}", b);
+        s
+    })
+}
+
+fn button(body: &Json, name: &str) -> Result<Button, GatewayError> {
+    text(body, name)?
+        .parse()
+        .map_err(|e: &str| GatewayError::BadRequest(e.to_string()))
+}
+
+fn axis(body: &Json, name: &str) -> Result<i16, GatewayError> {
+    field(body, name)?
+        .as_i64()
+        .and_then(|v| i16::try_from(v).ok())
+        .ok_or_else(|| {
+            GatewayError::BadRequest(format!("Field {} must be between -32768 and 32767", name))
+        })
+}
+
+/// Parses a request into the command to run, without touching the client
+fn route(method: &str, path: &str, body: &str) -> Result<Request, GatewayError> {
+    let expected = match path {
+        "/info" | "/stream" => "GET",
+        "/peek" | "/poke" | "/pointer" | "/input" => "POST",
+        _ => return Err(GatewayError::NotFound(format!("No endpoint at {}", path))),
+    };
+    if method != expected {
+        return Err(GatewayError::MethodNotAllowed(format!(
+            "{} only accepts {}",
+            path, expected
+        )));
+    }
+    match path {
+        "/info" => return Ok(Request::Info),
+        "/stream" => {
+            return Err(GatewayError::BadRequest(
+                "/stream needs a WebSocket upgrade".to_string(),
+            ))
+        }
+        _ => {}
+    }
+    let body = Json::parse(body).map_err(|e| GatewayError::BadRequest(e.to_string()))?;
+    if !matches!(body, Json::Object(_)) {
+        return Err(GatewayError::BadRequest(
+            "The body must be a JSON object".to_string(),
+        ));
+    }
+    match path {
+        "/peek" => Ok(Request::Peek(
+            region(&body)?,
+            PeekArgs {
+                addr: number(&body, "addr")?,
+                size: size(&body)?,
+            },
+        )),
+        "/poke" => Ok(Request::Poke(
+            region(&body)?,
+            PokeArgs {
+                addr: number(&body, "addr")?,
+                data: PokeData::new(bytes(&body, "data")?),
+            },
+        )),
+        "/pointer" => {
+            let jumps = match (body.get("chain"), body.get("jumps")) {
+                (Some(_), _) => {
+                    text(&body, "chain")?
+                        .parse::<PointerChain>()
+                        .map_err(|e| GatewayError::BadRequest(e.to_string()))?
+                        .jumps
+                }
+                (None, Some(jumps)) => jumps
+                    .as_array()
+                    .ok_or_else(|| {
+                        GatewayError::BadRequest("Field jumps must be an array".to_string())
+                    })?
+                    .iter()
+                    .map(|jump| {
+                        jump.as_u64().ok_or_else(|| {
+                            GatewayError::BadRequest("Every jump must be a number".to_string())
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                (None, None) => {
+                    return Err(GatewayError::BadRequest(
+                        "Missing field chain or jumps".to_string(),
+                    ))
+                }
+            };
+            if jumps.is_empty() {
+                return Err(GatewayError::BadRequest(
+                    "A pointer needs at least one jump".to_string(),
+                ));
+            }
+            let size = match body.get("size") {
+                Some(_) => Some(size(&body)?),
+                None => None,
+            };
+            Ok(Request::Pointer(jumps, size))
+        }
+        _ => input(&body),
+    }
+}
+
+/// Parses the body of `POST /input`, which live sessions also accept as a message
+fn input(body: &Json) -> Result<Request, GatewayError> {
+    if body.get("click").is_some() {
+        Ok(Request::Click(button(body, "click")?))
+    } else if body.get("press").is_some() {
+        Ok(Request::Press(button(body, "press")?))
+    } else if body.get("release").is_some() {
+        Ok(Request::Release(button(body, "release")?))
+    } else if body.get("stick").is_some() {
+        let stick = text(body, "stick")?
+            .parse()
+            .map_err(|e: &str| GatewayError::BadRequest(e.to_string()))?;
+        Ok(Request::Stick(
+            stick,
+            StickMovement(axis(body, "x")?, axis(body, "y")?),
+        ))
+    } else if body.get("script").is_some() {
+        Ok(Request::Sequence(
+            Script::parse(text(body, "script")?)?.compile()?,
+        ))
+    } else {
+        Err(GatewayError::BadRequest(
+            "Input needs one of click, press, release, stick or script".to_string(),
+        ))
+    }
+}
+
+fn ok() -> Json {
+    Json::object([("ok", Json::Bool(true))])
+}
+
+/// Runs a request against the client, on the worker thread
+fn execute(client: &SysBotClient, request: Request) -> Result<Json, GatewayError> {
+    match request {
+        Request::Info => Ok(Json::object([
+            ("title_id", Json::hex(client.get_title_id()?)),
+            ("build_id", Json::hex(client.get_build_id()?)),
+            ("version", Json::string(client.get_version()?)),
+            ("heap_base", Json::hex(client.get_heap_base()?)),
+            ("main_base", Json::hex(client.get_main_nso_base()?)),
+            ("language", Json::number(client.get_system_language()?)),
+        ])),
+        Request::Peek(region, args) => {
+            let size = args.size;
+            let mut bytes = client.peek_region(region, args)?;
+            bytes.truncate(size);
+            Ok(Json::object([("data", Json::string(hex(&bytes)))]))
+        }
+        Request::Poke(region, args) => client
+            .poke_region(region, args)
+            .map(|_| ok())
+            .map_err(GatewayError::from),
+        Request::Pointer(jumps, Some(size)) => {
+            let mut bytes = client.pointer_peek(&jumps, size)?;
+            bytes.truncate(size);
+            Ok(Json::object([("data", Json::string(hex(&bytes)))]))
+        }
+        Request::Pointer(jumps, None) => Ok(Json::object([(
+            "address",
+            Json::hex(client.pointer_all(&jumps)?),
+        )])),
+        Request::Click(button) => client
+            .click(button)
+            .map(|_| ok())
+            .map_err(GatewayError::from),
+        Request::Press(button) => client
+            .press(button)
+            .map(|_| ok())
+            .map_err(GatewayError::from),
+        Request::Release(button) => client
+            .release(button)
+            .map(|_| ok())
+            .map_err(GatewayError::from),
+        Request::Stick(stick, movement) => client
+            .set_stick(stick, movement)
+            .map(|_| ok())
+            .map_err(GatewayError::from),
+        Request::Sequence(sequence) => client
+            .run_sequence(&sequence)
+            .map(|_| ok())
+            .map_err(GatewayError::from),
+    }
+}
+
+/// Runs jobs one at a time until every sender is gone, polling the watches in between
+fn work(client: SysBotClient, queue: Receiver<Job>) {
+    let mut watcher = Watcher::new();
+    loop {
+        let job = if watcher.is_empty() {
+            queue.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            queue.recv_timeout(WATCH_TICK)
+        };
+        match job {
+            Ok(Job::Run(request, reply)) => {
+                let _ = reply.send(execute(&client, request));
+            }
+            Ok(Job::Watch {
+                region,
+                addr,
+                size,
+                interval,
+                events,
+                reply,
+            }) => {
+                let id = watcher.watch_with(region, addr, size, interval, move |event| {
+                    let _ = events.send(stream::event_message(event));
+                });
+                let _ = reply.send(id);
+            }
+            Ok(Job::Unwatch(id)) => {
+                watcher.unwatch(id);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        // A failed poll is retried on the next tick; requests report a lost connection themselves
+        let _ = watcher.poll(&client);
+    }
+}
+
+/// The method, path, headers and body of an HTTP request
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl HttpRequest {
+    /// Returns the value of a header, matching its name without regard to case
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Result<HttpRequest, GatewayError> {
+    let malformed = || GatewayError::BadRequest("Malformed HTTP request".to_string());
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| malformed())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(malformed());
+    };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    let mut headers = vec![];
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|_| malformed())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or_else(malformed)?;
+        headers.push((name.to_string(), value.trim().to_string()));
+    }
+    let mut request = HttpRequest {
+        method,
+        path,
+        headers,
+        body: String::new(),
+    };
+    let content_length = match request.header("content-length") {
+        Some(len) => len.parse().map_err(|_| malformed())?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_SIZE {
+        return Err(GatewayError::BadRequest(format!(
+            "The body must be at most {} bytes",
+            MAX_BODY_SIZE
+        )));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|_| malformed())?;
+    request.body = String::from_utf8(body)
+        .map_err(|_| GatewayError::BadRequest("The body must be UTF-8".to_string()))?;
+    Ok(request)
+}
+
+fn write_response(mut stream: &TcpStream, status: u16, body: &Json) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        502 => "Bad Gateway",
+        _ => "Service Unavailable",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Reads one request from the connection, queues it for the worker and writes back the result
+fn handle(stream: TcpStream, jobs: Sender<Job>, sessions: Connections) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let request = read_request(&stream);
+    if let Ok(request) = &request {
+        if request.method == "GET"
+            && request.path == "/stream"
+            && request.header("upgrade").is_some()
+        {
+            return stream::serve(stream, request, jobs, sessions);
+        }
+    }
+    let result = request
+        .and_then(|request| route(&request.method, &request.path, &request.body))
+        .and_then(|request| {
+            let (reply, response) = mpsc::channel();
+            let stopped = || GatewayError::Connection("The gateway has stopped");
+            jobs.send(Job::Run(request, reply)).map_err(|_| stopped())?;
+            response.recv().map_err(|_| stopped())?
+        });
+    let _ = match result {
+        Ok(body) => write_response(&stream, 200, &body),
+        Err(error) => write_response(&stream, error.status(), &error.to_json()),
+    };
+}
+
+/// An HTTP server that owns a [`SysBotClient`] and serialises every request onto it
+///
+/// The server stops, and the client disconnects, when the gateway is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use sysbot_rs::gateway::Gateway;
+/// use sysbot_rs::SysBotClient;
+///
+/// let client = SysBotClient::connect("192.168.0.2", 6000).unwrap();
+/// let gateway = Gateway::start(client, "127.0.0.1:8080").unwrap();
+/// // curl -d '{"addr": "0x8A3F70", "size": 4}' http://127.0.0.1:8080/peek
+/// gateway.wait();
+/// ```
+pub struct Gateway {
+    listener: Listener,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Gateway {
+    /// Starts serving on `addr`, taking ownership of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The connected client that every request is run on
+    /// * `addr` - The address to listen on, with port 0 picking a free port
+    pub fn start(client: SysBotClient, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (jobs, queue): (Sender<Job>, Receiver<Job>) = mpsc::channel();
+        let listener = Listener::start(addr, move |stream, sessions| {
+            handle(stream, jobs.clone(), sessions)
+        })?;
+        let worker = thread::spawn(move || work(client, queue));
+        Ok(Self {
+            listener,
+            worker: Some(worker),
+        })
+    }
+
+    /// Returns the address the gateway is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests until the listener fails, which in practice is forever
+    pub fn wait(mut self) {
+        self.listener.wait();
+    }
+
+    /// Stops accepting requests, closes live sessions, finishes the requests already queued and
+    /// disconnects the client
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.listener.stop();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Gateway {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gateway::Gateway;
+    use crate::json::Json;
+    use crate::test_server::TestServer;
+    use crate::SysBotClient;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Json) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, Json::parse(body).unwrap())
+    }
+
+    fn error_kind(response: &(u16, Json)) -> (u16, &str) {
+        let kind = response
+            .1
+            .get("error")
+            .and_then(|e| e.get("kind"))
+            .and_then(Json::as_str)
+            .unwrap();
+        (response.0, kind)
+    }
+
+    fn start(handler: fn(&str) -> Option<String>) -> (TestServer, Gateway) {
+        let server = TestServer::start(handler);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let gateway = Gateway::start(client, "127.0.0.1:0").unwrap();
+        (server, gateway)
+    }
+
+    #[test]
+    fn should_serve_memory_requests() {
+        let (server, gateway) = start(|command| match command.split(' ').next() {
+            Some("peekMain") => Some("0A0B0C0D\n".to_string()),
+            Some("pointerAll") => Some("000000000000ABCD\n".to_string()),
+            Some("pointerPeek") => Some("FFEE\n".to_string()),
+            _ => None,
+        });
+        let addr = gateway.local_addr();
+        let (status, body) = request(
+            addr,
+            "POST",
+            "/peek",
+            r#"{"addr": "0x10", "size": 4, "region": "main"}"#,
+        );
+        assert_eq!(200, status);
+        assert_eq!(Some("0A0B0C0D"), body.get("data").and_then(Json::as_str));
+        let (status, _) = request(addr, "POST", "/poke", r#"{"addr": 32, "data": "0x0102"}"#);
+        assert_eq!(200, status);
+        let (_, body) = request(addr, "POST", "/pointer", r#"{"chain": "[[main+18]+10]+8"}"#);
+        assert_eq!(Some("0xABCD"), body.get("address").and_then(Json::as_str));
+        let (_, body) = request(
+            addr,
+            "POST",
+            "/pointer",
+            r#"{"jumps": ["0x18", 8], "size": 2}"#,
+        );
+        assert_eq!(Some("FFEE"), body.get("data").and_then(Json::as_str));
+        gateway.shutdown();
+        assert_eq!(
+            vec![
+                "peekMain 0x10 0x4",
+                "poke 0x20 0x0102",
+                "pointerAll 0x18 0x10 0x8",
+                "pointerPeek 0x2 0x18 0x8",
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_send_input() {
+        let (server, gateway) = start(|_| None);
+        let addr = gateway.local_addr();
+        for body in [
+            r#"{"click": "A"}"#,
+            r#"{"press": "ZL"}"#,
+            r#"{"stick": "LSTICK", "x": -32768, "y": 0}"#,
+            r#"{"script": "release ZL"}"#,
+        ] {
+            assert_eq!(200, request(addr, "POST", "/input", body).0);
+        }
+        gateway.shutdown();
+        assert_eq!(
+            vec![
+                "click A",
+                "press ZL",
+                "setStick LSTICK -32768 0",
+                "clickSeq -ZL",
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_reject_oversized_reads() {
+        let (server, gateway) = start(|_| None);
+        let addr = gateway.local_addr();
+        for (path, body) in [
+            ("/peek", r#"{"addr": 16, "size": 65537}"#),
+            ("/peek", r#"{"addr": 16, "size": 2147483647}"#),
+            ("/peek", r#"{"addr": 16, "size": "0xFFFFFFFFFFFFFFFF"}"#),
+            ("/pointer", r#"{"jumps": [24, 8], "size": 65537}"#),
+        ] {
+            assert_eq!(
+                (400, "bad_request"),
+                error_kind(&request(addr, "POST", path, body))
+            );
+        }
+        gateway.shutdown();
+        assert!(server.finish().is_empty());
+    }
+
+    #[test]
+    fn should_return_structured_errors() {
+        let (server, gateway) = start(|command| match command {
+            "getTitleID" | "getBuildID" | "getHeapBase" | "getMainNsoBase" => {
+                Some("0100ABCD0000C000\n".to_string())
+            }
+            "getVersion" => Some("2.4\n".to_string()),
+            "getSystemLanguage" => Some("x\n".to_string()),
+            _ => None,
+        });
+        let addr = gateway.local_addr();
+        assert_eq!(
+            (404, "not_found"),
+            error_kind(&request(addr, "GET", "/missing", ""))
+        );
+        assert_eq!(
+            (405, "method_not_allowed"),
+            error_kind(&request(addr, "GET", "/peek", ""))
+        );
+        assert_eq!(
+            (400, "bad_request"),
+            error_kind(&request(addr, "POST", "/peek", "{\"addr\": 1"))
+        );
+        assert_eq!(
+            (400, "bad_request"),
+            error_kind(&request(addr, "POST", "/peek", r#"{"addr": 1}"#))
+        );
+        assert_eq!(
+            (400, "bad_request"),
+            error_kind(&request(addr, "POST", "/input", r#"{"click": "Q"}"#))
+        );
+        let response = request(addr, "GET", "/info", "");
+        assert_eq!((502, "console"), error_kind(&response));
+        assert_eq!(
+            Some("Failed to parse string to u8"),
+            response
+                .1
+                .get("error")
+                .and_then(|e| e.get("message"))
+                .and_then(Json::as_str)
+        );
+        gateway.shutdown();
+        assert_eq!(6, server.finish().len());
+    }
+}
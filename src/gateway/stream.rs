@@ -0,0 +1,262 @@
+//! Live sessions on `GET /stream`
+//!
+//! Each viewer gets a reader, which runs its messages through the gateway's queue, and a writer,
+//! which sends the replies along with the change events of every watch the viewer registered.
+//! A viewer's watches are removed when it disconnects.
+
+use crate::gateway::websocket::{accept_key, read_frame, write_frame, Fragments, Frame};
+use crate::gateway::{input, number, region, size, GatewayError, HttpRequest, Job};
+use crate::json::Json;
+use crate::listener::Connections;
+use crate::watch::{WatchEvent, WatchId};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// How often a watch is read when the viewer does not give an `interval_ms`
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The shortest `interval_ms` a viewer may ask for, so a watch cannot take over the worker
+const MIN_WATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Formats a watch event as the message pushed to the viewer
+pub(crate) fn event_message(event: &WatchEvent) -> Frame {
+    let hex = |bytes: &[u8]| Json::string(super::hex(bytes));
+    Frame::Text(
+        Json::object([(
+            "event",
+            Json::object([
+                ("watch", Json::number(u64::from(event.id))),
+                ("region", Json::string(event.region.to_string())),
+                ("addr", Json::hex(event.addr)),
+                ("old", hex(&event.old)),
+                ("new", hex(&event.new)),
+            ]),
+        )])
+        .to_string(),
+    )
+}
+
+/// Runs one message from a viewer, returning the reply
+fn reply(
+    body: &Json,
+    jobs: &Sender<Job>,
+    outgoing: &Sender<Frame>,
+    watches: &mut BTreeMap<u64, WatchId>,
+) -> Result<Json, GatewayError> {
+    let stopped = || GatewayError::Connection("The gateway has stopped");
+    if !matches!(body, Json::Object(_)) {
+        return Err(GatewayError::BadRequest(
+            "A message must be a JSON object".to_string(),
+        ));
+    }
+    if let Some(watch) = body.get("watch") {
+        let interval = match watch.get("interval_ms") {
+            Some(_) => Duration::from_millis(number(watch, "interval_ms")?).max(MIN_WATCH_INTERVAL),
+            None => DEFAULT_WATCH_INTERVAL,
+        };
+        let (reply, id) = mpsc::channel();
+        let job = Job::Watch {
+            region: region(watch)?,
+            addr: number(watch, "addr")?,
+            size: size(watch)?,
+            interval,
+            events: outgoing.clone(),
+            reply,
+        };
+        jobs.send(job).map_err(|_| stopped())?;
+        let id = id.recv().map_err(|_| stopped())?;
+        watches.insert(u64::from(id), id);
+        return Ok(Json::object([("watching", Json::number(u64::from(id)))]));
+    }
+    if body.get("unwatch").is_some() {
+        let id = watches
+            .remove(&number(body, "unwatch")?)
+            .ok_or_else(|| GatewayError::BadRequest("No watch with that id".to_string()))?;
+        jobs.send(Job::Unwatch(id)).map_err(|_| stopped())?;
+        return Ok(Json::object([("ok", Json::Bool(true))]));
+    }
+    let (reply, response) = mpsc::channel();
+    jobs.send(Job::Run(input(body)?, reply))
+        .map_err(|_| stopped())?;
+    response.recv().map_err(|_| stopped())?
+}
+
+/// Answers the handshake and serves the viewer until it disconnects
+pub(crate) fn serve(
+    mut stream: TcpStream,
+    request: &HttpRequest,
+    jobs: Sender<Job>,
+    sessions: Connections,
+) {
+    let upgrade = request
+        .header("upgrade")
+        .is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+    let Some(key) = request.header("sec-websocket-key").filter(|_| upgrade) else {
+        let error = GatewayError::BadRequest("/stream needs a WebSocket upgrade".to_string());
+        let _ = super::write_response(&stream, error.status(), &error.to_json());
+        return;
+    };
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    let (Ok(mut writer), Ok(peer)) = (stream.try_clone(), stream.peer_addr()) else {
+        return;
+    };
+    if stream.write_all(handshake.as_bytes()).is_err() || stream.set_read_timeout(None).is_err() {
+        return;
+    }
+    if let Ok(session) = stream.try_clone() {
+        sessions.lock().unwrap().insert(peer, session);
+    }
+    let (outgoing, frames) = mpsc::channel::<Frame>();
+    let sender = thread::spawn(move || {
+        for frame in frames {
+            let close = frame == Frame::Close;
+            if write_frame(&mut writer, &frame, None).is_err() || close {
+                break;
+            }
+        }
+    });
+    let mut watches = BTreeMap::new();
+    let mut fragments = Fragments::default();
+    while let Ok(frame) = read_frame(&mut stream, &mut fragments) {
+        let text = match frame {
+            Frame::Text(text) => text,
+            Frame::Ping(data) => {
+                let _ = outgoing.send(Frame::Pong(data));
+                continue;
+            }
+            Frame::Binary(_) | Frame::Pong(_) => continue,
+            Frame::Close => break,
+        };
+        let message = Json::parse(&text).map_err(|e| GatewayError::BadRequest(e.to_string()));
+        let reference = message.as_ref().ok().and_then(|m| m.get("ref")).cloned();
+        let mut response = message
+            .and_then(|body| reply(&body, &jobs, &outgoing, &mut watches))
+            .unwrap_or_else(|error| error.to_json());
+        if let (Json::Object(fields), Some(reference)) = (&mut response, reference) {
+            fields.insert("ref".to_string(), reference);
+        }
+        let _ = outgoing.send(Frame::Text(response.to_string()));
+    }
+    for id in watches.into_values() {
+        let _ = jobs.send(Job::Unwatch(id));
+    }
+    let _ = outgoing.send(Frame::Close);
+    let _ = sender.join();
+    sessions.lock().unwrap().remove(&peer);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gateway::websocket::{read_frame, write_frame, Fragments, Frame};
+    use crate::gateway::Gateway;
+    use crate::json::Json;
+    use crate::test_server::TestServer;
+    use crate::SysBotClient;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn open(addr: SocketAddr) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET /stream HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = vec![];
+        let mut byte = [0];
+        while !response.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        stream
+    }
+
+    fn send(stream: &mut TcpStream, message: &str) {
+        let frame = Frame::Text(message.to_string());
+        write_frame(stream, &frame, Some([7, 1, 2, 3])).unwrap();
+    }
+
+    fn receive(stream: &mut TcpStream) -> Json {
+        match read_frame(stream, &mut Fragments::default()).unwrap() {
+            Frame::Text(text) => Json::parse(&text).unwrap(),
+            frame => panic!("Unexpected frame {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn should_stream_watches_and_input_to_viewers() {
+        let polls = AtomicUsize::new(0);
+        let server = TestServer::start(move |command| match command.split(' ').next() {
+            Some("peekMulti") => {
+                let poll = polls.fetch_add(1, Ordering::SeqCst);
+                Some(format!("{:02X}\n", poll.min(1)))
+            }
+            _ => None,
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let gateway = Gateway::start(client, "127.0.0.1:0").unwrap();
+        let mut viewer = open(gateway.local_addr());
+        let mut controller = open(gateway.local_addr());
+
+        send(
+            &mut viewer,
+            r#"{"ref": 1, "watch": {"addr": "0x10", "size": 1, "interval_ms": 0}}"#,
+        );
+        send(&mut controller, r#"{"ref": "a", "press": "A"}"#);
+        assert_eq!(
+            r#"{"ok":true,"ref":"a"}"#,
+            receive(&mut controller).to_string()
+        );
+        send(&mut controller, r#"{"release": "Q"}"#);
+        let error = receive(&mut controller);
+        assert_eq!(
+            Some("bad_request"),
+            error
+                .get("error")
+                .and_then(|e| e.get("kind"))
+                .and_then(Json::as_str)
+        );
+        let mut messages = [receive(&mut viewer), receive(&mut viewer)];
+        messages.sort_by_key(|m| m.get("event").is_some());
+        assert_eq!(r#"{"ref":1,"watching":0}"#, messages[0].to_string());
+        assert_eq!(
+            r#"{"event":{"addr":"0x10","new":"01","old":"00","region":"heap","watch":0}}"#,
+            messages[1].to_string()
+        );
+        send(&mut viewer, r#"{"unwatch": 5}"#);
+        assert!(receive(&mut viewer).get("error").is_some());
+        send(
+            &mut viewer,
+            r#"{"watch": {"addr": "0x10", "size": "0x7FFFFFFF"}}"#,
+        );
+        assert!(receive(&mut viewer).get("error").is_some());
+
+        write_frame(&mut controller, &Frame::Close, Some([0; 4])).unwrap();
+        assert_eq!(
+            Frame::Close,
+            read_frame(&mut controller, &mut Fragments::default()).unwrap()
+        );
+        gateway.shutdown();
+        assert!(!matches!(
+            read_frame(&mut viewer, &mut Fragments::default()),
+            Ok(Frame::Text(_))
+        ));
+        let commands = server.finish();
+        assert!(commands.contains(&"press A".to_string()));
+        assert!(commands
+            .iter()
+            .all(|c| c == "press A" || c == "peekMulti 0x10 0x1"));
+    }
+}
@@ -0,0 +1,239 @@
+//! The parts of the WebSocket protocol (RFC 6455) the gateway needs
+//!
+//! Only what a server has to do is covered: answering the opening handshake, and reading masked
+//! frames from clients while writing unmasked ones back. Fragmented messages are joined before
+//! they are returned, and control frames sent between their fragments are returned as they arrive.
+
+use crate::gateway::MAX_BODY_SIZE;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+/// Appended to the client's key before hashing it for the handshake
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+/// A complete WebSocket message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, state) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The start of a fragmented message whose final fragment has not been read yet
+///
+/// Kept by the caller between calls to [`read_frame`], so that a control frame arriving between
+/// fragments can be returned without losing the fragments read so far.
+#[derive(Debug, Default)]
+pub(crate) struct Fragments(Option<(u8, Vec<u8>)>);
+
+/// Returns the `Sec-WebSocket-Accept` value that answers a client's `Sec-WebSocket-Key`
+pub(crate) fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// Reads the next message, joining fragments and unmasking the payload
+pub(crate) fn read_frame(reader: &mut impl Read, fragments: &mut Fragments) -> io::Result<Frame> {
+    let invalid = |message: &'static str| io::Error::new(ErrorKind::InvalidData, message);
+    let message = &mut fragments.0;
+    loop {
+        let mut header = [0; 2];
+        reader.read_exact(&mut header)?;
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0F;
+        let len = match header[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0; 8];
+                reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        if len > MAX_BODY_SIZE as u64 {
+            return Err(invalid("WebSocket frame is too large"));
+        }
+        let mut mask = [0; 4];
+        if header[1] & 0x80 != 0 {
+            reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0; len as usize];
+        reader.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        match (opcode, &mut *message) {
+            (CLOSE, _) => return Ok(Frame::Close),
+            (PING, _) => return Ok(Frame::Ping(payload)),
+            (PONG, _) => return Ok(Frame::Pong(payload)),
+            (CONTINUATION, Some((_, data))) => data.extend(payload),
+            (TEXT | BINARY, None) => *message = Some((opcode, payload)),
+            _ => return Err(invalid("Unexpected WebSocket frame")),
+        }
+        if fin {
+            break;
+        }
+        if message
+            .as_ref()
+            .is_some_and(|(_, data)| data.len() > MAX_BODY_SIZE)
+        {
+            return Err(invalid("WebSocket message is too large"));
+        }
+    }
+    match message.take() {
+        Some((TEXT, data)) => String::from_utf8(data)
+            .map(Frame::Text)
+            .map_err(|_| invalid("WebSocket text is not UTF-8")),
+        Some((_, data)) => Ok(Frame::Binary(data)),
+        None => Err(invalid("Unexpected WebSocket frame")),
+    }
+}
+
+/// Writes a message as a single frame, masked with `mask` when one is given
+///
+/// Servers never mask their frames, so only a client passes a mask.
+pub(crate) fn write_frame(
+    writer: &mut impl Write,
+    frame: &Frame,
+    mask: Option<[u8; 4]>,
+) -> io::Result<()> {
+    let (opcode, payload) = match frame {
+        Frame::Text(text) => (TEXT, text.as_bytes()),
+        Frame::Binary(data) => (BINARY, data.as_slice()),
+        Frame::Ping(data) => (PING, data.as_slice()),
+        Frame::Pong(data) => (PONG, data.as_slice()),
+        Frame::Close => (CLOSE, &[][..]),
+    };
+    let masked = if mask.is_some() { 0x80 } else { 0 };
+    let mut bytes = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => bytes.push(masked | len as u8),
+        len @ 126..=0xFFFF => {
+            bytes.push(masked | 126);
+            bytes.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            bytes.push(masked | 127);
+            bytes.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            bytes.extend_from_slice(&mask);
+            bytes.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => bytes.extend_from_slice(payload),
+    }
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gateway::websocket::{accept_key, read_frame, write_frame, Fragments, Frame};
+
+    #[test]
+    fn should_answer_handshake_key() {
+        // The example from RFC 6455
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+    }
+
+    #[test]
+    fn should_round_trip_frames() {
+        let long = Frame::Text("x".repeat(300));
+        let mut bytes = vec![];
+        write_frame(&mut bytes, &long, Some([1, 2, 3, 4])).unwrap();
+        write_frame(&mut bytes, &Frame::Ping(vec![9]), None).unwrap();
+        // A text message split over a text frame and a continuation frame, with a ping between
+        bytes.extend_from_slice(&[0x01, 0x02, b'h', b'i', 0x89, 0x01, 7, 0x80, 0x01, b'!']);
+        let mut reader = bytes.as_slice();
+        let mut fragments = Fragments::default();
+        let mut read = || read_frame(&mut reader, &mut fragments);
+        assert_eq!(long, read().unwrap());
+        assert_eq!(Frame::Ping(vec![9]), read().unwrap());
+        assert_eq!(Frame::Ping(vec![7]), read().unwrap());
+        assert_eq!(Frame::Text("hi!".to_string()), read().unwrap());
+        assert!(read().is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! A small JSON value used by the network front ends
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Display, Formatter, Write};
+
+/// A JSON value
+///
+/// Numbers keep their text so that 64-bit addresses are not rounded through `f64`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    pub fn parse(source: &str) -> Result<Json, &'static str> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err("Unexpected characters after JSON value");
+        }
+        Ok(value)
+    }
+
+    pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn number(value: impl Display) -> Json {
+        Json::Number(value.to_string())
+    }
+
+    pub fn string(value: impl Into<String>) -> Json {
+        Json::String(value.into())
+    }
+
+    /// A number written as a `0x` hex string, since JSON numbers cannot hold every `u64`
+    pub fn hex(value: u64) -> Json {
+        Json::String(format!("0x{:X}", value))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Reads a number, or a string holding a decimal or `0x` hex number
+    pub fn as_u64(&self) -> Option<u64> {
+        let text = match self {
+            Json::Number(n) | Json::String(n) => n.as_str(),
+            _ => return None,
+        };
+        match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => text.parse().ok(),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, &'static str> {
+        for expected in word.chars() {
+            if self.next() != Some(expected) {
+                return Err("Invalid JSON literal");
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, &'static str> {
+        self.whitespace();
+        match self.peek().ok_or("Unexpected end of JSON")? {
+            'n' => self.literal("null", Json::Null),
+            't' => self.literal("true", Json::Bool(true)),
+            'f' => self.literal("false", Json::Bool(false)),
+            '"' => Ok(Json::String(self.string()?)),
+            '[' => {
+                self.pos += 1;
+                let mut values = vec![];
+                self.whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => continue,
+                        Some(']') => return Ok(Json::Array(values)),
+                        _ => return Err("Expected , or ] in JSON array"),
+                    }
+                }
+            }
+            '{' => {
+                self.pos += 1;
+                let mut fields = BTreeMap::new();
+                self.whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.whitespace();
+                    if self.peek() != Some('"') {
+                        return Err("Expected a string key in JSON object");
+                    }
+                    let key = self.string()?;
+                    self.whitespace();
+                    if self.next() != Some(':') {
+                        return Err("Expected : in JSON object");
+                    }
+                    fields.insert(key, self.value()?);
+                    self.whitespace();
+                    match self.next() {
+                        Some(',') => continue,
+                        Some('}') => return Ok(Json::Object(fields)),
+                        _ => return Err("Expected , or } in JSON object"),
+                    }
+                }
+            }
+            '-' | '0'..='9' => {
+                let start = self.pos;
+                while matches!(self.peek(), Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+                    self.pos += 1;
+                }
+                let number = self.chars[start..self.pos].iter().collect::<String>();
+                number.parse::<f64>().map_err(|_| "Invalid JSON number")?;
+                Ok(Json::Number(number))
+            }
+            _ => Err("Unexpected character in JSON"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next().ok_or("Unterminated JSON string")? {
+                '"' => return Ok(s),
+                '\\' => match self.next().ok_or("Unterminated JSON string")? {
+                    '"' => s.push('"'),
+                    '\\' => s.push('\\'),
+                    '/' => s.push('/'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let hex = self
+                            .chars
+                            .get(self.pos..self.pos + 4)
+                            .ok_or("Invalid JSON escape")?
+                            .iter()
+                            .collect::<String>();
+                        self.pos += 4;
+                        let code =
+                            u32::from_str_radix(&hex, 16).map_err(|_| "Invalid JSON escape")?;
+                        s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    _ => return Err("Invalid JSON escape"),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::Json;
+
+    #[test]
+    fn should_round_trip_values() {
+        let json = Json::parse(
+            r#" {"addr": "0x8A3F70", "size": 18446744073709551615, "jumps": [1, -2.5e3],
+                "name": "Pika\"chu\nA", "ok": true, "none": null} "#,
+        )
+        .unwrap();
+        assert_eq!(Some(0x8A3F70), json.get("addr").and_then(Json::as_u64));
+        assert_eq!(Some(u64::MAX), json.get("size").and_then(Json::as_u64));
+        assert_eq!(
+            Some("Pika\"chu\nA"),
+            json.get("name").and_then(Json::as_str)
+        );
+        assert_eq!(
+            r#"{"addr":"0x8A3F70","jumps":[1,-2.5e3],"name":"Pika\"chu\nA","none":null,"ok":true,"size":18446744073709551615}"#,
+            json.to_string()
+        );
+        assert!(Json::parse("{\"a\":}").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+}
@@ -1,6 +1,28 @@
 //! A library for creating [sys-botbase](https://github.com/olliz0r/sys-botbase) controllers in Rust
 
+pub mod cheat;
 mod client;
+pub mod discovery;
+pub mod dump;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fleet;
+pub mod freeze;
+pub mod gateway;
+mod json;
+mod listener;
+pub mod pointer;
+pub mod protocol;
+pub mod proxy;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod recording;
+pub mod scan;
+pub mod script;
+pub mod snapshot;
+#[cfg(test)]
+mod test_server;
 pub mod types;
+pub mod watch;
 
 pub use client::*;
@@ -0,0 +1,99 @@
+//! The TCP listener shared by the servers that front a console
+//!
+//! A [`Listener`] accepts connections on its own thread and serves each one on a new thread.
+//! Servers register the connections that stay open in [`Connections`], so that stopping the
+//! listener can close them rather than wait for their peers to hang up.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+/// The open connections, so they can be closed when the listener stops
+pub(crate) type Connections = Arc<Mutex<HashMap<SocketAddr, TcpStream>>>;
+
+/// Accepts connections until it is stopped or dropped
+pub(crate) struct Listener {
+    addr: SocketAddr,
+    stopping: Arc<AtomicBool>,
+    connections: Connections,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    /// Starts listening on `addr`, calling `serve` on a new thread for every connection
+    pub(crate) fn start(
+        addr: impl ToSocketAddrs,
+        serve: impl Fn(TcpStream, Connections) + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stopping = Arc::new(AtomicBool::new(false));
+        let connections = Connections::default();
+        let serve = Arc::new(serve);
+        let stop = stopping.clone();
+        let open = connections.clone();
+        let acceptor = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let serve = serve.clone();
+                    let open = open.clone();
+                    thread::spawn(move || serve(stream, open));
+                }
+            }
+        });
+        Ok(Self {
+            addr,
+            stopping,
+            connections,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns how many registered connections are open
+    pub(crate) fn connections(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    /// Accepts connections until the listener fails, which in practice is forever
+    pub(crate) fn wait(&mut self) {
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+
+    /// Stops accepting connections and closes the registered ones
+    ///
+    /// Connections still being served finish on their own threads once their streams close.
+    pub(crate) fn stop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        if let Some(acceptor) = self.acceptor.take() {
+            // The acceptor only checks the flag once a connection arrives, so make one
+            let mut wake = self.addr;
+            if wake.ip().is_unspecified() {
+                wake.set_ip(Ipv4Addr::LOCALHOST.into());
+            }
+            let _ = TcpStream::connect(wake);
+            let _ = acceptor.join();
+        }
+        for connection in self.connections.lock().unwrap().values() {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,350 @@
+//! Finding pointer chains from main to a heap address
+//!
+//! Heap addresses move between sessions, but a chain of pointers starting at a fixed offset in
+//! main usually does not. A [`PointerScanner`] works backwards from a target address through a
+//! [`MemoryDump`] of main and the heap to find such chains, and [`cross_filter`] keeps the chains
+//! that still lead to the target in dumps taken from other sessions.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::pointer::{cross_filter, MemoryDump, PointerScanner};
+//! use sysbot_rs::SysBotClient;
+//! let client = SysBotClient::connect("0.0.0.0", 6000).unwrap();
+//! let first = MemoryDump::capture(&client, 0x1000000, 0x10000000).unwrap();
+//! let target = first.heap_base + 0x8A3F7C;
+//! let chains = PointerScanner::new().with_max_depth(3).scan(&first, target);
+//! // Restart the game and find the address again...
+//! let second = MemoryDump::capture(&client, 0x1000000, 0x10000000).unwrap();
+//! let second_target = second.heap_base + 0x7C2B14;
+//! for chain in cross_filter(chains, &[(&second, second_target)]) {
+//!     println!("{}", chain);
+//! }
+//! ```
+
+use crate::dump::{read_dump, DumpError};
+use crate::scan::{read_range, DEFAULT_CHUNK_SIZE};
+use crate::types::Region;
+use crate::SysBotClient;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A copy of main and the heap along with the addresses they were read from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryDump {
+    pub main_base: u64,
+    pub main: Vec<u8>,
+    pub heap_base: u64,
+    pub heap: Vec<u8>,
+}
+
+impl MemoryDump {
+    pub fn new(main_base: u64, main: Vec<u8>, heap_base: u64, heap: Vec<u8>) -> Self {
+        Self {
+            main_base,
+            main,
+            heap_base,
+            heap,
+        }
+    }
+
+    /// Reads the first `main_len` bytes of main and `heap_len` bytes of the heap
+    pub fn capture(
+        client: &SysBotClient,
+        main_len: usize,
+        heap_len: usize,
+    ) -> Result<Self, &'static str> {
+        Ok(Self {
+            main_base: client.get_main_nso_base()?,
+            main: read_range(client, Region::Main, 0, main_len, DEFAULT_CHUNK_SIZE)?,
+            heap_base: client.get_heap_base()?,
+            heap: read_range(client, Region::Heap, 0, heap_len, DEFAULT_CHUNK_SIZE)?,
+        })
+    }
+
+    /// Loads dumps of main and the heap that both start at the beginning of their region
+    pub fn load(
+        main_path: impl AsRef<Path>,
+        heap_path: impl AsRef<Path>,
+    ) -> Result<Self, DumpError> {
+        let (main_header, main) = read_dump(main_path)?;
+        let (heap_header, heap) = read_dump(heap_path)?;
+        if main_header.region != Region::Main || heap_header.region != Region::Heap {
+            return Err(DumpError::Format("Expected dumps of main and the heap"));
+        }
+        if main_header.start != 0 || heap_header.start != 0 {
+            return Err(DumpError::Format("Dumps must start at the region base"));
+        }
+        Ok(MemoryDump::new(
+            main_header.base,
+            main,
+            heap_header.base,
+            heap,
+        ))
+    }
+
+    fn in_main(&self, addr: u64) -> bool {
+        addr >= self.main_base && addr - self.main_base < self.main.len() as u64
+    }
+
+    fn in_heap(&self, addr: u64) -> bool {
+        addr >= self.heap_base && addr - self.heap_base < self.heap.len() as u64
+    }
+
+    /// Reads the pointer stored at an absolute address, if it is inside the dump
+    pub fn read_u64(&self, addr: u64) -> Option<u64> {
+        let (base, bytes) = if self.in_main(addr) {
+            (self.main_base, &self.main)
+        } else if self.in_heap(addr) {
+            (self.heap_base, &self.heap)
+        } else {
+            return None;
+        };
+        let offset = (addr - base) as usize;
+        Some(u64::from_le_bytes(
+            bytes.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    }
+
+    /// Every aligned pointer into the heap as `(value, address)`, sorted by value
+    fn heap_pointers(&self) -> Vec<(u64, u64)> {
+        let mut pointers = [(self.main_base, &self.main), (self.heap_base, &self.heap)]
+            .into_iter()
+            .flat_map(|(base, bytes)| {
+                bytes.chunks_exact(8).enumerate().map(move |(i, chunk)| {
+                    (
+                        u64::from_le_bytes(chunk.try_into().unwrap()),
+                        base + i as u64 * 8,
+                    )
+                })
+            })
+            .filter(|(value, _)| self.in_heap(*value))
+            .collect::<Vec<_>>();
+        pointers.sort_unstable();
+        pointers
+    }
+}
+
+/// A path from main to an address, written as `[[main+X]+Y]+Z`
+///
+/// The first jump is an offset from main and the last is added to the final pointer, matching
+/// the jumps taken by [`SysBotClient::pointer_peek`].
+///
+/// [`SysBotClient::pointer_peek`]: crate::SysBotClient::pointer_peek
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PointerChain {
+    pub jumps: Vec<u64>,
+}
+
+impl PointerChain {
+    /// Follows the chain through a dump, returning the address it leads to
+    pub fn resolve(&self, dump: &MemoryDump) -> Option<u64> {
+        let (first, rest) = self.jumps.split_first()?;
+        let (last, middle) = rest.split_last()?;
+        let mut pointer = dump.read_u64(dump.main_base.wrapping_add(*first))?;
+        for jump in middle {
+            pointer = dump.read_u64(pointer.wrapping_add(*jump))?;
+        }
+        Some(pointer.wrapping_add(*last))
+    }
+}
+
+impl Display for PointerChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Some((last, derefs)) = self.jumps.split_last() else {
+            return write!(f, "main");
+        };
+        write!(f, "{}main", "[".repeat(derefs.len()))?;
+        for jump in derefs {
+            write!(f, "+{:X}]", jump)?;
+        }
+        write!(f, "+{:X}", last)
+    }
+}
+
+impl FromStr for PointerChain {
+    type Err = &'static str;
+
+    /// Parses a chain such as `[[main+4C1E2A8]+68]+10`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let rest = s.trim_start_matches('[');
+        let derefs = s.len() - rest.len();
+        let rest = rest
+            .strip_prefix("main")
+            .ok_or("Pointer chain must start at main")?;
+        let parts = rest.split(']').collect::<Vec<_>>();
+        if derefs == 0 || parts.len() != derefs + 1 {
+            return Err("Unbalanced brackets in pointer chain");
+        }
+        let jumps = parts
+            .iter()
+            .map(|part| {
+                let hex = part.strip_prefix('+').ok_or("Expected + before offset")?;
+                let hex = hex.strip_prefix("0x").unwrap_or(hex);
+                u64::from_str_radix(hex, 16).map_err(|_| "Invalid offset in pointer chain")
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { jumps })
+    }
+}
+
+/// Searches a [`MemoryDump`] for pointer chains from main to an address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PointerScanner {
+    max_depth: usize,
+    max_offset: u64,
+    max_results: usize,
+}
+
+impl Default for PointerScanner {
+    fn default() -> Self {
+        Self {
+            max_depth: 4,
+            max_offset: 0x800,
+            max_results: 10_000,
+        }
+    }
+}
+
+impl PointerScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the most pointers a chain may follow
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the largest offset allowed between a pointer and the address it leads to
+    pub fn with_max_offset(mut self, max_offset: u64) -> Self {
+        self.max_offset = max_offset;
+        self
+    }
+
+    /// Stops the scan once this many chains have been found
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    /// Finds chains from main to `target`, an absolute address, shortest first
+    pub fn scan(&self, dump: &MemoryDump, target: u64) -> Vec<PointerChain> {
+        let pointers = dump.heap_pointers();
+        let mut results = BTreeSet::new();
+        let mut visited = BTreeSet::from([target]);
+        let mut level = vec![(target, vec![])];
+        for depth in 1..=self.max_depth {
+            let mut next = vec![];
+            for (addr, suffix) in &level {
+                let low = addr.saturating_sub(self.max_offset);
+                let first = pointers.partition_point(|(value, _)| *value < low);
+                for (value, slot) in pointers[first..].iter().take_while(|(v, _)| v <= addr) {
+                    let mut jumps = vec![addr - value];
+                    jumps.extend(suffix);
+                    if dump.in_main(*slot) {
+                        jumps.insert(0, slot - dump.main_base);
+                        results.insert((depth, PointerChain { jumps }));
+                        if results.len() >= self.max_results {
+                            return results.into_iter().map(|(_, chain)| chain).collect();
+                        }
+                    } else if depth < self.max_depth && visited.insert(*slot) {
+                        next.push((*slot, jumps));
+                    }
+                }
+            }
+            level = next;
+        }
+        results.into_iter().map(|(_, chain)| chain).collect()
+    }
+}
+
+/// Keeps the chains that lead to the paired target in every dump
+pub fn cross_filter(chains: Vec<PointerChain>, dumps: &[(&MemoryDump, u64)]) -> Vec<PointerChain> {
+    chains
+        .into_iter()
+        .filter(|chain| {
+            dumps
+                .iter()
+                .all(|(dump, target)| chain.resolve(dump) == Some(*target))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pointer::{cross_filter, MemoryDump, PointerChain, PointerScanner};
+
+    const MAIN: u64 = 0x8000_0000;
+    const HEAP: u64 = 0x1_0000_0000;
+
+    fn write(bytes: &mut [u8], offset: usize, value: u64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Main holds a pointer to an object at `object`, which points to another at `inner`
+    fn dump(object: u64, inner: u64) -> MemoryDump {
+        let mut main = vec![0; 0x40];
+        let mut heap = vec![0; 0x400];
+        write(&mut main, 0x18, HEAP + object);
+        write(&mut heap, object as usize + 0x10, HEAP + inner);
+        MemoryDump::new(MAIN, main, HEAP, heap)
+    }
+
+    #[test]
+    fn should_find_chains() {
+        let dump = dump(0x100, 0x200);
+        let target = HEAP + 0x208;
+        let scanner = PointerScanner::new().with_max_offset(0x80);
+        let chains = scanner.clone().scan(&dump, target);
+        assert_eq!(
+            vec!["[[main+18]+10]+8".parse::<PointerChain>().unwrap()],
+            chains
+        );
+        assert_eq!(Some(target), chains[0].resolve(&dump));
+        assert!(scanner
+            .clone()
+            .with_max_depth(1)
+            .scan(&dump, target)
+            .is_empty());
+        assert_eq!(
+            vec![PointerChain {
+                jumps: vec![0x18, 0x8]
+            }],
+            PointerScanner::new()
+                .with_max_depth(1)
+                .scan(&dump, HEAP + 0x108)
+        );
+    }
+
+    #[test]
+    fn should_cross_filter_dumps() {
+        let first = dump(0x100, 0x200);
+        let mut chains = PointerScanner::new().scan(&first, HEAP + 0x208);
+        assert_eq!(2, chains.len());
+        chains.push("[main+20]+0".parse().unwrap());
+        let second = dump(0x300, 0x80);
+        assert_eq!(
+            vec!["[[main+18]+10]+8".parse::<PointerChain>().unwrap()],
+            cross_filter(chains.clone(), &[(&second, HEAP + 0x88)])
+        );
+        assert!(cross_filter(chains, &[(&second, HEAP + 0x208)]).is_empty());
+    }
+
+    #[test]
+    fn should_round_trip_notation() {
+        let chain = PointerChain {
+            jumps: vec![0x4C1E2A8, 0x68, 0x10],
+        };
+        assert_eq!("[[main+4C1E2A8]+68]+10", chain.to_string());
+        assert_eq!(Ok(chain), "[[main+0x4C1E2A8]+68]+10".parse());
+        assert!("[main+10".parse::<PointerChain>().is_err());
+        assert!("[[main+10]+8".parse::<PointerChain>().is_err());
+        assert!("main+10".parse::<PointerChain>().is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! What the sys-botbase text protocol looks like on the wire
+//!
+//! Every command is a single line ending in `\r\n`. Most commands are silent; the ones listed in
+//! [`RESPONDING_COMMANDS`] answer with a single line ending in `\n`.
+//!
+//! Newer sys-botbase versions add commands, and an older version silently ignores a command it
+//! does not know, so a client waiting for its response would wait forever. [`Capabilities`]
+//! records which commands a version supports, going by a table of the versions that commands
+//! first appeared in. The crate does not ship such a table, since a wrong entry would make a client
+//! refuse a command the console supports, so callers pass one they have checked against the
+//! sys-botbase release notes.
+
+use std::collections::BTreeSet;
+
+/// The commands that sys-botbase answers with a response line
+pub const RESPONDING_COMMANDS: [&str; 22] = [
+    "peek",
+    "peekMulti",
+    "peekAbsolute",
+    "peekAbsoluteMulti",
+    "peekMain",
+    "peekMainMulti",
+    "pointer",
+    "pointerAll",
+    "pointerRelative",
+    "pointerPeek",
+    "pointerPeekMulti",
+    "getTitleID",
+    "getTitleVersion",
+    "getBuildID",
+    "getSystemLanguage",
+    "getHeapBase",
+    "getMainNsoBase",
+    "getVersion",
+    "isProgramRunning",
+    "pixelPeek",
+    "freezeCount",
+    "charge",
+];
+
+/// Returns the name of a command line, which is its first word
+pub fn command_name(command: &str) -> &str {
+    command.split_whitespace().next().unwrap_or_default()
+}
+
+/// Returns whether sys-botbase answers the command with a response line
+///
+/// # Example
+///
+/// ```
+/// use sysbot_rs::protocol::responds;
+/// assert!(responds("peek 0x8A3F70 0x4"));
+/// assert!(!responds("click A"));
+/// ```
+pub fn responds(command: &str) -> bool {
+    RESPONDING_COMMANDS.contains(&command_name(command))
+}
+
+/// The error returned when the client cannot connect to the console
+pub const CONNECT_FAILED: &str = "Failed to connect to TcpStream";
+
+/// The error returned when a command cannot be written to the connection
+pub const SEND_FAILED: &str = "Failed to send command";
+
+/// The error returned when the connection closes or fails before a response arrives
+pub const RECEIVE_FAILED: &str = "Failed to receive a response";
+
+/// The error returned by a client whose connection has already been closed
+pub const NOT_CONNECTED: &str = "SysBotClient not connected";
+
+/// The errors that mean a connection is unusable, rather than the console failing one command
+///
+/// Gateways, bindings and the CLI map these to their own connection errors, so every error the
+/// client raises for a broken connection must be listed here.
+pub const CONNECTION_ERRORS: [&str; 4] =
+    [CONNECT_FAILED, SEND_FAILED, RECEIVE_FAILED, NOT_CONNECTED];
+
+/// The error returned for a command that the connected sys-botbase version does not support
+pub const UNSUPPORTED: &str = "Command not supported by this sys-botbase version";
+
+/// The error returned when capabilities are asked for without any command versions to go by
+pub const NO_COMMAND_VERSIONS: &str =
+    "No command versions to check the sys-botbase version against";
+
+/// The error returned when the version reported by `getVersion` is not `major.minor`
+pub const UNKNOWN_VERSION: &str = "Unrecognised sys-botbase version";
+
+/// The commands a sys-botbase version supports
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    version: String,
+    unsupported: BTreeSet<&'static str>,
+}
+
+impl Capabilities {
+    /// Returns the capabilities of a version as reported by `getVersion`, such as `"2.4"`
+    ///
+    /// Commands not listed in `versions` are assumed to be supported by every version. Fails with
+    /// [`NO_COMMAND_VERSIONS`] if `versions` is empty and [`UNKNOWN_VERSION`] if the version
+    /// cannot be parsed, since either way nothing could be marked unsupported.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version reported by `getVersion`
+    /// * `versions` - The `(major, minor)` version that each command first appeared in
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sysbot_rs::protocol::Capabilities;
+    /// let capabilities = Capabilities::with_versions("1.8", &[("pixelPeek", (1, 9))]).unwrap();
+    /// assert!(capabilities.supports("pointerAll 0x4C1DC58 0x18"));
+    /// assert!(!capabilities.supports("pixelPeek"));
+    /// ```
+    pub fn with_versions(
+        version: &str,
+        versions: &[(&'static str, (u32, u32))],
+    ) -> Result<Self, &'static str> {
+        if versions.is_empty() {
+            return Err(NO_COMMAND_VERSIONS);
+        }
+        let version = version.trim();
+        let mut parts = version.split('.').map(str::parse::<u32>);
+        let parsed = match (parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor))) => (major, minor),
+            (Some(Ok(major)), None) => (major, 0),
+            _ => return Err(UNKNOWN_VERSION),
+        };
+        let unsupported = versions
+            .iter()
+            .filter(|(_, since)| parsed < *since)
+            .map(|(command, _)| *command)
+            .collect();
+        Ok(Self {
+            version: version.to_string(),
+            unsupported,
+        })
+    }
+
+    /// Returns the version reported by `getVersion`
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns whether a command, given by name or as a whole command line, is supported
+    pub fn supports(&self, command: &str) -> bool {
+        !self.unsupported.contains(command_name(command))
+    }
+
+    /// Returns the known commands that are not supported
+    pub fn unsupported(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.unsupported.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::{Capabilities, NO_COMMAND_VERSIONS, UNKNOWN_VERSION};
+
+    /// Made-up versions, since the tests only check how a table is applied
+    const VERSIONS: [(&str, (u32, u32)); 4] = [
+        ("freeze", (2, 0)),
+        ("isProgramRunning", (2, 1)),
+        ("pointerPeekMulti", (2, 1)),
+        ("charge", (2, 2)),
+    ];
+
+    #[test]
+    fn should_support_commands_by_version() {
+        let old = Capabilities::with_versions("2.0\n", &VERSIONS).unwrap();
+        assert_eq!("2.0", old.version());
+        assert!(old.supports("freeze 0x10 0x01"));
+        assert!(!old.supports("isProgramRunning 0x0100ABF008968000"));
+        assert_eq!(
+            vec!["charge", "isProgramRunning", "pointerPeekMulti"],
+            old.unsupported().collect::<Vec<_>>()
+        );
+        let new = Capabilities::with_versions("2.4", &VERSIONS).unwrap();
+        assert_eq!(0, new.unsupported().count());
+    }
+
+    #[test]
+    fn should_refuse_unknown_versions_and_empty_tables() {
+        assert_eq!(
+            Err(UNKNOWN_VERSION),
+            Capabilities::with_versions("custom", &VERSIONS)
+        );
+        assert_eq!(
+            Err(UNKNOWN_VERSION),
+            Capabilities::with_versions("2.", &VERSIONS)
+        );
+        assert_eq!(
+            Err(NO_COMMAND_VERSIONS),
+            Capabilities::with_versions("2.4", &[])
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! A sys-botbase compatible server that lets many tools share one console
+//!
+//! The [`Proxy`] listens for the same text protocol sys-botbase speaks, so tools such as PKHeX
+//! can connect to it as if it were the console. Each command line from a downstream connection
+//! is queued, run on the one upstream [`SysBotClient`] in the order it arrived, and its response
+//! line, if the command has one, is written back to the connection that sent it. Every
+//! connection and command is reported to a log callback as a [`ProxyEvent`]. Commands that would
+//! change the upstream connection for every tool, such as `configure echoCommands`, are refused.
+//!
+//! sys-botbase sends no reply to `configure`, so a refused command is only reported to the log
+//! callback and the tool that sent it is told nothing. A tool that turns on `echoCommands` will
+//! believe echo is on while its commands are never echoed, so tools should be set up to run with
+//! echo off before pointing them at the proxy.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::proxy::Proxy;
+//! use sysbot_rs::SysBotClient;
+//!
+//! let client = SysBotClient::connect("192.168.0.2", 6000).unwrap();
+//! let proxy = Proxy::start(client, "0.0.0.0:6000", |event| eprintln!("{}", event)).unwrap();
+//! proxy.wait();
+//! ```
+
+use crate::listener::Listener;
+use crate::SysBotClient;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Something that happened on a downstream connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProxyEvent {
+    Connected(SocketAddr),
+    /// A command was run, with the length of its response if it had one
+    Command {
+        peer: SocketAddr,
+        command: String,
+        response: Option<usize>,
+    },
+    /// A command was refused, or could not be run upstream, in which case the connection is
+    /// closed
+    Failed {
+        peer: SocketAddr,
+        command: String,
+        error: &'static str,
+    },
+    Disconnected(SocketAddr),
+}
+
+impl fmt::Display for ProxyEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyEvent::Connected(peer) => write!(f, "{} connected", peer),
+            ProxyEvent::Command {
+                peer,
+                command,
+                response: Some(len),
+            } => write!(f, "{} > {} ({} bytes)", peer, command, len),
+            ProxyEvent::Command {
+                peer,
+                command,
+                response: None,
+            } => write!(f, "{} > {}", peer, command),
+            ProxyEvent::Failed {
+                peer,
+                command,
+                error,
+            } => write!(f, "{} > {} failed: {}", peer, command, error),
+            ProxyEvent::Disconnected(peer) => write!(f, "{} disconnected", peer),
+        }
+    }
+}
+
+/// The error logged for a command that would change the upstream connection for everyone
+pub const REFUSED: &str = "Command would change the connection shared with other tools";
+
+type Log = Arc<dyn Fn(&ProxyEvent) + Send + Sync>;
+
+type Job = (String, Sender<Result<Option<Vec<u8>>, &'static str>>);
+
+/// Returns whether a command is refused instead of being run upstream
+///
+/// With `echoCommands` on, sys-botbase writes every command back before its response, which every
+/// other connection would read as the response to its own command.
+fn is_refused(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    words.next() == Some("configure") && words.next() == Some("echoCommands")
+}
+
+/// Relays command lines from one downstream connection until it disconnects
+fn relay(stream: TcpStream, peer: SocketAddr, jobs: Sender<Job>, log: Log) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    log(&ProxyEvent::Connected(peer));
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let command = line.trim().to_string();
+        if command.is_empty() {
+            continue;
+        }
+        if is_refused(&command) {
+            log(&ProxyEvent::Failed {
+                peer,
+                command,
+                error: REFUSED,
+            });
+            continue;
+        }
+        let (reply, response) = mpsc::channel();
+        let result = jobs
+            .send((command.clone(), reply))
+            .map_err(|_| "The proxy has stopped")
+            .and_then(|_| response.recv().map_err(|_| "The proxy has stopped")?);
+        match result {
+            Ok(response) => {
+                log(&ProxyEvent::Command {
+                    peer,
+                    command,
+                    response: response.as_ref().map(|r| r.len()),
+                });
+                if let Some(response) = response {
+                    if writer
+                        .write_all(&response)
+                        .and_then(|_| writer.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            Err(error) => {
+                log(&ProxyEvent::Failed {
+                    peer,
+                    command,
+                    error,
+                });
+                break;
+            }
+        }
+    }
+    log(&ProxyEvent::Disconnected(peer));
+}
+
+/// A TCP server that shares a [`SysBotClient`] between any number of sys-botbase clients
+///
+/// The proxy stops, closing every downstream connection and disconnecting the client, when it
+/// is shut down or dropped.
+pub struct Proxy {
+    listener: Listener,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Proxy {
+    /// Starts listening on `addr`, taking ownership of the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The connected client that every command is run on
+    /// * `addr` - The address to listen on, with port 0 picking a free port
+    /// * `log` - Called with every connection, command and failure
+    pub fn start(
+        client: SysBotClient,
+        addr: impl ToSocketAddrs,
+        log: impl Fn(&ProxyEvent) + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let (jobs, queue): (Sender<Job>, Receiver<Job>) = mpsc::channel();
+        let log: Log = Arc::new(log);
+        let listener = Listener::start(addr, move |stream, connections| {
+            let (Ok(peer), Ok(connection)) = (stream.peer_addr(), stream.try_clone()) else {
+                return;
+            };
+            connections.lock().unwrap().insert(peer, connection);
+            relay(stream, peer, jobs.clone(), log.clone());
+            connections.lock().unwrap().remove(&peer);
+        })?;
+        let worker = thread::spawn(move || {
+            for (command, reply) in queue {
+                let _ = reply.send(client.raw(&command));
+            }
+        });
+        Ok(Self {
+            listener,
+            worker: Some(worker),
+        })
+    }
+
+    /// Returns the address the proxy is listening on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// Returns how many downstream connections are open
+    pub fn connections(&self) -> usize {
+        self.listener.connections()
+    }
+
+    /// Serves connections until the listener fails, which in practice is forever
+    pub fn wait(mut self) {
+        self.listener.wait();
+    }
+
+    /// Stops accepting connections, closes the open ones and disconnects the client
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.listener.stop();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proxy::{Proxy, ProxyEvent, REFUSED};
+    use crate::test_server::TestServer;
+    use crate::SysBotClient;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::sync::{Arc, Mutex};
+
+    fn send(stream: &mut BufReader<TcpStream>, command: &str) {
+        stream
+            .get_mut()
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .unwrap();
+    }
+
+    fn receive(stream: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        stream.read_line(&mut line).unwrap();
+        line
+    }
+
+    #[test]
+    fn should_relay_commands_from_every_connection() {
+        let server = TestServer::start(|command| match command {
+            "peek 0x10 0x2" => Some("0A0B\n".to_string()),
+            "getVersion" => Some("2.4\n".to_string()),
+            _ => None,
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let log = events.clone();
+        let proxy = Proxy::start(client, "127.0.0.1:0", move |event| {
+            log.lock().unwrap().push(event.clone())
+        })
+        .unwrap();
+        let mut bot = BufReader::new(TcpStream::connect(proxy.local_addr()).unwrap());
+        let mut editor = BufReader::new(TcpStream::connect(proxy.local_addr()).unwrap());
+        send(&mut bot, "click A");
+        send(&mut bot, "getVersion");
+        assert_eq!("2.4\n", receive(&mut bot));
+        send(&mut editor, "peek 0x10 0x2");
+        assert_eq!("0A0B\n", receive(&mut editor));
+        let editor_addr = editor.get_ref().local_addr().unwrap();
+        drop(editor);
+        while proxy.connections() > 1 {
+            std::thread::yield_now();
+        }
+        proxy.shutdown();
+        assert_eq!(
+            vec!["click A", "getVersion", "peek 0x10 0x2"],
+            server.finish()
+        );
+        let events = events.lock().unwrap();
+        assert!(events.contains(&ProxyEvent::Command {
+            peer: editor_addr,
+            command: "peek 0x10 0x2".to_string(),
+            response: Some(5),
+        }));
+        assert!(events.contains(&ProxyEvent::Disconnected(editor_addr)));
+        assert_eq!(
+            format!("{} > peek 0x10 0x2 (5 bytes)", editor_addr),
+            ProxyEvent::Command {
+                peer: editor_addr,
+                command: "peek 0x10 0x2".to_string(),
+                response: Some(5),
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn should_refuse_echoing_commands() {
+        let server =
+            TestServer::start(|command| (command == "getVersion").then(|| "2.4\n".to_string()));
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let log = events.clone();
+        let proxy = Proxy::start(client, "127.0.0.1:0", move |event| {
+            log.lock().unwrap().push(event.clone())
+        })
+        .unwrap();
+        let mut tool = BufReader::new(TcpStream::connect(proxy.local_addr()).unwrap());
+        let peer = tool.get_ref().local_addr().unwrap();
+        send(&mut tool, "configure echoCommands 1");
+        send(&mut tool, "getVersion");
+        assert_eq!("2.4\n", receive(&mut tool));
+        proxy.shutdown();
+        assert_eq!(vec!["getVersion"], server.finish());
+        assert!(events.lock().unwrap().contains(&ProxyEvent::Failed {
+            peer,
+            command: "configure echoCommands 1".to_string(),
+            error: REFUSED,
+        }));
+    }
+}
@@ -0,0 +1,566 @@
+//! Python bindings, built with the `python` feature
+//!
+//! The extension module is named `sysbot_rs` and is built with
+//! [maturin](https://www.maturin.rs), which reads its settings from `pyproject.toml`:
+//!
+//! ```text
+//! maturin develop --release
+//! ```
+//!
+//! It exposes [`SysBotClient`](crate::SysBotClient) with the same methods as the Rust client, taking and returning
+//! `int` addresses and `bytes` data, along with the `Button`, `Stick` and `ConfigureOption`
+//! types. Buttons may also be given by name, as in `client.click("A")`. The interpreter lock is
+//! released while a command waits on the console, so other Python threads keep running.
+//!
+//! Client errors are raised as `SysBotConnectionError` when the console cannot be reached and
+//! as `SysBotError`, its base class, otherwise. Invalid arguments raise `ValueError`.
+//!
+//! ```python
+//! from sysbot_rs import SysBotClient, Button, Stick
+//!
+//! client = SysBotClient("192.168.0.2", 6000)
+//! client.click(Button.A)
+//! client.set_stick(Stick.LEFT, 0, 0x7FFF)
+//! party = client.peek(0x8A3F70, 0x158)
+//! client.poke_main(0x100, b"\x01\x02")
+//! ```
+
+use crate::types::{Button, ConfigureOption, PeekArgs, PokeArgs, PokeData, Region, Stick};
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::sync::{Mutex, MutexGuard};
+
+pyo3::create_exception!(
+    sysbot_rs,
+    SysBotError,
+    PyException,
+    "Raised when sys-botbase cannot carry out a command."
+);
+pyo3::create_exception!(
+    sysbot_rs,
+    SysBotConnectionError,
+    SysBotError,
+    "Raised when the console cannot be reached or the connection was lost."
+);
+
+fn client_error(error: &'static str) -> PyErr {
+    if crate::SysBotClient::is_connection_error(error) {
+        SysBotConnectionError::new_err(error)
+    } else {
+        SysBotError::new_err(error)
+    }
+}
+
+fn value_error(error: &'static str) -> PyErr {
+    PyValueError::new_err(error)
+}
+
+/// A button on the controller
+#[pyclass(
+    name = "Button",
+    module = "sysbot_rs",
+    eq,
+    eq_int,
+    frozen,
+    from_py_object
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PyButton {
+    A,
+    B,
+    X,
+    Y,
+    LSTICK,
+    RSTICK,
+    L,
+    R,
+    ZL,
+    ZR,
+    PLUS,
+    MINUS,
+    DLEFT,
+    DUP,
+    DDOWN,
+    DRIGHT,
+    HOME,
+    CAPTURE,
+}
+
+impl From<PyButton> for Button {
+    fn from(button: PyButton) -> Self {
+        match button {
+            PyButton::A => Button::A,
+            PyButton::B => Button::B,
+            PyButton::X => Button::X,
+            PyButton::Y => Button::Y,
+            PyButton::LSTICK => Button::STICK(Stick::LEFT),
+            PyButton::RSTICK => Button::STICK(Stick::RIGHT),
+            PyButton::L => Button::L,
+            PyButton::R => Button::R,
+            PyButton::ZL => Button::ZL,
+            PyButton::ZR => Button::ZR,
+            PyButton::PLUS => Button::PLUS,
+            PyButton::MINUS => Button::MINUS,
+            PyButton::DLEFT => Button::DLEFT,
+            PyButton::DUP => Button::DUP,
+            PyButton::DDOWN => Button::DDOWN,
+            PyButton::DRIGHT => Button::DRIGHT,
+            PyButton::HOME => Button::HOME,
+            PyButton::CAPTURE => Button::CAPTURE,
+        }
+    }
+}
+
+impl From<Button> for PyButton {
+    fn from(button: Button) -> Self {
+        match button {
+            Button::A => PyButton::A,
+            Button::B => PyButton::B,
+            Button::X => PyButton::X,
+            Button::Y => PyButton::Y,
+            Button::STICK(Stick::LEFT) => PyButton::LSTICK,
+            Button::STICK(Stick::RIGHT) => PyButton::RSTICK,
+            Button::L => PyButton::L,
+            Button::R => PyButton::R,
+            Button::ZL => PyButton::ZL,
+            Button::ZR => PyButton::ZR,
+            Button::PLUS => PyButton::PLUS,
+            Button::MINUS => PyButton::MINUS,
+            Button::DLEFT => PyButton::DLEFT,
+            Button::DUP => PyButton::DUP,
+            Button::DDOWN => PyButton::DDOWN,
+            Button::DRIGHT => PyButton::DRIGHT,
+            Button::HOME => PyButton::HOME,
+            Button::CAPTURE => PyButton::CAPTURE,
+        }
+    }
+}
+
+#[pymethods]
+impl PyButton {
+    /// Parses a button name as sys-botbase spells it, such as `"A"` or `"DU"`.
+    #[staticmethod]
+    fn parse(name: &str) -> PyResult<Self> {
+        name.parse::<Button>().map(Self::from).map_err(value_error)
+    }
+
+    fn __str__(&self) -> String {
+        Button::from(*self).to_string()
+    }
+}
+
+/// A button given either as a `Button` or by name
+#[derive(FromPyObject)]
+enum ButtonArg {
+    Button(PyButton),
+    Name(String),
+}
+
+impl TryFrom<ButtonArg> for Button {
+    type Error = PyErr;
+
+    fn try_from(button: ButtonArg) -> PyResult<Self> {
+        match button {
+            ButtonArg::Button(button) => Ok(button.into()),
+            ButtonArg::Name(name) => name.parse().map_err(value_error),
+        }
+    }
+}
+
+/// One of the two sticks on the controller
+#[pyclass(
+    name = "Stick",
+    module = "sysbot_rs",
+    eq,
+    eq_int,
+    frozen,
+    from_py_object
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PyStick {
+    LEFT,
+    RIGHT,
+}
+
+impl From<PyStick> for Stick {
+    fn from(stick: PyStick) -> Self {
+        match stick {
+            PyStick::LEFT => Stick::LEFT,
+            PyStick::RIGHT => Stick::RIGHT,
+        }
+    }
+}
+
+#[pymethods]
+impl PyStick {
+    fn __str__(&self) -> String {
+        Stick::from(*self).to_string()
+    }
+}
+
+/// A sys-botbase setting, created with one of the static methods or parsed from text
+#[pyclass(
+    name = "ConfigureOption",
+    module = "sysbot_rs",
+    eq,
+    frozen,
+    from_py_object
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PyConfigureOption(ConfigureOption);
+
+#[pymethods]
+impl PyConfigureOption {
+    /// Parses an option name followed by its value, such as `"echoCommands false"`.
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        text.parse().map(Self).map_err(value_error)
+    }
+
+    #[staticmethod]
+    fn main_loop_sleep_time(ms: u64) -> Self {
+        Self(ConfigureOption::MainLoopSleepTime(ms))
+    }
+
+    #[staticmethod]
+    fn button_click_sleep_time(ms: u64) -> Self {
+        Self(ConfigureOption::ButtonClickSleepTime(ms))
+    }
+
+    #[staticmethod]
+    fn echo_commands(enabled: bool) -> Self {
+        Self(ConfigureOption::EchoCommands(enabled))
+    }
+
+    #[staticmethod]
+    fn print_debug_result_codes(enabled: bool) -> Self {
+        Self(ConfigureOption::PrintDebugResultCodes(enabled))
+    }
+
+    #[staticmethod]
+    fn key_sleep_time(ms: u64) -> Self {
+        Self(ConfigureOption::KeySleepTime(ms))
+    }
+
+    #[staticmethod]
+    fn finger_diameter(diameter: u32) -> Self {
+        Self(ConfigureOption::FingerDiameter(diameter))
+    }
+
+    #[staticmethod]
+    fn poll_rate(ms: u64) -> Self {
+        Self(ConfigureOption::PollRate(ms))
+    }
+
+    #[staticmethod]
+    fn freeze_rate(ms: u64) -> Self {
+        Self(ConfigureOption::FreezeRate(ms))
+    }
+
+    /// Sets the emulated controller type, by the number sys-botbase uses for it.
+    #[staticmethod]
+    fn controller_type(device_type: u32) -> PyResult<Self> {
+        let device_type = device_type.to_string().parse().map_err(value_error)?;
+        Ok(Self(ConfigureOption::ControllerType(device_type)))
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ConfigureOption.parse({:?})", self.0.to_string())
+    }
+}
+
+/// A connection to sys-botbase
+///
+/// The client is locked for each command, so one instance can be shared between Python threads.
+#[pyclass(name = "SysBotClient", module = "sysbot_rs", frozen)]
+pub struct PySysBotClient {
+    client: Mutex<crate::SysBotClient>,
+}
+
+impl PySysBotClient {
+    fn client(&self) -> MutexGuard<'_, crate::SysBotClient> {
+        self.client
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs `f` on the client without holding the interpreter lock
+    fn run<T: Send>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&crate::SysBotClient) -> Result<T, &'static str> + Send,
+    ) -> PyResult<T> {
+        py.detach(|| f(&self.client())).map_err(client_error)
+    }
+
+    fn peek_bytes<'py>(
+        &self,
+        py: Python<'py>,
+        region: Region,
+        addr: u64,
+        size: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        if size == 0 {
+            return Err(PyValueError::new_err("The size must not be zero"));
+        }
+        let bytes = self.run(py, |client| {
+            client.peek_region(region, PeekArgs { addr, size })
+        })?;
+        let bytes = bytes
+            .get(..size)
+            .ok_or_else(|| SysBotError::new_err("Short peek response"))?;
+        Ok(PyBytes::new(py, bytes))
+    }
+
+    fn poke_bytes(&self, py: Python<'_>, region: Region, addr: u64, data: &[u8]) -> PyResult<()> {
+        if data.is_empty() {
+            return Err(PyValueError::new_err("The data must not be empty"));
+        }
+        let data = PokeData::new(data.to_vec());
+        self.run(py, |client| {
+            client.poke_region(region, PokeArgs { addr, data })
+        })
+    }
+}
+
+#[pymethods]
+impl PySysBotClient {
+    #[new]
+    #[pyo3(signature = (host, port = 6000))]
+    fn new(py: Python<'_>, host: &str, port: u16) -> PyResult<Self> {
+        let client = py
+            .detach(|| crate::SysBotClient::connect(host, port))
+            .map_err(client_error)?;
+        Ok(Self {
+            client: Mutex::new(client),
+        })
+    }
+
+    /// Reads `size` bytes at an address relative to the heap, or to another region given as
+    /// `"heap"`, `"main"` or `"absolute"`.
+    #[pyo3(signature = (addr, size, region = "heap"))]
+    fn peek<'py>(
+        &self,
+        py: Python<'py>,
+        addr: u64,
+        size: usize,
+        region: &str,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let region = region.parse().map_err(value_error)?;
+        self.peek_bytes(py, region, addr, size)
+    }
+
+    fn peek_main<'py>(
+        &self,
+        py: Python<'py>,
+        addr: u64,
+        size: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        self.peek_bytes(py, Region::Main, addr, size)
+    }
+
+    fn peek_absolute<'py>(
+        &self,
+        py: Python<'py>,
+        addr: u64,
+        size: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        self.peek_bytes(py, Region::Absolute, addr, size)
+    }
+
+    /// Writes `data` at an address relative to the heap, or to another region given as
+    /// `"heap"`, `"main"` or `"absolute"`.
+    #[pyo3(signature = (addr, data, region = "heap"))]
+    fn poke(&self, py: Python<'_>, addr: u64, data: &[u8], region: &str) -> PyResult<()> {
+        let region = region.parse().map_err(value_error)?;
+        self.poke_bytes(py, region, addr, data)
+    }
+
+    fn poke_main(&self, py: Python<'_>, addr: u64, data: &[u8]) -> PyResult<()> {
+        self.poke_bytes(py, Region::Main, addr, data)
+    }
+
+    fn poke_absolute(&self, py: Python<'_>, addr: u64, data: &[u8]) -> PyResult<()> {
+        self.poke_bytes(py, Region::Absolute, addr, data)
+    }
+
+    /// Follows a `[[main+X]+Y]+Z` pointer chain, given as `[X, Y, Z]`, and returns the
+    /// address it leads to.
+    fn pointer(&self, py: Python<'_>, jumps: Vec<u64>) -> PyResult<u64> {
+        self.run(py, |client| client.pointer(&jumps))
+    }
+
+    fn pointer_all(&self, py: Python<'_>, jumps: Vec<u64>) -> PyResult<u64> {
+        self.run(py, |client| client.pointer_all(&jumps))
+    }
+
+    fn pointer_relative(&self, py: Python<'_>, jumps: Vec<u64>) -> PyResult<u64> {
+        self.run(py, |client| client.pointer_relative(&jumps))
+    }
+
+    fn pointer_peek<'py>(
+        &self,
+        py: Python<'py>,
+        jumps: Vec<u64>,
+        size: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = self.run(py, |client| client.pointer_peek(&jumps, size))?;
+        let bytes = bytes
+            .get(..size)
+            .ok_or_else(|| SysBotError::new_err("Short peek response"))?;
+        Ok(PyBytes::new(py, bytes))
+    }
+
+    fn pointer_poke(&self, py: Python<'_>, jumps: Vec<u64>, data: &[u8]) -> PyResult<()> {
+        let data = PokeData::new(data.to_vec());
+        self.run(py, |client| client.pointer_poke(&jumps, data))
+    }
+
+    fn click(&self, py: Python<'_>, button: ButtonArg) -> PyResult<()> {
+        let button = button.try_into()?;
+        self.run(py, |client| client.click(button))
+    }
+
+    fn press(&self, py: Python<'_>, button: ButtonArg) -> PyResult<()> {
+        let button = button.try_into()?;
+        self.run(py, |client| client.press(button))
+    }
+
+    fn release(&self, py: Python<'_>, button: ButtonArg) -> PyResult<()> {
+        let button = button.try_into()?;
+        self.run(py, |client| client.release(button))
+    }
+
+    /// Moves a stick to a raw position, with each axis from `-0x8000` to `0x7FFF`.
+    fn set_stick(&self, py: Python<'_>, stick: PyStick, x: i16, y: i16) -> PyResult<()> {
+        self.run(py, |client| {
+            client.set_stick(stick.into(), crate::types::StickMovement(x, y))
+        })
+    }
+
+    fn release_all(&self, py: Python<'_>) -> PyResult<()> {
+        self.run(py, |client| client.release_all())
+    }
+
+    fn detach_controller(&self, py: Python<'_>) -> PyResult<()> {
+        self.run(py, |client| client.detach_controller())
+    }
+
+    fn configure(&self, py: Python<'_>, option: PyConfigureOption) -> PyResult<()> {
+        self.run(py, |client| client.configure(option.0))
+    }
+
+    fn get_title_id(&self, py: Python<'_>) -> PyResult<u64> {
+        self.run(py, |client| client.get_title_id())
+    }
+
+    fn get_build_id(&self, py: Python<'_>) -> PyResult<u64> {
+        self.run(py, |client| client.get_build_id())
+    }
+
+    fn get_heap_base(&self, py: Python<'_>) -> PyResult<u64> {
+        self.run(py, |client| client.get_heap_base())
+    }
+
+    fn get_main_nso_base(&self, py: Python<'_>) -> PyResult<u64> {
+        self.run(py, |client| client.get_main_nso_base())
+    }
+
+    fn get_system_language(&self, py: Python<'_>) -> PyResult<u8> {
+        self.run(py, |client| client.get_system_language())
+    }
+
+    fn get_version(&self, py: Python<'_>) -> PyResult<String> {
+        self.run(py, |client| client.get_version())
+    }
+
+    fn is_program_running(&self, py: Python<'_>, title_id: u64) -> PyResult<bool> {
+        self.run(py, |client| client.is_program_running(title_id))
+    }
+
+    /// Sends a command line as is, returning its response line if the command has one.
+    fn raw<'py>(&self, py: Python<'py>, command: &str) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        let response = self.run(py, |client| client.raw(command))?;
+        Ok(response.map(|response| PyBytes::new(py, &response)))
+    }
+}
+
+#[pymodule]
+fn sysbot_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySysBotClient>()?;
+    m.add_class::<PyButton>()?;
+    m.add_class::<PyStick>()?;
+    m.add_class::<PyConfigureOption>()?;
+    m.add("SysBotError", m.py().get_type::<SysBotError>())?;
+    m.add(
+        "SysBotConnectionError",
+        m.py().get_type::<SysBotConnectionError>(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_server::TestServer;
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn should_drive_console_from_python() {
+        let server = TestServer::start(|command| match command {
+            "peek 0x8A3F70 0x4" => Some("01020304\n".to_string()),
+            "pointerAll 0x4C1DC58 0x18" => Some("0000000012ABCDEF\n".to_string()),
+            "getVersion" => Some("2.4\n".to_string()),
+            _ => None,
+        });
+        Python::initialize();
+        Python::attach(|py| {
+            let module = pyo3::wrap_pymodule!(super::sysbot_rs)(py);
+            let globals = PyDict::new(py);
+            globals.set_item("sysbot_rs", module).unwrap();
+            globals.set_item("port", server.port).unwrap();
+            py.run(
+                cr#"
+client = sysbot_rs.SysBotClient("127.0.0.1", port)
+assert client.peek(0x8A3F70, 4) == b"\x01\x02\x03\x04"
+client.poke_main(0x100, b"\xAB\xCD")
+client.click(sysbot_rs.Button.A)
+client.press("ZL")
+client.set_stick(sysbot_rs.Stick.LEFT, 0, 0x7FFF)
+client.configure(sysbot_rs.ConfigureOption.echo_commands(False))
+assert client.pointer_all([0x4C1DC58, 0x18]) == 0x12ABCDEF
+assert client.raw("getVersion") == b"2.4\n"
+assert str(sysbot_rs.Button.DUP) == "DU"
+try:
+    client.click("START")
+    assert False
+except ValueError:
+    pass
+assert issubclass(sysbot_rs.SysBotConnectionError, sysbot_rs.SysBotError)
+"#,
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            globals.del_item("client").unwrap();
+        });
+        assert_eq!(
+            vec![
+                "peek 0x8A3F70 0x4",
+                "pokeMain 0x100 0xABCD",
+                "click A",
+                "press ZL",
+                "setStick LSTICK 0 32767",
+                "configure echoCommands false",
+                "pointerAll 0x4C1DC58 0x18",
+                "getVersion",
+            ],
+            server.finish()
+        );
+    }
+}
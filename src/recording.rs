@@ -0,0 +1,532 @@
+//! Recording input sessions with timestamps and replaying them with the same timing
+//!
+//! Recordings serialize to one event per line, prefixed by its offset in milliseconds and
+//! followed by the command sys-botbase receives:
+//!
+//! ```text
+//! 0 press A
+//! 120 release A
+//! 300 setStick LSTICK 0 32767
+//! 650 click HOME
+//! ```
+
+use crate::types::{Button, ControllerState, Stick, StickMovement};
+use crate::SysBotClient;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A single controller input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    Click(Button),
+    Press(Button),
+    Release(Button),
+    SetStick(Stick, StickMovement),
+}
+
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InputEvent::Click(b) => write!(f, "click {}", b),
+            InputEvent::Press(b) => write!(f, "press {}", b),
+            InputEvent::Release(b) => write!(f, "release {}", b),
+            InputEvent::SetStick(stick, mv) => write!(f, "setStick {} {} {}", stick, mv.0, mv.1),
+        }
+    }
+}
+
+impl FromStr for InputEvent {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["click", b] => Ok(InputEvent::Click(b.parse()?)),
+            ["press", b] => Ok(InputEvent::Press(b.parse()?)),
+            ["release", b] => Ok(InputEvent::Release(b.parse()?)),
+            ["setStick", stick, x, y] => Ok(InputEvent::SetStick(
+                stick.parse()?,
+                StickMovement(
+                    x.parse().map_err(|_| "Invalid stick coordinate")?,
+                    y.parse().map_err(|_| "Invalid stick coordinate")?,
+                ),
+            )),
+            _ => Err("Unknown input event"),
+        }
+    }
+}
+
+/// Something that controller inputs can be sent to
+pub trait InputSink {
+    fn send_input(&self, event: &InputEvent) -> Result<(), &'static str>;
+}
+
+impl InputSink for SysBotClient {
+    fn send_input(&self, event: &InputEvent) -> Result<(), &'static str> {
+        match *event {
+            InputEvent::Click(b) => self.click(b),
+            InputEvent::Press(b) => self.press(b),
+            InputEvent::Release(b) => self.release(b),
+            InputEvent::SetStick(stick, mv) => self.set_stick(stick, mv),
+        }
+    }
+}
+
+/// An input and its offset from the start of a recording
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedEvent {
+    pub at: Duration,
+    pub event: InputEvent,
+}
+
+/// A timeline of inputs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    events: Vec<TimedEvent>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an event, keeping the timeline ordered
+    pub fn push(&mut self, at: Duration, event: InputEvent) {
+        let index = self.events.partition_point(|e| e.at <= at);
+        self.events.insert(index, TimedEvent { at, event });
+    }
+
+    pub fn events(&self) -> &[TimedEvent] {
+        &self.events
+    }
+
+    /// The offset of the last event
+    pub fn duration(&self) -> Duration {
+        self.events.last().map(|e| e.at).unwrap_or_default()
+    }
+
+    pub fn parse(source: &str) -> Result<Self, &'static str> {
+        let mut recording = Recording::new();
+        for line in source.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (at, event) = line
+                .split_once(' ')
+                .ok_or("Recording line is missing an event")?;
+            let at = at.parse().map_err(|_| "Invalid recording timestamp")?;
+            recording.push(Duration::from_millis(at), event.parse()?);
+        }
+        Ok(recording)
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for TimedEvent { at, event } in &self.events {
+            writeln!(f, "{} {}", at.as_millis(), event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the inputs sent through it, optionally forwarding them to another sink
+pub struct Recorder<'a> {
+    sink: Option<&'a dyn InputSink>,
+    start: Instant,
+    recording: Mutex<Recording>,
+}
+
+impl Default for Recorder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Recorder<'a> {
+    /// Creates a recorder whose timeline starts now
+    pub fn new() -> Self {
+        Self {
+            sink: None,
+            start: Instant::now(),
+            recording: Mutex::new(Recording::new()),
+        }
+    }
+
+    /// Creates a recorder that also sends every input on to `sink`, such as a [`SysBotClient`]
+    ///
+    /// [`SysBotClient`]: struct@crate::SysBotClient
+    pub fn forwarding(sink: &'a dyn InputSink) -> Self {
+        Self {
+            sink: Some(sink),
+            ..Self::new()
+        }
+    }
+
+    fn recording(&self) -> MutexGuard<'_, Recording> {
+        self.recording
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl InputSink for Recorder<'_> {
+    fn send_input(&self, event: &InputEvent) -> Result<(), &'static str> {
+        if let Some(sink) = self.sink {
+            sink.send_input(event)?;
+        }
+        let at = Duration::from_millis(self.start.elapsed().as_millis() as u64);
+        self.recording().push(at, *event);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct PlaybackState {
+    paused: bool,
+    stopped: bool,
+}
+
+/// Pauses, resumes or stops a [`Player`] from another thread
+#[derive(Clone, Default)]
+pub struct PlaybackControl {
+    state: Arc<(Mutex<PlaybackState>, Condvar)>,
+}
+
+impl PlaybackControl {
+    fn lock(&self) -> MutexGuard<'_, PlaybackState> {
+        self.state
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut PlaybackState)) {
+        f(&mut self.lock());
+        self.state.1.notify_all();
+    }
+
+    pub fn pause(&self) {
+        self.update(|s| s.paused = true)
+    }
+
+    pub fn resume(&self) {
+        self.update(|s| s.paused = false)
+    }
+
+    /// Ends playback before the next event is sent
+    ///
+    /// Buttons the recording pressed but had not yet released are released, and sticks it moved
+    /// are centred, so the controller is not left mid-input. The control stays stopped, ending
+    /// any later playback straight away, until it is [`reset`].
+    ///
+    /// [`reset`]: PlaybackControl::reset
+    pub fn stop(&self) {
+        self.update(|s| s.stopped = true)
+    }
+
+    /// Clears a stop or pause so the control can be used for another playback
+    pub fn reset(&self) {
+        self.update(|s| *s = PlaybackState::default())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+}
+
+/// Replays a [`Recording`] with its original timing, scaled by a speed factor
+///
+/// Time spent paused does not count towards the timeline, so events after a pause keep their
+/// spacing.
+pub struct Player {
+    recording: Recording,
+    speed: f64,
+    control: PlaybackControl,
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            speed: 1.0,
+            control: PlaybackControl::default(),
+        }
+    }
+
+    /// Sets the playback speed, where `2.0` plays twice as fast
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn control(&self) -> PlaybackControl {
+        self.control.clone()
+    }
+
+    /// Sends every event to `sink` at its scheduled time, blocking until playback completes or is
+    /// stopped
+    pub fn play(&self, sink: &dyn InputSink) -> Result<(), &'static str> {
+        if !(self.speed.is_finite() && self.speed > 0.0) {
+            return Err("Playback speed must be positive");
+        }
+        let condvar = &self.control.state.1;
+        let start = Instant::now();
+        let mut paused_for = Duration::ZERO;
+        let mut controller = ControllerState::new();
+        for TimedEvent { at, event } in self.recording.events() {
+            let target = at.div_f64(self.speed);
+            let mut state = self.control.lock();
+            loop {
+                if state.stopped {
+                    drop(state);
+                    return Player::reset(&controller, sink);
+                }
+                if state.paused {
+                    let paused_at = Instant::now();
+                    state = condvar
+                        .wait_while(state, |s| s.paused && !s.stopped)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    paused_for += paused_at.elapsed();
+                    continue;
+                }
+                let elapsed = start.elapsed().saturating_sub(paused_for);
+                if elapsed >= target {
+                    break;
+                }
+                state = condvar
+                    .wait_timeout(state, target - elapsed)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            }
+            drop(state);
+            sink.send_input(event)?;
+            match *event {
+                InputEvent::Press(b) => {
+                    controller.hold(b);
+                }
+                InputEvent::Release(b) => {
+                    controller.release(b);
+                }
+                InputEvent::SetStick(stick, mv) => controller.set_stick(stick, mv),
+                InputEvent::Click(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Releases the buttons and centres the sticks that playback left out of neutral
+    fn reset(controller: &ControllerState, sink: &dyn InputSink) -> Result<(), &'static str> {
+        for button in controller.held() {
+            sink.send_input(&InputEvent::Release(button))?;
+        }
+        for stick in [Stick::LEFT, Stick::RIGHT] {
+            if controller.stick(stick) != StickMovement::CENTER {
+                sink.send_input(&InputEvent::SetStick(stick, StickMovement::CENTER))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::recording::{InputEvent, InputSink, PlaybackControl, Player, Recorder, Recording};
+    use crate::types::{Button, Stick, StickMovement};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Records when each input arrived instead of sending it anywhere
+    struct FakeSink {
+        start: Instant,
+        received: Mutex<Vec<(Duration, InputEvent)>>,
+    }
+
+    impl FakeSink {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                received: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl InputSink for FakeSink {
+        fn send_input(&self, event: &InputEvent) -> Result<(), &'static str> {
+            self.received
+                .lock()
+                .unwrap()
+                .push((self.start.elapsed(), *event));
+            Ok(())
+        }
+    }
+
+    fn recording() -> Recording {
+        Recording::parse(
+            "0 press A\n\
+             100 release A\n\
+             200 setStick LSTICK -32768 32767\n",
+        )
+        .unwrap()
+    }
+
+    /// Checks only that an event was not early, since a loaded machine can make it late
+    fn assert_not_before(expected_millis: u64, actual: Duration) {
+        let expected = Duration::from_millis(expected_millis);
+        assert!(
+            actual >= expected,
+            "expected no earlier than {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    /// Stops playback as soon as it has received `after` events
+    struct StoppingSink {
+        inner: FakeSink,
+        control: PlaybackControl,
+        after: usize,
+    }
+
+    impl InputSink for StoppingSink {
+        fn send_input(&self, event: &InputEvent) -> Result<(), &'static str> {
+            self.inner.send_input(event)?;
+            if self.inner.received.lock().unwrap().len() == self.after {
+                self.control.stop();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_round_trip_text() {
+        let recording = recording();
+        assert_eq!(
+            Some(&InputEvent::SetStick(
+                Stick::LEFT,
+                StickMovement(-32768, 32767)
+            )),
+            recording.events().last().map(|e| &e.event)
+        );
+        assert_eq!(
+            Ok(recording.clone()),
+            Recording::parse(&recording.to_string())
+        );
+        assert!(Recording::parse("10 press START").is_err());
+        assert!(Recording::parse("soon press A").is_err());
+    }
+
+    #[test]
+    fn should_record_with_timestamps() {
+        let sink = FakeSink::new();
+        let recorder = Recorder::forwarding(&sink);
+        recorder.send_input(&InputEvent::Press(Button::B)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        recorder
+            .send_input(&InputEvent::Release(Button::B))
+            .unwrap();
+        let recording = recorder.finish();
+        assert_eq!(2, sink.received.lock().unwrap().len());
+        assert_not_before(0, recording.events()[0].at);
+        assert_not_before(50, recording.events()[1].at);
+    }
+
+    #[test]
+    fn should_play_with_speed_scaling() {
+        let sink = FakeSink::new();
+        Player::new(recording())
+            .with_speed(2.0)
+            .play(&sink)
+            .unwrap();
+        let received = sink.received.into_inner().unwrap();
+        assert_eq!(
+            recording()
+                .events()
+                .iter()
+                .map(|e| e.event)
+                .collect::<Vec<_>>(),
+            received.iter().map(|r| r.1).collect::<Vec<_>>()
+        );
+        assert_not_before(0, received[0].0);
+        assert_not_before(50, received[1].0);
+        assert_not_before(100, received[2].0);
+    }
+
+    #[test]
+    fn should_shift_timeline_while_paused() {
+        let sink = FakeSink::new();
+        let player = Player::new(recording());
+        let control = player.control();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                control.pause();
+                thread::sleep(Duration::from_millis(100));
+                control.resume();
+            });
+            player.play(&sink).unwrap();
+        });
+        let received = sink.received.into_inner().unwrap();
+        assert_not_before(0, received[0].0);
+        assert_not_before(200, received[1].0);
+        assert_not_before(300, received[2].0);
+    }
+
+    #[test]
+    fn should_release_held_inputs_when_stopped() {
+        let player = Player::new(recording());
+        let sink = StoppingSink {
+            inner: FakeSink::new(),
+            control: player.control(),
+            after: 1,
+        };
+        player.play(&sink).unwrap();
+        let received = sink.inner.received.into_inner().unwrap();
+        assert_eq!(
+            vec![InputEvent::Press(Button::A), InputEvent::Release(Button::A)],
+            received.iter().map(|r| r.1).collect::<Vec<_>>()
+        );
+
+        let player = Player::new(
+            Recording::parse("0 press B\n0 setStick RSTICK 100 0\n100 release B\n").unwrap(),
+        );
+        let sink = StoppingSink {
+            inner: FakeSink::new(),
+            control: player.control(),
+            after: 2,
+        };
+        player.play(&sink).unwrap();
+        let received = sink.inner.received.into_inner().unwrap();
+        assert_eq!(
+            vec![
+                InputEvent::Press(Button::B),
+                InputEvent::SetStick(Stick::RIGHT, StickMovement(100, 0)),
+                InputEvent::Release(Button::B),
+                InputEvent::SetStick(Stick::RIGHT, StickMovement::CENTER),
+            ],
+            received.iter().map(|r| r.1).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_play_again_after_reset() {
+        let player = Player::new(recording());
+        let control = player.control();
+        control.stop();
+        let sink = FakeSink::new();
+        player.play(&sink).unwrap();
+        assert!(sink.received.into_inner().unwrap().is_empty());
+
+        control.reset();
+        let sink = FakeSink::new();
+        player.play(&sink).unwrap();
+        assert_eq!(3, sink.received.into_inner().unwrap().len());
+    }
+}
@@ -0,0 +1,449 @@
+//! Searching memory for values
+//!
+//! A [`Scanner`] reads a range of memory in chunks and finds the addresses holding a value, a
+//! range of values or a byte pattern. Value searches return a [`Scan`], which can be narrowed
+//! down by reading its candidates again and keeping the ones that changed, stayed the same,
+//! increased or decreased.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::scan::{Filter, Scanner};
+//! use sysbot_rs::types::Region;
+//! use sysbot_rs::SysBotClient;
+//! let client = SysBotClient::connect("0.0.0.0", 6000).unwrap();
+//! let scanner = Scanner::new(Region::Heap, 0, 0x100000).with_alignment(4);
+//! let mut scan = scanner.find::<u32>(&client, Filter::Exact(1500)).unwrap();
+//! // Spend some money in game...
+//! scan.next(&client, Filter::Decreased).unwrap();
+//! scan.next(&client, Filter::Exact(1200)).unwrap();
+//! println!("{:X?}", scan.addresses());
+//! ```
+
+use crate::types::{MemoryValue, PeekArgs, Region};
+use crate::SysBotClient;
+use std::str::FromStr;
+
+/// The number of bytes read by each peek unless the scanner is told otherwise
+pub const DEFAULT_CHUNK_SIZE: usize = 0x1000;
+
+/// Reads `len` bytes starting at `start` with one peek per `chunk_size` bytes
+pub(crate) fn read_range(
+    client: &SysBotClient,
+    region: Region,
+    start: u64,
+    len: usize,
+    chunk_size: usize,
+) -> Result<Vec<u8>, &'static str> {
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than zero");
+    }
+    let mut bytes = Vec::with_capacity(len);
+    while bytes.len() < len {
+        let size = chunk_size.min(len - bytes.len());
+        let mut chunk = client.peek_region(
+            region,
+            PeekArgs {
+                addr: start + bytes.len() as u64,
+                size,
+            },
+        )?;
+        if chunk.len() < size {
+            return Err("Failed to read scanned memory");
+        }
+        chunk.truncate(size);
+        bytes.extend(chunk);
+    }
+    Ok(bytes)
+}
+
+/// Decides which values a search or narrowing step keeps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter<T> {
+    Exact(T),
+    /// Values between the two bounds, inclusive
+    Range(T, T),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl<T: PartialOrd> Filter<T> {
+    /// Whether the filter needs the value from a previous scan
+    pub fn is_relative(&self) -> bool {
+        !matches!(self, Filter::Exact(_) | Filter::Range(_, _))
+    }
+
+    fn matches(&self, old: &T, new: &T) -> bool {
+        match self {
+            Filter::Exact(value) => new == value,
+            Filter::Range(min, max) => min <= new && new <= max,
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+        }
+    }
+}
+
+/// A sequence of bytes to search for, where `None` matches any byte
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BytePattern(pub Vec<Option<u8>>);
+
+impl BytePattern {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Checks the pattern against the start of `bytes`
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() >= self.len()
+            && self
+                .0
+                .iter()
+                .zip(bytes)
+                .all(|(expected, actual)| expected.is_none_or(|e| e == *actual))
+    }
+}
+
+impl FromStr for BytePattern {
+    type Err = &'static str;
+
+    /// Parses hex bytes separated by whitespace, with `??` or `?` as a wildcard
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sysbot_rs::scan::BytePattern;
+    /// let pattern: BytePattern = "E8 03 ?? 00".parse().unwrap();
+    /// assert!(pattern.matches(&[0xE8, 0x03, 0x7F, 0x00]));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s
+            .split_whitespace()
+            .map(|byte| match byte {
+                "?" | "??" => Ok(None),
+                _ if byte.len() == 2 => u8::from_str_radix(byte, 16)
+                    .map(Some)
+                    .map_err(|_| "Invalid byte in pattern"),
+                _ => Err("Invalid byte in pattern"),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if bytes.is_empty() {
+            return Err("Pattern is empty");
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// An address that still matches a scan and the value last read from it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Candidate<T> {
+    pub addr: u64,
+    pub value: T,
+}
+
+/// Searches a range of memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scanner {
+    region: Region,
+    start: u64,
+    len: usize,
+    chunk_size: usize,
+    alignment: u64,
+}
+
+impl Scanner {
+    /// Creates a scanner over `len` bytes starting at `start`, relative to `region`
+    pub fn new(region: Region, start: u64, len: usize) -> Self {
+        Self {
+            region,
+            start,
+            len,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            alignment: 1,
+        }
+    }
+
+    /// Sets the number of bytes read by each peek
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Only matches addresses that are a multiple of `alignment`
+    pub fn with_alignment(mut self, alignment: u64) -> Self {
+        self.alignment = alignment.max(1);
+        self
+    }
+
+    /// Reads the whole range
+    pub fn read(&self, client: &SysBotClient) -> Result<Vec<u8>, &'static str> {
+        read_range(client, self.region, self.start, self.len, self.chunk_size)
+    }
+
+    fn offsets(&self, width: usize) -> impl Iterator<Item = usize> + '_ {
+        let first = (self.alignment - self.start % self.alignment) % self.alignment;
+        (first as usize..(self.len + 1).saturating_sub(width)).step_by(self.alignment as usize)
+    }
+
+    /// Reads the range one chunk at a time, calling `visit` with each offset a `width` byte value
+    /// can start at and the bytes read from there on
+    ///
+    /// Only the current chunk and the end of the previous one are kept in memory.
+    fn search(
+        &self,
+        client: &SysBotClient,
+        width: usize,
+        mut visit: impl FnMut(usize, &[u8]),
+    ) -> Result<(), &'static str> {
+        if self.chunk_size == 0 {
+            return Err("Chunk size must be greater than zero");
+        }
+        let mut offsets = self.offsets(width).peekable();
+        let mut window = Vec::new();
+        let mut base = 0;
+        while base + window.len() < self.len && offsets.peek().is_some() {
+            let read = base + window.len();
+            let size = self.chunk_size.min(self.len - read);
+            window.extend(read_range(
+                client,
+                self.region,
+                self.start + read as u64,
+                size,
+                size,
+            )?);
+            let end = base + window.len();
+            while let Some(&offset) = offsets.peek() {
+                if offset + width > end {
+                    break;
+                }
+                visit(offset, &window[offset - base..]);
+                offsets.next();
+            }
+            // Keep only the bytes the next offset still needs
+            let keep = offsets.peek().map_or(end, |&offset| offset.min(end));
+            window.drain(..keep - base);
+            base = keep;
+        }
+        Ok(())
+    }
+
+    /// Finds every address in the range whose value matches an exact or range filter
+    pub fn find<T: MemoryValue + PartialOrd>(
+        &self,
+        client: &SysBotClient,
+        filter: Filter<T>,
+    ) -> Result<Scan<T>, &'static str> {
+        if filter.is_relative() {
+            return Err("Filter needs a previous scan");
+        }
+        let mut candidates = Vec::new();
+        self.search(client, T::SIZE, |offset, bytes| {
+            if let Some(value) = T::from_le_slice(bytes) {
+                if filter.matches(&value, &value) {
+                    candidates.push(Candidate {
+                        addr: self.start + offset as u64,
+                        value,
+                    });
+                }
+            }
+        })?;
+        Ok(Scan {
+            region: self.region,
+            chunk_size: self.chunk_size,
+            candidates,
+        })
+    }
+
+    /// Finds every address in the range where `pattern` matches
+    pub fn find_pattern(
+        &self,
+        client: &SysBotClient,
+        pattern: &BytePattern,
+    ) -> Result<Vec<u64>, &'static str> {
+        if pattern.is_empty() {
+            return Err("Pattern is empty");
+        }
+        let mut addresses = Vec::new();
+        self.search(client, pattern.len(), |offset, bytes| {
+            if pattern.matches(bytes) {
+                addresses.push(self.start + offset as u64);
+            }
+        })?;
+        Ok(addresses)
+    }
+}
+
+/// The candidates left by a value search
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scan<T> {
+    region: Region,
+    chunk_size: usize,
+    candidates: Vec<Candidate<T>>,
+}
+
+impl<T: MemoryValue + PartialOrd> Scan<T> {
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn candidates(&self) -> &[Candidate<T>] {
+        &self.candidates
+    }
+
+    pub fn addresses(&self) -> Vec<u64> {
+        self.candidates.iter().map(|c| c.addr).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    /// Reads every candidate again and keeps the ones that match `filter`, returning how many are
+    /// left
+    ///
+    /// Candidates within one chunk of each other are read together, so candidates far apart never
+    /// read the memory between them. Relative filters compare against the value read by the
+    /// previous step.
+    pub fn next(
+        &mut self,
+        client: &SysBotClient,
+        filter: Filter<T>,
+    ) -> Result<usize, &'static str> {
+        let mut values = Vec::with_capacity(self.candidates.len());
+        let mut group = 0;
+        while group < self.candidates.len() {
+            let start = self.candidates[group].addr;
+            let end = self.candidates[group..]
+                .iter()
+                .position(|c| c.addr - start + T::SIZE as u64 > self.chunk_size as u64)
+                .map_or(self.candidates.len(), |i| group + i.max(1));
+            let len = (self.candidates[end - 1].addr - start) as usize + T::SIZE;
+            let bytes = read_range(client, self.region, start, len, self.chunk_size)?;
+            values.extend(
+                self.candidates[group..end]
+                    .iter()
+                    .map(|c| T::from_le_slice(&bytes[(c.addr - start) as usize..])),
+            );
+            group = end;
+        }
+        let mut values = values.into_iter();
+        self.candidates
+            .retain_mut(|candidate| match values.next().flatten() {
+                Some(value) if filter.matches(&candidate.value, &value) => {
+                    candidate.value = value;
+                    true
+                }
+                _ => false,
+            });
+        Ok(self.candidates.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scan::{BytePattern, Candidate, Filter, Scanner};
+    use crate::test_server::TestServer;
+    use crate::types::Region;
+    use crate::SysBotClient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serves peeks from `memory`, which is swapped for `after` once `reads` peeks have been made
+    fn server(memory: Vec<u8>, after: Vec<u8>, reads: usize) -> TestServer {
+        let count = Arc::new(AtomicUsize::new(0));
+        TestServer::start(move |command| {
+            let memory = if count.fetch_add(1, Ordering::SeqCst) < reads {
+                &memory
+            } else {
+                &after
+            };
+            let args = command.split(' ').collect::<Vec<_>>();
+            let addr = usize::from_str_radix(&args[1][2..], 16).unwrap();
+            let size = usize::from_str_radix(&args[2][2..], 16).unwrap();
+            let hex = memory[addr..addr + size]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<String>();
+            Some(format!("{}\n", hex))
+        })
+    }
+
+    #[test]
+    fn should_find_and_narrow_values() {
+        let before = [100u16, 7, 100, 100, 3, 100]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let after = [100u16, 7, 90, 110, 3, 100]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let server = server(before, after, 3);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let scanner = Scanner::new(Region::Heap, 0, 12)
+            .with_chunk_size(5)
+            .with_alignment(2);
+        let mut scan = scanner.find::<u16>(&client, Filter::Exact(100)).unwrap();
+        assert_eq!(vec![0, 4, 6, 10], scan.addresses());
+        assert_eq!(Ok(1), scan.next(&client, Filter::Decreased));
+        assert_eq!(&[Candidate { addr: 4, value: 90 }], scan.candidates());
+        assert!(scanner.find::<u16>(&client, Filter::Changed).is_err());
+        drop(client);
+        let reads = ["peek 0x0 0x5", "peek 0x5 0x5", "peek 0xA 0x2"];
+        let narrow = ["peek 0x0 0x2", "peek 0x4 0x4", "peek 0xA 0x2"];
+        assert_eq!([reads, narrow].concat(), server.finish());
+    }
+
+    #[test]
+    fn should_find_ranges_and_patterns() {
+        let memory = vec![0x00, 0xE8, 0x03, 0x10, 0x00, 0xE8, 0x03, 0x20, 0x00];
+        let server = server(memory.clone(), memory, usize::MAX);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let scanner = Scanner::new(Region::Main, 0, 9);
+        let pattern = "E8 03 ?? 00".parse::<BytePattern>().unwrap();
+        assert_eq!(Ok(vec![1, 5]), scanner.find_pattern(&client, &pattern));
+        let scan = scanner
+            .find::<u8>(&client, Filter::Range(0x10, 0x20))
+            .unwrap();
+        assert_eq!(vec![3, 7], scan.addresses());
+        assert!("E8 0".parse::<BytePattern>().is_err());
+        assert!("".parse::<BytePattern>().is_err());
+    }
+
+    #[test]
+    fn should_not_read_between_distant_candidates() {
+        let mut memory = vec![0; 0x1000];
+        memory[0x10] = 7;
+        memory[0xF00] = 7;
+        let mut after = memory.clone();
+        after[0xF00] = 8;
+        let server = server(memory, after, 0x10);
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let scanner = Scanner::new(Region::Heap, 0, 0x1000).with_chunk_size(0x100);
+        let mut scan = scanner.find::<u8>(&client, Filter::Exact(7)).unwrap();
+        assert_eq!(vec![0x10, 0xF00], scan.addresses());
+        assert_eq!(Ok(1), scan.next(&client, Filter::Changed));
+        assert_eq!(
+            &[Candidate {
+                addr: 0xF00,
+                value: 8
+            }],
+            scan.candidates()
+        );
+        drop(client);
+        let commands = server.finish();
+        assert_eq!(18, commands.len());
+        assert_eq!(["peek 0x10 0x1", "peek 0xF00 0x1"], commands[16..]);
+    }
+}
@@ -0,0 +1,678 @@
+//! A small text format for writing input routines without Rust
+//!
+//! Statements are separated by `;` or new lines and `#` starts a comment:
+//!
+//! ```text
+//! let delay = 500ms
+//! A; wait $delay
+//! hold B 1s              # press, wait and release
+//! press ZR; release ZR
+//! LSTICK up 300ms        # tilt then recentre
+//! RSTICK 0.5 -0.5        # normalized coordinates, or raw integers like `RSTICK 0 -32768`
+//! RSTICK center
+//! repeat 5 {
+//!     click DDOWN; wait 100ms
+//! }
+//! ```
+//!
+//! Durations are written as `500ms`, `1s` or `1.5s`, with bare numbers taken as milliseconds,
+//! and must be longer than 0ms. Repeat counts are whole numbers without a unit. Variables are
+//! assigned with `let` and referenced with `$`, holding either a duration or a repeat count.
+//!
+//! # Example
+//!
+//! ```
+//! use sysbot_rs::script::Script;
+//! let script = Script::parse("repeat 2 { A; wait 1s }").unwrap();
+//! assert_eq!(
+//!     vec!["click A", "wait 1000ms", "click A", "wait 1000ms"],
+//!     script.dry_run().unwrap()
+//! );
+//! ```
+
+use crate::types::{Button, Direction, SeqParam, Sequence, Stick, StickMovement};
+use crate::SysBotClient;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// An error raised while parsing, compiling or running a [`Script`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The script is malformed at the given 1-based line
+    Syntax { line: usize, message: String },
+    /// The client failed while running the script
+    Client(&'static str),
+}
+
+impl ScriptError {
+    fn syntax(line: usize, message: impl Into<String>) -> Self {
+        ScriptError::Syntax {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ScriptError::Client(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A parsed input script
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    statements: Vec<Statement>,
+}
+
+impl Script {
+    /// The most inputs a script may expand to once its loops are unrolled
+    pub const MAX_INPUTS: usize = 100_000;
+
+    /// The most loop iterations a script may run while it is compiled, counting every pass
+    /// through every loop, including loops that add no inputs
+    pub const MAX_ITERATIONS: usize = 1_000_000;
+
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let tokens = tokenize(source);
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+        let statements = parser.block(None)?;
+        Ok(Self { statements })
+    }
+
+    /// Resolves variables and unrolls loops into a [`Sequence`]
+    pub fn compile(&self) -> Result<Sequence, ScriptError> {
+        let mut compiler = Compiler {
+            variables: HashMap::new(),
+            params: vec![],
+            iterations: 0,
+        };
+        compiler.statements(&self.statements)?;
+        Ok(compiler
+            .params
+            .into_iter()
+            .fold(Sequence::new(), |sequence, param| sequence.then(param)))
+    }
+
+    /// Runs the script against a client with individual commands
+    pub fn run(&self, client: &SysBotClient) -> Result<(), ScriptError> {
+        let sequence = self.compile()?;
+        client
+            .run_sequence_individually(&sequence)
+            .map_err(ScriptError::Client)
+    }
+
+    /// Returns the commands [`run`] would send, with the waits between them as `wait <n>ms`
+    ///
+    /// [`run`]: Script::run
+    pub fn dry_run(&self) -> Result<Vec<String>, ScriptError> {
+        Ok(self
+            .compile()?
+            .params()
+            .iter()
+            .map(|param| match param {
+                SeqParam::Click(b) => format!("click {}", b),
+                SeqParam::Press(b) => format!("press {}", b),
+                SeqParam::Release(b) => format!("release {}", b),
+                SeqParam::MoveLeft(mv) => format!("setStick {} {} {}", Stick::LEFT, mv.0, mv.1),
+                SeqParam::MoveRight(mv) => {
+                    format!("setStick {} {} {}", Stick::RIGHT, mv.0, mv.1)
+                }
+                SeqParam::Wait(t) => format!("wait {}ms", t),
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    /// A duration in milliseconds, written with a unit or a fraction
+    Duration(u64),
+    /// A bare whole number, usable as a repeat count or a duration in milliseconds
+    Count(u64),
+    Variable(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Command {
+    Click(Button),
+    Press(Button),
+    Release(Button),
+    Wait(Value),
+    Hold(Button, Value),
+    Stick(Stick, StickMovement, Option<Value>),
+    Repeat(Value, Vec<Statement>),
+    Let(String, Value),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Statement {
+    line: usize,
+    command: Command,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Word(String),
+    OpenBrace,
+    CloseBrace,
+    Equals,
+    Separator,
+}
+
+struct Token {
+    kind: TokenKind,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let code = line.split('#').next().unwrap_or_default();
+        let mut word = String::new();
+        for c in code.chars() {
+            let kind = match c {
+                '{' => Some(TokenKind::OpenBrace),
+                '}' => Some(TokenKind::CloseBrace),
+                '=' => Some(TokenKind::Equals),
+                ';' => Some(TokenKind::Separator),
+                c if c.is_whitespace() => None,
+                c => {
+                    word.push(c);
+                    continue;
+                }
+            };
+            if !word.is_empty() {
+                tokens.push(Token {
+                    kind: TokenKind::Word(std::mem::take(&mut word)),
+                    line: line_number,
+                });
+            }
+            if let Some(kind) = kind {
+                tokens.push(Token {
+                    kind,
+                    line: line_number,
+                });
+            }
+        }
+        if !word.is_empty() {
+            tokens.push(Token {
+                kind: TokenKind::Word(word),
+                line: line_number,
+            });
+        }
+        tokens.push(Token {
+            kind: TokenKind::Separator,
+            line: line_number,
+        });
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn line(&self) -> usize {
+        self.peek()
+            .or_else(|| self.tokens.last())
+            .map(|t| t.line)
+            .unwrap_or(1)
+    }
+
+    fn next_word(&mut self, expected: &str) -> Result<String, ScriptError> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Word(word),
+                ..
+            }) => {
+                let word = word.clone();
+                self.position += 1;
+                Ok(word)
+            }
+            _ => Err(ScriptError::syntax(
+                self.line(),
+                format!("expected {}", expected),
+            )),
+        }
+    }
+
+    fn next_is_word(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token {
+                kind: TokenKind::Word(_),
+                ..
+            })
+        )
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<(), ScriptError> {
+        match self.peek() {
+            Some(token) if token.kind == kind => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(ScriptError::syntax(
+                self.line(),
+                format!("expected `{}`", expected),
+            )),
+        }
+    }
+
+    /// Parses statements until the end of the script, or the closing brace of a block opened on
+    /// `opened_on`
+    fn block(&mut self, opened_on: Option<usize>) -> Result<Vec<Statement>, ScriptError> {
+        let mut statements = vec![];
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                None => {
+                    return match opened_on {
+                        Some(line) => Err(ScriptError::syntax(
+                            line,
+                            "block is missing its closing `}`",
+                        )),
+                        None => Ok(statements),
+                    }
+                }
+                Some(TokenKind::Separator) => self.position += 1,
+                Some(TokenKind::CloseBrace) => {
+                    if opened_on.is_none() {
+                        return Err(ScriptError::syntax(self.line(), "unexpected `}`"));
+                    }
+                    self.position += 1;
+                    return Ok(statements);
+                }
+                Some(_) => {
+                    statements.push(self.statement()?);
+                    match self.peek().map(|t| &t.kind) {
+                        None | Some(TokenKind::Separator) | Some(TokenKind::CloseBrace) => {}
+                        Some(_) => {
+                            return Err(ScriptError::syntax(
+                                self.line(),
+                                "expected `;` or a new line after statement",
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, ScriptError> {
+        let line = self.line();
+        let word = self.next_word("a command")?;
+        let command = match word.to_ascii_lowercase().as_str() {
+            "click" => Command::Click(self.button()?),
+            "press" => Command::Press(self.button()?),
+            "release" => Command::Release(self.button()?),
+            "wait" => Command::Wait(self.duration()?),
+            "hold" => Command::Hold(self.button()?, self.duration()?),
+            "repeat" => {
+                let count = self.count()?;
+                self.expect(TokenKind::OpenBrace, "{")?;
+                Command::Repeat(count, self.block(Some(line))?)
+            }
+            "let" => {
+                let name = self.next_word("a variable name")?;
+                if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(ScriptError::syntax(
+                        line,
+                        format!("invalid variable name `{}`", name),
+                    ));
+                }
+                self.expect(TokenKind::Equals, "=")?;
+                Command::Let(name, self.value()?)
+            }
+            _ => match word.parse::<Stick>() {
+                Ok(stick) if self.next_is_word() => {
+                    let movement = self.movement()?;
+                    let duration = if self.next_is_word() {
+                        Some(self.duration()?)
+                    } else {
+                        None
+                    };
+                    Command::Stick(stick, movement, duration)
+                }
+                _ => Command::Click(word.parse::<Button>().map_err(|_| {
+                    ScriptError::syntax(line, format!("unknown command `{}`", word))
+                })?),
+            },
+        };
+        Ok(Statement { line, command })
+    }
+
+    fn button(&mut self) -> Result<Button, ScriptError> {
+        let line = self.line();
+        let word = self.next_word("a button")?;
+        word.parse()
+            .map_err(|_| ScriptError::syntax(line, format!("unknown button `{}`", word)))
+    }
+
+    fn value(&mut self) -> Result<Value, ScriptError> {
+        let line = self.line();
+        let word = self.next_word("a duration, count or variable")?;
+        if let Some(name) = word.strip_prefix('$') {
+            return Ok(Value::Variable(name.to_string()));
+        }
+        if let Some(count) = parse_count(&word) {
+            return Ok(Value::Count(count));
+        }
+        parse_duration(&word).map(Value::Duration).ok_or_else(|| {
+            ScriptError::syntax(line, format!("invalid duration or count `{}`", word))
+        })
+    }
+
+    fn duration(&mut self) -> Result<Value, ScriptError> {
+        let line = self.line();
+        let word = self.next_word("a duration or variable")?;
+        if let Some(name) = word.strip_prefix('$') {
+            return Ok(Value::Variable(name.to_string()));
+        }
+        match parse_duration(&word) {
+            Some(0) => Err(ScriptError::syntax(
+                line,
+                "duration must be longer than 0ms",
+            )),
+            Some(millis) => Ok(Value::Duration(millis)),
+            None => Err(ScriptError::syntax(
+                line,
+                format!("invalid duration `{}`", word),
+            )),
+        }
+    }
+
+    fn count(&mut self) -> Result<Value, ScriptError> {
+        let line = self.line();
+        let word = self.next_word("a repeat count or variable")?;
+        if let Some(name) = word.strip_prefix('$') {
+            return Ok(Value::Variable(name.to_string()));
+        }
+        parse_count(&word)
+            .map(Value::Count)
+            .ok_or_else(|| ScriptError::syntax(line, format!("invalid repeat count `{}`", word)))
+    }
+
+    fn movement(&mut self) -> Result<StickMovement, ScriptError> {
+        let line = self.line();
+        let first = self.next_word("a direction or coordinates")?;
+        if matches!(first.to_ascii_lowercase().as_str(), "center" | "centre") {
+            return Ok(StickMovement::CENTER);
+        }
+        if let Ok(direction) = first.parse::<Direction>() {
+            return Ok(direction.into());
+        }
+        let second = self.next_word("a y coordinate")?;
+        let invalid = || {
+            ScriptError::syntax(
+                line,
+                format!("invalid stick coordinates `{} {}`", first, second),
+            )
+        };
+        if first.contains('.') || second.contains('.') {
+            let x = first.parse::<f32>().map_err(|_| invalid())?;
+            let y = second.parse::<f32>().map_err(|_| invalid())?;
+            Ok(StickMovement::from_normalized(x, y))
+        } else {
+            let x = first.parse::<i16>().map_err(|_| invalid())?;
+            let y = second.parse::<i16>().map_err(|_| invalid())?;
+            Ok(StickMovement(x, y))
+        }
+    }
+}
+
+/// Parses a whole number without a unit
+fn parse_count(word: &str) -> Option<u64> {
+    if word.is_empty() || !word.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    word.parse().ok()
+}
+
+/// Parses a duration in milliseconds, like `500ms`, `1.5s` or a bare `500`
+fn parse_duration(word: &str) -> Option<u64> {
+    let (number, scale) = if let Some(number) = word.strip_suffix("ms") {
+        (number, 1.0)
+    } else if let Some(number) = word.strip_suffix('s') {
+        (number, 1000.0)
+    } else {
+        (word, 1.0)
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n >= 0.0 && n.is_finite() => Some((n * scale).round() as u64),
+        _ => None,
+    }
+}
+
+struct Compiler {
+    variables: HashMap<String, Value>,
+    params: Vec<SeqParam>,
+    iterations: usize,
+}
+
+impl Compiler {
+    /// Looks up a variable, returning a [`Value::Duration`] or [`Value::Count`]
+    fn resolve(&self, line: usize, value: &Value) -> Result<Value, ScriptError> {
+        match value {
+            Value::Variable(name) => self.variables.get(name).cloned().ok_or_else(|| {
+                ScriptError::syntax(line, format!("undefined variable `${}`", name))
+            }),
+            literal => Ok(literal.clone()),
+        }
+    }
+
+    fn millis(&self, line: usize, value: &Value) -> Result<u32, ScriptError> {
+        let millis = match self.resolve(line, value)? {
+            Value::Duration(n) | Value::Count(n) => n,
+            Value::Variable(_) => unreachable!("variables resolve to literals"),
+        };
+        if millis == 0 {
+            return Err(ScriptError::syntax(
+                line,
+                "duration must be longer than 0ms",
+            ));
+        }
+        u32::try_from(millis).map_err(|_| ScriptError::syntax(line, "duration is too long"))
+    }
+
+    fn count(&self, line: usize, value: &Value) -> Result<u64, ScriptError> {
+        match self.resolve(line, value)? {
+            Value::Count(n) => Ok(n),
+            _ => Err(ScriptError::syntax(
+                line,
+                "repeat count must be a whole number without a unit",
+            )),
+        }
+    }
+
+    fn push(&mut self, line: usize, param: SeqParam) -> Result<(), ScriptError> {
+        if self.params.len() >= Script::MAX_INPUTS {
+            return Err(ScriptError::syntax(
+                line,
+                format!("script expands to more than {} inputs", Script::MAX_INPUTS),
+            ));
+        }
+        self.params.push(param);
+        Ok(())
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), ScriptError> {
+        for Statement { line, command } in statements {
+            let line = *line;
+            match command {
+                Command::Click(b) => self.push(line, SeqParam::Click(*b))?,
+                Command::Press(b) => self.push(line, SeqParam::Press(*b))?,
+                Command::Release(b) => self.push(line, SeqParam::Release(*b))?,
+                Command::Wait(value) => {
+                    let millis = self.millis(line, value)?;
+                    self.push(line, SeqParam::Wait(millis))?;
+                }
+                Command::Hold(b, value) => {
+                    let millis = self.millis(line, value)?;
+                    self.push(line, SeqParam::Press(*b))?;
+                    self.push(line, SeqParam::Wait(millis))?;
+                    self.push(line, SeqParam::Release(*b))?;
+                }
+                Command::Stick(stick, movement, None) => {
+                    self.push(line, SeqParam::move_stick(*stick, *movement))?
+                }
+                Command::Stick(stick, movement, Some(value)) => {
+                    let millis = self.millis(line, value)?;
+                    for param in SeqParam::tilt(*stick, *movement, millis) {
+                        self.push(line, param)?;
+                    }
+                }
+                Command::Repeat(value, body) => {
+                    for _ in 0..self.count(line, value)? {
+                        self.iterations += 1;
+                        if self.iterations > Script::MAX_ITERATIONS {
+                            return Err(ScriptError::syntax(
+                                line,
+                                format!(
+                                    "script runs more than {} loop iterations",
+                                    Script::MAX_ITERATIONS
+                                ),
+                            ));
+                        }
+                        self.statements(body)?;
+                    }
+                }
+                Command::Let(name, value) => {
+                    let value = self.resolve(line, value)?;
+                    self.variables.insert(name.clone(), value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::script::{Script, ScriptError};
+    use crate::types::{Button, SeqParam, StickMovement};
+
+    #[test]
+    fn should_compile_statements() {
+        let script = Script::parse(
+            "A; wait 500ms; hold B 1s\n\
+             LSTICK up 300ms\n\
+             RSTICK 0.5 -1.0; RSTICK center",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                "click A",
+                "wait 500ms",
+                "press B",
+                "wait 1000ms",
+                "release B",
+                "setStick LSTICK 0 32767",
+                "wait 300ms",
+                "setStick LSTICK 0 0",
+                "setStick RSTICK 16384 -32767",
+                "setStick RSTICK 0 0",
+            ],
+            script.dry_run().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_unroll_loops_with_variables() {
+        let script = Script::parse(
+            "let n = 2 # count\n\
+             let t = 1.5s\n\
+             repeat $n {\n\
+                 LSTICK\n\
+                 repeat 2 { wait $t }\n\
+             }",
+        )
+        .unwrap();
+        let sequence = script.compile().unwrap();
+        let expected = [
+            SeqParam::Click(Button::STICK(crate::types::Stick::LEFT)),
+            SeqParam::Wait(1500),
+            SeqParam::Wait(1500),
+        ]
+        .repeat(2);
+        assert_eq!(expected, sequence.params());
+    }
+
+    #[test]
+    fn should_parse_raw_stick_coordinates() {
+        let script = Script::parse("RSTICK -32768 32767").unwrap();
+        assert_eq!(
+            &[SeqParam::MoveRight(StickMovement(-32768, 32767))],
+            script.compile().unwrap().params()
+        );
+    }
+
+    #[test]
+    fn should_report_error_lines() {
+        let error = |source| match Script::parse(source).and_then(|s| s.compile()) {
+            Err(ScriptError::Syntax { line, .. }) => line,
+            other => panic!("expected a syntax error, got {:?}", other),
+        };
+        assert_eq!(2, error("A\nSTART"));
+        assert_eq!(1, error("hold B"));
+        assert_eq!(3, error("A\n\nwait $missing"));
+        assert_eq!(2, error("A\nrepeat 3 {\nB"));
+        assert_eq!(1, error("A B"));
+        assert_eq!(1, error("}"));
+    }
+
+    #[test]
+    fn should_reject_fractional_counts_and_zero_durations() {
+        let error = |source| match Script::parse(source).and_then(|s| s.compile()) {
+            Err(ScriptError::Syntax { line, message }) => (line, message),
+            other => panic!("expected a syntax error, got {:?}", other),
+        };
+        assert_eq!(
+            (1, "invalid repeat count `1.5s`".to_string()),
+            error("repeat 1.5s { A }")
+        );
+        assert_eq!(
+            (
+                2,
+                "repeat count must be a whole number without a unit".to_string()
+            ),
+            error("let t = 2s\nrepeat $t { A }")
+        );
+        let zero = "duration must be longer than 0ms".to_string();
+        assert_eq!((2, zero.clone()), error("A\nwait 0"));
+        assert_eq!((1, zero.clone()), error("hold A 0ms"));
+        assert_eq!((1, zero.clone()), error("LSTICK up 0.0001s"));
+        assert_eq!((3, zero), error("let t = 0\nA\nwait $t"));
+        assert!(Script::parse("repeat 0 { A }").unwrap().compile().is_ok());
+    }
+
+    #[test]
+    fn should_limit_loops_without_inputs() {
+        let script = Script::parse("repeat 18446744073709551615 {\n  let x = 1\n}").unwrap();
+        assert_eq!(
+            Err(ScriptError::syntax(
+                1,
+                "script runs more than 1000000 loop iterations"
+            )),
+            script.compile()
+        );
+        let nested = Script::parse("repeat 2000 {\n  repeat 1000 {\n  }\n}").unwrap();
+        assert!(nested.compile().is_err());
+    }
+}
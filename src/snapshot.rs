@@ -0,0 +1,372 @@
+//! Comparing memory before and after something happens
+//!
+//! A [`Snapshot`] holds copies of one or more ranges of a region, read with as few `peekMulti`
+//! commands as possible. Diffing two snapshots of the same ranges gives every run of changed
+//! bytes, along with what the surrounding aligned values changed from and to when read as
+//! `u8`, `u16`, `u32` and `f32`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysbot_rs::snapshot::Snapshot;
+//! use sysbot_rs::types::Region;
+//! use sysbot_rs::SysBotClient;
+//! let client = SysBotClient::connect("0.0.0.0", 6000).unwrap();
+//! let ranges = [(0x8A0000, 0x1000), (0x9C0000, 0x200)];
+//! let before = Snapshot::capture(&client, Region::Heap, &ranges).unwrap();
+//! // Buy an item in game...
+//! let after = Snapshot::capture(&client, Region::Heap, &ranges).unwrap();
+//! let diff = before.diff(&after).unwrap();
+//! diff.save("purchase.diff").unwrap();
+//! ```
+
+use crate::scan::DEFAULT_CHUNK_SIZE;
+use crate::types::{PeekArgs, Region};
+use crate::SysBotClient;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// A copy of the bytes starting at an address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotRange {
+    pub start: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Copies of ranges of memory relative to one region
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub region: Region,
+    pub ranges: Vec<SnapshotRange>,
+}
+
+impl Snapshot {
+    pub fn new(region: Region, ranges: Vec<SnapshotRange>) -> Self {
+        Self { region, ranges }
+    }
+
+    /// Reads each `(start, len)` range, batching the reads into `peekMulti` commands of up to
+    /// [`DEFAULT_CHUNK_SIZE`] bytes
+    pub fn capture(
+        client: &SysBotClient,
+        region: Region,
+        ranges: &[(u64, usize)],
+    ) -> Result<Self, &'static str> {
+        let mut pieces = vec![];
+        for (index, (start, len)) in ranges.iter().enumerate() {
+            for offset in (0..*len).step_by(DEFAULT_CHUNK_SIZE) {
+                let size = DEFAULT_CHUNK_SIZE.min(len - offset);
+                pieces.push((index, start + offset as u64, size));
+            }
+        }
+        let mut captured = ranges
+            .iter()
+            .map(|(start, len)| SnapshotRange {
+                start: *start,
+                bytes: Vec::with_capacity(*len),
+            })
+            .collect::<Vec<_>>();
+        let mut batch: Vec<(usize, u64, usize)> = vec![];
+        let mut pieces = pieces.into_iter().peekable();
+        while let Some(piece) = pieces.next() {
+            batch.push(piece);
+            let total = batch.iter().map(|(_, _, size)| size).sum::<usize>();
+            if let Some((_, _, size)) = pieces.peek() {
+                if total + size <= DEFAULT_CHUNK_SIZE {
+                    continue;
+                }
+            }
+            let args = batch
+                .iter()
+                .map(|(_, addr, size)| PeekArgs {
+                    addr: *addr,
+                    size: *size,
+                })
+                .collect();
+            let bytes = client.peek_region_multi(region, args)?;
+            if bytes.len() < total {
+                return Err("Failed to read snapshot memory");
+            }
+            let mut offset = 0;
+            for (index, _, size) in batch.drain(..) {
+                captured[index]
+                    .bytes
+                    .extend_from_slice(&bytes[offset..offset + size]);
+                offset += size;
+            }
+        }
+        Ok(Self {
+            region,
+            ranges: captured,
+        })
+    }
+
+    /// Compares this snapshot with a later one of the same ranges
+    pub fn diff(&self, after: &Snapshot) -> Result<Diff, &'static str> {
+        let same_layout = self.region == after.region
+            && self.ranges.len() == after.ranges.len()
+            && self
+                .ranges
+                .iter()
+                .zip(&after.ranges)
+                .all(|(a, b)| a.start == b.start && a.bytes.len() == b.bytes.len());
+        if !same_layout {
+            return Err("Snapshots cover different memory");
+        }
+        let mut changes = vec![];
+        for (before, after) in self.ranges.iter().zip(&after.ranges) {
+            let (old, new) = (&before.bytes, &after.bytes);
+            let mut i = 0;
+            while i < old.len() {
+                if old[i] == new[i] {
+                    i += 1;
+                    continue;
+                }
+                let run_start = i;
+                while i < old.len() && old[i] != new[i] {
+                    i += 1;
+                }
+                changes.push(Change {
+                    addr: before.start + run_start as u64,
+                    old: old[run_start..i].to_vec(),
+                    new: new[run_start..i].to_vec(),
+                    interpretations: interpret(before.start, old, new, run_start, i),
+                });
+            }
+        }
+        Ok(Diff {
+            region: self.region,
+            changes,
+        })
+    }
+}
+
+/// Reads every aligned `u8`, `u16`, `u32` and `f32` overlapping `run_start..run_end`
+fn interpret(
+    start: u64,
+    old: &[u8],
+    new: &[u8],
+    run_start: usize,
+    run_end: usize,
+) -> Vec<Interpretation> {
+    let mut interpretations = vec![];
+    for width in [1usize, 2, 4] {
+        let first = (start as usize + run_start) / width * width;
+        let mut addr = first;
+        while addr < start as usize + run_end {
+            let offset = addr.wrapping_sub(start as usize);
+            if addr >= start as usize && offset + width <= old.len() {
+                let (o, n) = (&old[offset..offset + width], &new[offset..offset + width]);
+                let values = match width {
+                    1 => vec![(TypedValue::U8(o[0]), TypedValue::U8(n[0]))],
+                    2 => vec![(
+                        TypedValue::U16(u16::from_le_bytes(o.try_into().unwrap())),
+                        TypedValue::U16(u16::from_le_bytes(n.try_into().unwrap())),
+                    )],
+                    _ => vec![
+                        (
+                            TypedValue::U32(u32::from_le_bytes(o.try_into().unwrap())),
+                            TypedValue::U32(u32::from_le_bytes(n.try_into().unwrap())),
+                        ),
+                        (
+                            TypedValue::F32(f32::from_le_bytes(o.try_into().unwrap())),
+                            TypedValue::F32(f32::from_le_bytes(n.try_into().unwrap())),
+                        ),
+                    ],
+                };
+                interpretations.extend(values.into_iter().map(|(old, new)| Interpretation {
+                    addr: addr as u64,
+                    old,
+                    new,
+                }));
+            }
+            addr += width;
+        }
+    }
+    interpretations
+}
+
+/// A value read from memory as a particular type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TypedValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    F32(f32),
+}
+
+impl TypedValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TypedValue::U8(_) => "u8",
+            TypedValue::U16(_) => "u16",
+            TypedValue::U32(_) => "u32",
+            TypedValue::F32(_) => "f32",
+        }
+    }
+}
+
+impl Display for TypedValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypedValue::U8(v) => write!(f, "{}", v),
+            TypedValue::U16(v) => write!(f, "{}", v),
+            TypedValue::U32(v) => write!(f, "{}", v),
+            TypedValue::F32(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// What an aligned value overlapping a change was before and after
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interpretation {
+    pub addr: u64,
+    pub old: TypedValue,
+    pub new: TypedValue,
+}
+
+/// A run of consecutive bytes that changed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub addr: u64,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    pub interpretations: Vec<Interpretation>,
+}
+
+/// The changes between two snapshots
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diff {
+    pub region: Region,
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Writes the diff as text
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl Display for Diff {
+    /// Writes a line per change followed by an indented line per interpretation
+    ///
+    /// ```text
+    /// region heap
+    /// 0x10 E803 -> DC05
+    ///   u8 0x10 232 -> 220
+    ///   u16 0x10 1000 -> 1500
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "region {}", self.region)?;
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<String>()
+        };
+        for change in &self.changes {
+            writeln!(
+                f,
+                "0x{:X} {} -> {}",
+                change.addr,
+                hex(&change.old),
+                hex(&change.new)
+            )?;
+            for i in &change.interpretations {
+                writeln!(
+                    f,
+                    "  {} 0x{:X} {} -> {}",
+                    i.old.type_name(),
+                    i.addr,
+                    i.old,
+                    i.new
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::snapshot::{Interpretation, Snapshot, SnapshotRange, TypedValue};
+    use crate::test_server::TestServer;
+    use crate::types::Region;
+    use crate::SysBotClient;
+
+    fn snapshot(bytes: Vec<u8>) -> Snapshot {
+        Snapshot::new(Region::Heap, vec![SnapshotRange { start: 0x10, bytes }])
+    }
+
+    #[test]
+    fn should_report_changed_runs() {
+        let before = snapshot(vec![0xE8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        let after = snapshot(vec![0xDC, 0x05, 0x00, 0x00, 0x00, 0x00, 0x80, 0x3F]);
+        let diff = before.diff(&after).unwrap();
+        assert_eq!(
+            vec![(0x10, 2), (0x16, 2)],
+            diff.changes
+                .iter()
+                .map(|c| (c.addr, c.old.len()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(&Interpretation {
+                addr: 0x10,
+                old: TypedValue::U16(1000),
+                new: TypedValue::U16(1500)
+            }),
+            diff.changes[0].interpretations.get(2)
+        );
+        assert!(before.diff(&before).unwrap().is_empty());
+        assert!(before.diff(&snapshot(vec![0; 4])).is_err());
+    }
+
+    #[test]
+    fn should_format_diff() {
+        let before = snapshot(vec![0x00, 0x00, 0x00, 0x00]);
+        let after = snapshot(vec![0x00, 0x00, 0x80, 0x3F]);
+        assert_eq!(
+            "region heap\n\
+             0x12 0000 -> 803F\n\
+             \x20 u8 0x12 0 -> 128\n\
+             \x20 u8 0x13 0 -> 63\n\
+             \x20 u16 0x12 0 -> 16256\n\
+             \x20 u32 0x10 0 -> 1065353216\n\
+             \x20 f32 0x10 0 -> 1\n",
+            before.diff(&after).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn should_batch_captures() {
+        let server = TestServer::start(|command| {
+            let sizes = command
+                .split(' ')
+                .skip(2)
+                .step_by(2)
+                .map(|s| usize::from_str_radix(&s[2..], 16).unwrap())
+                .sum::<usize>();
+            Some(format!("{}\n", "01".repeat(sizes)))
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let snapshot =
+            Snapshot::capture(&client, Region::Main, &[(0x100, 0x1800), (0x4000, 0x10)]).unwrap();
+        assert_eq!(vec![1; 0x1800], snapshot.ranges[0].bytes);
+        assert_eq!(vec![1; 0x10], snapshot.ranges[1].bytes);
+        drop(client);
+        assert_eq!(
+            vec![
+                "peekMainMulti 0x100 0x1000",
+                "peekMainMulti 0x1100 0x800 0x4000 0x10"
+            ],
+            server.finish()
+        );
+    }
+}
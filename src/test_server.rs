@@ -0,0 +1,46 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::thread::JoinHandle;
+
+/// A local stand-in for a sys-botbase server used by the tests
+///
+/// Every line received is recorded and passed to the handler, whose response (if any) is written
+/// back verbatim. The server serves a single connection and stops once the client disconnects.
+pub(crate) struct TestServer {
+    pub port: u16,
+    handle: JoinHandle<Vec<String>>,
+}
+
+impl TestServer {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test server");
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("Failed to accept connection");
+            let mut writer = stream.try_clone().unwrap();
+            let mut commands = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let command = line.trim().to_string();
+                if let Some(response) = handler(&command) {
+                    writer.write_all(response.as_bytes()).unwrap();
+                    writer.flush().unwrap();
+                }
+                commands.push(command);
+            }
+            commands
+        });
+        Self { port, handle }
+    }
+
+    /// Waits for the client to disconnect and returns every command the server received
+    pub fn finish(self) -> Vec<String> {
+        self.handle.join().expect("Test server panicked")
+    }
+}
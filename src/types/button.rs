@@ -1,7 +1,9 @@
 use crate::types::stick::Stick;
 use std::fmt;
 use std::fmt::Formatter;
+use std::str::FromStr;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Button {
     A,
     B,
@@ -22,6 +24,30 @@ pub enum Button {
     CAPTURE,
 }
 
+impl Button {
+    /// Every button that can be pressed or held on the controller
+    pub const ALL: [Button; 18] = [
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+        Button::STICK(Stick::LEFT),
+        Button::STICK(Stick::RIGHT),
+        Button::L,
+        Button::R,
+        Button::ZL,
+        Button::ZR,
+        Button::PLUS,
+        Button::MINUS,
+        Button::DLEFT,
+        Button::DUP,
+        Button::DDOWN,
+        Button::DRIGHT,
+        Button::HOME,
+        Button::CAPTURE,
+    ];
+}
+
 impl fmt::Display for Button {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -45,3 +71,46 @@ impl fmt::Display for Button {
         }
     }
 }
+
+impl FromStr for Button {
+    type Err = &'static str;
+
+    /// Parses the names sys-botbase accepts, as well as the long forms of the d-pad buttons
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(Button::A),
+            "B" => Ok(Button::B),
+            "X" => Ok(Button::X),
+            "Y" => Ok(Button::Y),
+            "RSTICK" => Ok(Button::STICK(Stick::RIGHT)),
+            "LSTICK" => Ok(Button::STICK(Stick::LEFT)),
+            "L" => Ok(Button::L),
+            "R" => Ok(Button::R),
+            "ZL" => Ok(Button::ZL),
+            "ZR" => Ok(Button::ZR),
+            "PLUS" => Ok(Button::PLUS),
+            "MINUS" => Ok(Button::MINUS),
+            "DL" | "DLEFT" => Ok(Button::DLEFT),
+            "DU" | "DUP" => Ok(Button::DUP),
+            "DD" | "DDOWN" => Ok(Button::DDOWN),
+            "DR" | "DRIGHT" => Ok(Button::DRIGHT),
+            "HOME" => Ok(Button::HOME),
+            "CAPTURE" => Ok(Button::CAPTURE),
+            _ => Err("Unknown button"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::Button;
+
+    #[test]
+    fn should_parse_displayed_names() {
+        for button in Button::ALL {
+            assert_eq!(Ok(button), button.to_string().parse());
+        }
+        assert_eq!(Ok(Button::DLEFT), "dleft".parse());
+        assert!("START".parse::<Button>().is_err());
+    }
+}
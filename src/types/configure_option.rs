@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HidDeviceType {
     JoyRight1,
     JoyLeft2,
@@ -50,6 +52,38 @@ impl Display for HidDeviceType {
     }
 }
 
+impl FromStr for HidDeviceType {
+    type Err = &'static str;
+
+    /// Parses the number sys-botbase uses for the device type
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1" => Ok(HidDeviceType::JoyRight1),
+            "2" => Ok(HidDeviceType::JoyLeft2),
+            "3" => Ok(HidDeviceType::FullKey3),
+            "4" => Ok(HidDeviceType::JoyLeft4),
+            "5" => Ok(HidDeviceType::JoyRight5),
+            "6" => Ok(HidDeviceType::FullKey6),
+            "7" => Ok(HidDeviceType::LarkHvcLeft),
+            "8" => Ok(HidDeviceType::LarkHvcRight),
+            "9" => Ok(HidDeviceType::LarkNesLeft),
+            "10" => Ok(HidDeviceType::LarkNesRight),
+            "11" => Ok(HidDeviceType::Lucia),
+            "12" => Ok(HidDeviceType::Palma),
+            "13" => Ok(HidDeviceType::FullKey13),
+            "15" => Ok(HidDeviceType::FullKey15),
+            "17" => Ok(HidDeviceType::DebugPad),
+            "19" => Ok(HidDeviceType::System19),
+            "20" => Ok(HidDeviceType::System20),
+            "21" => Ok(HidDeviceType::System21),
+            "22" => Ok(HidDeviceType::Lagon),
+            "28" => Ok(HidDeviceType::Lager),
+            _ => Err("Unknown controller type"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ConfigureOption {
     MainLoopSleepTime(u64),
     ButtonClickSleepTime(u64),
@@ -77,3 +111,59 @@ impl Display for ConfigureOption {
         }
     }
 }
+
+impl ConfigureOption {
+    /// The name of every option, as sys-botbase spells it
+    pub const KEYS: [&'static str; 9] = [
+        "mainLoopSleepTime",
+        "buttonClickSleepTime",
+        "echoCommands",
+        "printDebugResultCodes",
+        "keySleepTime",
+        "fingerDiameter",
+        "pollRate",
+        "freezeRate",
+        "controllerType",
+    ];
+}
+
+impl FromStr for ConfigureOption {
+    type Err = &'static str;
+
+    /// Parses an option name followed by its value, the same way it is displayed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use sysbot_rs::types::ConfigureOption;
+    /// let option: ConfigureOption = "echoCommands false".parse().unwrap();
+    /// assert_eq!("echoCommands false", option.to_string());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or("Expected an option name and value")?;
+        let value = value.trim();
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| "Invalid number for option")
+        };
+        let flag = || value.parse::<bool>().map_err(|_| "Expected true or false");
+        match key {
+            "mainLoopSleepTime" => Ok(ConfigureOption::MainLoopSleepTime(number()?)),
+            "buttonClickSleepTime" => Ok(ConfigureOption::ButtonClickSleepTime(number()?)),
+            "echoCommands" => Ok(ConfigureOption::EchoCommands(flag()?)),
+            "printDebugResultCodes" => Ok(ConfigureOption::PrintDebugResultCodes(flag()?)),
+            "keySleepTime" => Ok(ConfigureOption::KeySleepTime(number()?)),
+            "fingerDiameter" => Ok(ConfigureOption::FingerDiameter(
+                value.parse().map_err(|_| "Invalid number for option")?,
+            )),
+            "pollRate" => Ok(ConfigureOption::PollRate(number()?)),
+            "freezeRate" => Ok(ConfigureOption::FreezeRate(number()?)),
+            "controllerType" => Ok(ConfigureOption::ControllerType(value.parse()?)),
+            _ => Err("Unknown configure option"),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::types::button::Button;
+use crate::types::seq_param::SeqParam;
+use crate::types::stick::Stick;
+use crate::types::stick_movement::StickMovement;
+use std::collections::BTreeSet;
+
+/// The buttons held and stick positions of a controller
+///
+/// A [`SysBotClient`] tracks the state it has put the controller in, and [`apply`] moves the
+/// controller to a target state with the fewest commands.
+///
+/// [`SysBotClient`]: struct@crate::SysBotClient
+/// [`apply`]: fn@crate::SysBotClient::apply
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ControllerState {
+    held: BTreeSet<Button>,
+    left_stick: StickMovement,
+    right_stick: StickMovement,
+}
+
+impl ControllerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the state with `button` held down
+    pub fn with_button(mut self, button: Button) -> Self {
+        self.hold(button);
+        self
+    }
+
+    /// Returns the state with `stick` moved to `movement`
+    pub fn with_stick(mut self, stick: Stick, movement: StickMovement) -> Self {
+        self.set_stick(stick, movement);
+        self
+    }
+
+    /// Marks `button` as held, returning false if it already was
+    pub fn hold(&mut self, button: Button) -> bool {
+        self.held.insert(button)
+    }
+
+    /// Marks `button` as released, returning false if it was not held
+    pub fn release(&mut self, button: Button) -> bool {
+        self.held.remove(&button)
+    }
+
+    pub fn set_stick(&mut self, stick: Stick, movement: StickMovement) {
+        match stick {
+            Stick::LEFT => self.left_stick = movement,
+            Stick::RIGHT => self.right_stick = movement,
+        }
+    }
+
+    pub fn is_held(&self, button: Button) -> bool {
+        self.held.contains(&button)
+    }
+
+    pub fn held(&self) -> impl Iterator<Item = Button> + '_ {
+        self.held.iter().copied()
+    }
+
+    pub fn stick(&self, stick: Stick) -> StickMovement {
+        match stick {
+            Stick::LEFT => self.left_stick,
+            Stick::RIGHT => self.right_stick,
+        }
+    }
+
+    /// Returns true if no buttons are held and both sticks are centred
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Computes the commands needed to move from this state to `target`
+    ///
+    /// Releases are ordered before presses so that swapping buttons never has both held at once.
+    pub fn diff(&self, target: &ControllerState) -> Vec<SeqParam> {
+        let mut commands = self
+            .held
+            .difference(&target.held)
+            .map(|b| SeqParam::Release(*b))
+            .collect::<Vec<_>>();
+        commands.extend(
+            target
+                .held
+                .difference(&self.held)
+                .map(|b| SeqParam::Press(*b)),
+        );
+        if self.left_stick != target.left_stick {
+            commands.push(SeqParam::MoveLeft(target.left_stick));
+        }
+        if self.right_stick != target.right_stick {
+            commands.push(SeqParam::MoveRight(target.right_stick));
+        }
+        commands
+    }
+
+    /// Updates the state as if `param` had been sent to the controller
+    pub(crate) fn update(&mut self, param: &SeqParam) {
+        match param {
+            SeqParam::Press(b) => {
+                self.hold(*b);
+            }
+            SeqParam::Release(b) => {
+                self.release(*b);
+            }
+            SeqParam::MoveLeft(mv) => self.set_stick(Stick::LEFT, *mv),
+            SeqParam::MoveRight(mv) => self.set_stick(Stick::RIGHT, *mv),
+            SeqParam::Click(_) | SeqParam::Wait(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{Button, ControllerState, SeqParam, Stick, StickMovement};
+
+    #[test]
+    fn should_diff_to_nothing_when_equal() {
+        let state = ControllerState::new()
+            .with_button(Button::A)
+            .with_stick(Stick::LEFT, StickMovement(100, -100));
+        assert!(state.diff(&state.clone()).is_empty());
+    }
+
+    #[test]
+    fn should_release_before_press() {
+        let current = ControllerState::new()
+            .with_button(Button::A)
+            .with_button(Button::L);
+        let target = ControllerState::new()
+            .with_button(Button::B)
+            .with_button(Button::L)
+            .with_stick(Stick::RIGHT, StickMovement(0, 0x7FFF));
+        assert_eq!(
+            vec![
+                SeqParam::Release(Button::A),
+                SeqParam::Press(Button::B),
+                SeqParam::MoveRight(StickMovement(0, 0x7FFF)),
+            ],
+            current.diff(&target)
+        );
+    }
+
+    #[test]
+    fn should_reach_target_after_update() {
+        let mut current = ControllerState::new()
+            .with_button(Button::ZR)
+            .with_stick(Stick::LEFT, StickMovement(-0x8000, 0));
+        let target = ControllerState::new().with_button(Button::STICK(Stick::LEFT));
+        for param in current.diff(&target) {
+            current.update(&param);
+        }
+        assert_eq!(target, current);
+    }
+}
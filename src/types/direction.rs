@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+/// A named direction a stick can be tilted in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction {
+    /// The angle of the direction in degrees, counter-clockwise from [`Direction::Right`]
+    pub fn angle(&self) -> f32 {
+        match self {
+            Direction::Right => 0.0,
+            Direction::UpRight => 45.0,
+            Direction::Up => 90.0,
+            Direction::UpLeft => 135.0,
+            Direction::Left => 180.0,
+            Direction::DownLeft => 225.0,
+            Direction::Down => 270.0,
+            Direction::DownRight => 315.0,
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = &'static str;
+
+    /// Parses direction names case-insensitively, with diagonals optionally split by `-` or `_`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "up" => Ok(Direction::Up),
+            "upright" => Ok(Direction::UpRight),
+            "right" => Ok(Direction::Right),
+            "downright" => Ok(Direction::DownRight),
+            "down" => Ok(Direction::Down),
+            "downleft" => Ok(Direction::DownLeft),
+            "left" => Ok(Direction::Left),
+            "upleft" => Ok(Direction::UpLeft),
+            _ => Err("Unknown direction"),
+        }
+    }
+}
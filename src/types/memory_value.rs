@@ -0,0 +1,44 @@
+/// A fixed-size value that can be read from or written to memory in little-endian order
+pub trait MemoryValue: Copy + Sized {
+    const SIZE: usize;
+
+    /// Reads the value from the start of `bytes`, returning `None` if there are too few
+    fn from_le_slice(bytes: &[u8]) -> Option<Self>;
+
+    fn to_le_vec(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_memory_value {
+    ($($t:ty),*) => {
+        $(
+            impl MemoryValue for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn from_le_slice(bytes: &[u8]) -> Option<Self> {
+                    Some(<$t>::from_le_bytes(bytes.get(..Self::SIZE)?.try_into().ok()?))
+                }
+
+                fn to_le_vec(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_memory_value!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+#[cfg(test)]
+mod test {
+    use crate::types::MemoryValue;
+
+    #[test]
+    fn should_round_trip_little_endian() {
+        assert_eq!(
+            Some(0x12345678),
+            u32::from_le_slice(&[0x78, 0x56, 0x34, 0x12, 0x0A])
+        );
+        assert_eq!(vec![0x00, 0x00, 0x80, 0x3F], 1.0f32.to_le_vec());
+        assert_eq!(None, u64::from_le_slice(&[0; 4]));
+    }
+}
@@ -1,18 +1,30 @@
 mod button;
 mod configure_option;
+mod controller_state;
+mod direction;
+mod memory_value;
 mod peek_args;
 mod poke_args;
 mod poke_data;
+mod region;
+mod running_program;
 mod seq_param;
+mod sequence;
 mod stick;
 mod stick_movement;
 pub mod thread_message;
 
 pub use button::*;
 pub use configure_option::*;
+pub use controller_state::*;
+pub use direction::*;
+pub use memory_value::*;
 pub use peek_args::*;
 pub use poke_args::*;
 pub use poke_data::*;
+pub use region::*;
+pub use running_program::*;
 pub use seq_param::*;
+pub use sequence::*;
 pub use stick::*;
 pub use stick_movement::*;
@@ -1,6 +1,6 @@
 use crate::types::poke_data::PokeData;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PokeArgs {
     pub addr: u64,
     pub data: PokeData,
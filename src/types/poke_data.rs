@@ -1,4 +1,7 @@
-#[derive(Clone)]
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PokeData {
     data: Vec<u8>,
 }
@@ -7,16 +10,19 @@ impl PokeData {
     pub fn new(data: Vec<u8>) -> Self {
         Self { data }
     }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
 }
 
-impl ToString for PokeData {
-    fn to_string(&self) -> String {
-        let mut string = String::with_capacity(self.data.len() + 2);
-        string += "0x";
+impl fmt::Display for PokeData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "0x")?;
         for datum in &self.data {
-            string += &format!("{:0>2X}", datum);
+            write!(f, "{:0>2X}", datum)?;
         }
-        string
+        Ok(())
     }
 }
 
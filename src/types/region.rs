@@ -0,0 +1,37 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// The memory an address is relative to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Region {
+    /// Relative to the start of the heap, as used by `peek` and `poke`
+    Heap,
+    /// Relative to the start of the main NSO, as used by `peekMain` and `pokeMain`
+    Main,
+    /// An absolute address, as used by `peekAbsolute` and `pokeAbsolute`
+    Absolute,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Heap => write!(f, "heap"),
+            Region::Main => write!(f, "main"),
+            Region::Absolute => write!(f, "absolute"),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "heap" => Ok(Region::Heap),
+            "main" => Ok(Region::Main),
+            "absolute" => Ok(Region::Absolute),
+            _ => Err("Unknown memory region"),
+        }
+    }
+}
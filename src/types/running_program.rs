@@ -0,0 +1,15 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+/// The title and build of the program currently running on the console
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunningProgram {
+    pub title_id: u64,
+    pub build_id: u64,
+}
+
+impl fmt::Display for RunningProgram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016X} (build {:016X})", self.title_id, self.build_id)
+    }
+}
@@ -1,8 +1,10 @@
 use crate::types::button::Button;
+use crate::types::stick::Stick;
 use crate::types::stick_movement::StickMovement;
 use std::fmt;
 use std::fmt::Formatter;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SeqParam {
     Click(Button),
     Press(Button),
@@ -12,6 +14,26 @@ pub enum SeqParam {
     Wait(u32),
 }
 
+impl SeqParam {
+    /// Creates a movement of `stick` to `movement`
+    pub fn move_stick(stick: Stick, movement: impl Into<StickMovement>) -> Self {
+        match stick {
+            Stick::LEFT => SeqParam::MoveLeft(movement.into()),
+            Stick::RIGHT => SeqParam::MoveRight(movement.into()),
+        }
+    }
+
+    /// Creates the parameters that tilt `stick` to `movement` for `duration` milliseconds and then
+    /// centre it again
+    pub fn tilt(stick: Stick, movement: impl Into<StickMovement>, duration: u32) -> [Self; 3] {
+        [
+            SeqParam::move_stick(stick, movement),
+            SeqParam::Wait(duration),
+            SeqParam::move_stick(stick, StickMovement::CENTER),
+        ]
+    }
+}
+
 impl fmt::Display for SeqParam {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
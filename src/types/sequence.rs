@@ -0,0 +1,239 @@
+use crate::types::button::Button;
+use crate::types::seq_param::SeqParam;
+use crate::types::stick::Stick;
+use crate::types::stick_movement::StickMovement;
+use std::time::Duration;
+
+/// A builder for input sequences sent with `clickSeq`
+///
+/// # Example
+///
+/// ```
+/// use sysbot_rs::types::{Button, Direction, Sequence};
+/// let sequence = Sequence::new()
+///     .click(Button::A)
+///     .wait(500)
+///     .hold(Button::B, 200)
+///     .tilt_left(Direction::Up, 300);
+/// assert_eq!(
+///     vec!["clickSeq A,W500,+B,W200,-B,%0,32767,W300,%0,0".to_string()],
+///     sequence.commands().unwrap()
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sequence {
+    params: Vec<SeqParam>,
+    buffer_size: usize,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sequence {
+    /// The default maximum length of a single command line, including the line ending
+    pub const DEFAULT_BUFFER_SIZE: usize = 4096;
+
+    /// The default value of sys-botbase's `buttonClickSleepTime` in milliseconds
+    pub const DEFAULT_CLICK_SLEEP_TIME: u64 = 50;
+
+    const COMMAND: &'static str = "clickSeq ";
+
+    pub fn new() -> Self {
+        Self {
+            params: vec![],
+            buffer_size: Self::DEFAULT_BUFFER_SIZE,
+        }
+    }
+
+    /// Sets the maximum length of a command line, beyond which the sequence is split
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    pub fn then(mut self, param: SeqParam) -> Self {
+        self.params.push(param);
+        self
+    }
+
+    pub fn click(self, button: Button) -> Self {
+        self.then(SeqParam::Click(button))
+    }
+
+    pub fn press(self, button: Button) -> Self {
+        self.then(SeqParam::Press(button))
+    }
+
+    pub fn release(self, button: Button) -> Self {
+        self.then(SeqParam::Release(button))
+    }
+
+    /// Waits for `millis` milliseconds
+    pub fn wait(self, millis: u32) -> Self {
+        self.then(SeqParam::Wait(millis))
+    }
+
+    /// Holds `button` for `millis` milliseconds
+    pub fn hold(self, button: Button, millis: u32) -> Self {
+        self.press(button).wait(millis).release(button)
+    }
+
+    pub fn move_left(self, movement: impl Into<StickMovement>) -> Self {
+        self.then(SeqParam::move_stick(Stick::LEFT, movement))
+    }
+
+    pub fn move_right(self, movement: impl Into<StickMovement>) -> Self {
+        self.then(SeqParam::move_stick(Stick::RIGHT, movement))
+    }
+
+    /// Tilts the left stick for `millis` milliseconds before centring it
+    pub fn tilt_left(mut self, movement: impl Into<StickMovement>, millis: u32) -> Self {
+        self.params
+            .extend(SeqParam::tilt(Stick::LEFT, movement, millis));
+        self
+    }
+
+    /// Tilts the right stick for `millis` milliseconds before centring it
+    pub fn tilt_right(mut self, movement: impl Into<StickMovement>, millis: u32) -> Self {
+        self.params
+            .extend(SeqParam::tilt(Stick::RIGHT, movement, millis));
+        self
+    }
+
+    pub fn params(&self) -> &[SeqParam] {
+        &self.params
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Estimates how long the console takes to run the sequence
+    ///
+    /// # Arguments
+    ///
+    /// * `click_sleep_time` - The `buttonClickSleepTime` the server is configured with
+    pub fn estimated_duration(&self, click_sleep_time: u64) -> Duration {
+        let millis = self
+            .params
+            .iter()
+            .map(|p| match p {
+                SeqParam::Click(_) => click_sleep_time,
+                SeqParam::Wait(t) => *t as u64,
+                _ => 0,
+            })
+            .sum();
+        Duration::from_millis(millis)
+    }
+
+    /// Checks that the sequence can be sent
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.params.is_empty() {
+            return Err("Sequence is empty");
+        }
+        if self.params.contains(&SeqParam::Wait(0)) {
+            return Err("Sequence waits must be longer than 0ms");
+        }
+        let longest = self
+            .params
+            .iter()
+            .map(|p| p.to_string().len())
+            .max()
+            .unwrap_or_default();
+        if Self::COMMAND.len() + longest + 2 > self.buffer_size {
+            return Err("Sequence buffer size is too small");
+        }
+        Ok(())
+    }
+
+    /// Splits the sequence into groups that each fit in a single `clickSeq` command
+    pub fn chunks(&self) -> Result<Vec<Vec<SeqParam>>, &'static str> {
+        self.validate()?;
+        let mut chunks = vec![];
+        let mut chunk: Vec<SeqParam> = vec![];
+        let mut length = Self::COMMAND.len() + 2;
+        for param in &self.params {
+            let param_length = param.to_string().len() + if chunk.is_empty() { 0 } else { 1 };
+            if length + param_length > self.buffer_size {
+                chunks.push(std::mem::take(&mut chunk));
+                length = Self::COMMAND.len() + 2;
+                chunk.push(*param);
+                length += param.to_string().len();
+            } else {
+                chunk.push(*param);
+                length += param_length;
+            }
+        }
+        chunks.push(chunk);
+        Ok(chunks)
+    }
+
+    /// Compiles the sequence to the `clickSeq` commands that would be sent, without line endings
+    pub fn commands(&self) -> Result<Vec<String>, &'static str> {
+        Ok(self
+            .chunks()?
+            .into_iter()
+            .map(|chunk| {
+                let args = chunk
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{}{}", Self::COMMAND, args)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{Button, Direction, Sequence, StickMovement};
+    use std::time::Duration;
+
+    #[test]
+    fn should_estimate_duration() {
+        let sequence = Sequence::new()
+            .click(Button::A)
+            .wait(500)
+            .hold(Button::B, 200)
+            .tilt_right(Direction::Left, 300);
+        assert_eq!(
+            Duration::from_millis(1050),
+            sequence.estimated_duration(Sequence::DEFAULT_CLICK_SLEEP_TIME)
+        );
+    }
+
+    #[test]
+    fn should_split_at_buffer_size() {
+        let sequence = Sequence::new()
+            .click(Button::A)
+            .click(Button::B)
+            .move_left(StickMovement(-32768, 32767))
+            .click(Button::HOME)
+            .with_buffer_size(26);
+        let commands = sequence.commands().unwrap();
+        assert_eq!(
+            vec![
+                "clickSeq A,B".to_string(),
+                "clickSeq %-32768,32767".to_string(),
+                "clickSeq HOME".to_string()
+            ],
+            commands
+        );
+        assert!(commands.iter().all(|c| c.len() + 2 <= 26));
+    }
+
+    #[test]
+    fn should_reject_invalid_sequences() {
+        assert!(Sequence::new().validate().is_err());
+        assert!(Sequence::new().hold(Button::A, 0).validate().is_err());
+        assert!(Sequence::new()
+            .move_left(StickMovement(-32768, -32768))
+            .with_buffer_size(16)
+            .validate()
+            .is_err());
+    }
+}
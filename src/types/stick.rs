@@ -1,6 +1,8 @@
 use std::fmt;
 use std::fmt::Formatter;
+use std::str::FromStr;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Stick {
     RIGHT,
     LEFT,
@@ -14,3 +16,15 @@ impl fmt::Display for Stick {
         }
     }
 }
+
+impl FromStr for Stick {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "RSTICK" => Ok(Stick::RIGHT),
+            "LSTICK" => Ok(Stick::LEFT),
+            _ => Err("Unknown stick"),
+        }
+    }
+}
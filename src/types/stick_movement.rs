@@ -1,10 +1,108 @@
+use crate::types::direction::Direction;
 use std::fmt;
 use std::fmt::Formatter;
 
+/// The raw position of a stick, with positive values to the right and up
+///
+/// Each axis ranges from `-0x8000` to `0x7FFF`. The constructors taking floats map `-1.0..=1.0`
+/// onto `-0x7FFF..=0x7FFF` so that both directions have the same reach.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct StickMovement(pub i16, pub i16);
 
+impl StickMovement {
+    /// The stick at rest
+    pub const CENTER: StickMovement = StickMovement(0, 0);
+
+    const MAX: f32 = i16::MAX as f32;
+
+    /// Creates a movement from normalized coordinates, clamping each axis to `-1.0..=1.0`
+    pub fn from_normalized(x: f32, y: f32) -> Self {
+        let scale = |v: f32| (v.clamp(-1.0, 1.0) * Self::MAX).round() as i16;
+        Self(scale(x), scale(y))
+    }
+
+    /// Creates a movement from an angle in degrees, counter-clockwise from the right, and a
+    /// magnitude in `0.0..=1.0`
+    pub fn from_angle(degrees: f32, magnitude: f32) -> Self {
+        let magnitude = magnitude.clamp(0.0, 1.0);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self::from_normalized(cos * magnitude, sin * magnitude)
+    }
+
+    /// Creates a movement tilting the stick fully in `direction`
+    pub fn from_direction(direction: Direction) -> Self {
+        Self::from_angle(direction.angle(), 1.0)
+    }
+
+    /// Returns the position as normalized coordinates
+    pub fn to_normalized(&self) -> (f32, f32) {
+        let scale = |v: i16| (v as f32 / Self::MAX).clamp(-1.0, 1.0);
+        (scale(self.0), scale(self.1))
+    }
+
+    /// Returns the normalized distance of the stick from the centre
+    pub fn magnitude(&self) -> f32 {
+        let (x, y) = self.to_normalized();
+        x.hypot(y).min(1.0)
+    }
+
+    /// Returns the centred position if the stick is within the normalized `deadzone` radius
+    pub fn with_deadzone(self, deadzone: f32) -> Self {
+        if self.magnitude() < deadzone {
+            Self::CENTER
+        } else {
+            self
+        }
+    }
+}
+
+impl From<Direction> for StickMovement {
+    fn from(direction: Direction) -> Self {
+        Self::from_direction(direction)
+    }
+}
+
 impl fmt::Display for StickMovement {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{},{}", self.0, self.1)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::types::{Direction, StickMovement};
+
+    #[test]
+    fn should_scale_and_clamp_normalized() {
+        assert_eq!(
+            StickMovement(32767, -32767),
+            StickMovement::from_normalized(1.0, -1.0)
+        );
+        assert_eq!(
+            StickMovement(32767, 0),
+            StickMovement::from_normalized(3.5, 0.0)
+        );
+        assert_eq!(
+            StickMovement(16384, 0),
+            StickMovement::from_normalized(0.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn should_follow_sign_convention() {
+        assert_eq!(StickMovement(0, 32767), Direction::Up.into());
+        assert_eq!(StickMovement(-32767, 0), Direction::Left.into());
+        assert_eq!(StickMovement(-23170, -23170), Direction::DownLeft.into());
+        assert_eq!(
+            StickMovement(23170, 23170),
+            StickMovement::from_angle(45.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn should_centre_within_deadzone() {
+        let slight = StickMovement::from_normalized(0.05, -0.05);
+        assert_eq!(StickMovement::CENTER, slight.with_deadzone(0.1));
+        assert_eq!(slight, slight.with_deadzone(0.05));
+    }
+}
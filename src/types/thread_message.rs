@@ -4,3 +4,6 @@ pub(crate) struct ThreadMessage {
     pub close: bool,
     pub size: usize,
 }
+
+/// A response size telling the worker to read until the response ends with a newline
+pub(crate) const READ_LINE: usize = usize::MAX;
@@ -0,0 +1,307 @@
+//! Polling memory for changes
+//!
+//! A [`Watcher`] holds any number of watches, each with its own poll interval. Every tick, the
+//! watches that are due are read with one `peekMulti`, `peekMainMulti` or `peekAbsoluteMulti`
+//! command per region, and a [`WatchEvent`] is delivered for each one whose bytes changed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use sysbot_rs::types::Region;
+//! use sysbot_rs::watch::Watcher;
+//! use sysbot_rs::SysBotClient;
+//! let client = SysBotClient::connect("0.0.0.0", 6000).unwrap();
+//! let mut watcher = Watcher::new();
+//! let (_, events) = watcher.watch(Region::Heap, 0x8A3F7C, 1, Duration::from_millis(100));
+//! std::thread::spawn(move || {
+//!     for event in events {
+//!         println!("battle flag {:?} -> {:?}", event.old, event.new);
+//!     }
+//! });
+//! watcher
+//!     .run(&client, Duration::from_millis(50), |_| true)
+//!     .unwrap();
+//! ```
+
+use crate::types::{PeekArgs, Region};
+use crate::SysBotClient;
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Identifies a watch registered with a [`Watcher`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WatchId(u64);
+
+impl From<WatchId> for u64 {
+    fn from(id: WatchId) -> Self {
+        id.0
+    }
+}
+
+/// A change in the bytes of a watched address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub id: WatchId,
+    pub region: Region,
+    pub addr: u64,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+enum Delivery {
+    Channel(Sender<WatchEvent>),
+    Callback(Box<dyn FnMut(&WatchEvent) + Send>),
+}
+
+struct Watch {
+    region: Region,
+    addr: u64,
+    size: usize,
+    interval: Duration,
+    next_poll: Instant,
+    last: Option<Vec<u8>>,
+    delivery: Delivery,
+}
+
+/// Polls watched addresses and reports when their bytes change
+///
+/// The first read of a watch only records its bytes; events are delivered for later changes.
+#[derive(Default)]
+pub struct Watcher {
+    watches: BTreeMap<WatchId, Watch>,
+    next_id: u64,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(
+        &mut self,
+        region: Region,
+        addr: u64,
+        size: usize,
+        interval: Duration,
+        delivery: Delivery,
+    ) -> WatchId {
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        self.watches.insert(
+            id,
+            Watch {
+                region,
+                addr,
+                size,
+                interval,
+                next_poll: Instant::now(),
+                last: None,
+                delivery,
+            },
+        );
+        id
+    }
+
+    /// Watches `size` bytes at an address, delivering changes over the returned channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - The region the address is relative to
+    /// * `addr` - The address to watch
+    /// * `size` - The number of bytes to watch
+    /// * `interval` - How often to read the address
+    pub fn watch(
+        &mut self,
+        region: Region,
+        addr: u64,
+        size: usize,
+        interval: Duration,
+    ) -> (WatchId, Receiver<WatchEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.add(region, addr, size, interval, Delivery::Channel(sender));
+        (id, receiver)
+    }
+
+    /// Watches `size` bytes at an address, calling `callback` on every change
+    pub fn watch_with(
+        &mut self,
+        region: Region,
+        addr: u64,
+        size: usize,
+        interval: Duration,
+        callback: impl FnMut(&WatchEvent) + Send + 'static,
+    ) -> WatchId {
+        self.add(
+            region,
+            addr,
+            size,
+            interval,
+            Delivery::Callback(Box::new(callback)),
+        )
+    }
+
+    /// Stops a watch, returning false if it did not exist
+    pub fn unwatch(&mut self, id: WatchId) -> bool {
+        self.watches.remove(&id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Reads every watch that is due and delivers the changes, returning how many were delivered
+    ///
+    /// Watches whose channel has been dropped are removed the next time they change.
+    pub fn poll(&mut self, client: &SysBotClient) -> Result<usize, &'static str> {
+        let now = Instant::now();
+        let mut due: BTreeMap<Region, Vec<WatchId>> = BTreeMap::new();
+        for (id, watch) in &self.watches {
+            if watch.next_poll <= now {
+                due.entry(watch.region).or_default().push(*id);
+            }
+        }
+        let mut delivered = 0;
+        let mut closed = vec![];
+        for (region, ids) in due {
+            let args = ids
+                .iter()
+                .map(|id| PeekArgs {
+                    addr: self.watches[id].addr,
+                    size: self.watches[id].size,
+                })
+                .collect::<Vec<_>>();
+            let bytes = client.peek_region_multi(region, args)?;
+            let mut offset = 0;
+            for id in ids {
+                let watch = self.watches.get_mut(&id).unwrap();
+                let new = bytes
+                    .get(offset..offset + watch.size)
+                    .ok_or("Failed to read watched memory")?
+                    .to_vec();
+                offset += watch.size;
+                watch.next_poll = (watch.next_poll + watch.interval).max(now);
+                let old = match watch.last.replace(new.clone()) {
+                    Some(old) if old != new => old,
+                    _ => continue,
+                };
+                let event = WatchEvent {
+                    id,
+                    region,
+                    addr: watch.addr,
+                    old,
+                    new,
+                };
+                match &mut watch.delivery {
+                    Delivery::Channel(sender) => {
+                        if sender.send(event).is_err() {
+                            closed.push(id);
+                            continue;
+                        }
+                    }
+                    Delivery::Callback(callback) => callback(&event),
+                }
+                delivered += 1;
+            }
+        }
+        for id in closed {
+            self.watches.remove(&id);
+        }
+        Ok(delivered)
+    }
+
+    /// Polls every `tick` until `keep_running` returns false
+    pub fn run(
+        &mut self,
+        client: &SysBotClient,
+        tick: Duration,
+        mut keep_running: impl FnMut(&mut Watcher) -> bool,
+    ) -> Result<(), &'static str> {
+        while keep_running(self) {
+            let started = Instant::now();
+            self.poll(client)?;
+            if let Some(remaining) = tick.checked_sub(started.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_server::TestServer;
+    use crate::types::Region;
+    use crate::watch::{WatchEvent, Watcher};
+    use crate::SysBotClient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn should_coalesce_watches_per_region() {
+        let polls = AtomicUsize::new(0);
+        let server = TestServer::start(move |command| {
+            let poll = polls.fetch_add(1, Ordering::SeqCst) / 2;
+            match command.split(' ').next() {
+                Some("peekMulti") => Some(format!("{:02X}AABB\n", poll.min(1))),
+                Some("peekMainMulti") => Some("0000\n".to_string()),
+                _ => None,
+            }
+        });
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let mut watcher = Watcher::new();
+        let (flag, flag_events) = watcher.watch(Region::Heap, 0x10, 1, Duration::ZERO);
+        let (_, other_events) = watcher.watch(Region::Heap, 0x20, 2, Duration::ZERO);
+        let callback_events = Arc::new(Mutex::new(vec![]));
+        let events = callback_events.clone();
+        watcher.watch_with(Region::Main, 0x30, 2, Duration::ZERO, move |e| {
+            events.lock().unwrap().push(e.clone())
+        });
+        assert_eq!(Ok(0), watcher.poll(&client));
+        assert_eq!(Ok(1), watcher.poll(&client));
+        assert_eq!(
+            WatchEvent {
+                id: flag,
+                region: Region::Heap,
+                addr: 0x10,
+                old: vec![0x00],
+                new: vec![0x01]
+            },
+            flag_events.try_recv().unwrap()
+        );
+        assert!(other_events.try_recv().is_err());
+        assert!(callback_events.lock().unwrap().is_empty());
+        drop(client);
+        assert_eq!(
+            vec![
+                "peekMulti 0x10 0x1 0x20 0x2",
+                "peekMainMulti 0x30 0x2",
+                "peekMulti 0x10 0x1 0x20 0x2",
+                "peekMainMulti 0x30 0x2"
+            ],
+            server.finish()
+        );
+    }
+
+    #[test]
+    fn should_respect_intervals() {
+        let server = TestServer::start(|_| Some("00\n".to_string()));
+        let client = SysBotClient::connect("127.0.0.1", server.port).unwrap();
+        let mut watcher = Watcher::new();
+        let (slow, _events) = watcher.watch(Region::Absolute, 0x10, 1, Duration::from_secs(60));
+        watcher.poll(&client).unwrap();
+        watcher.poll(&client).unwrap();
+        assert!(watcher.unwatch(slow));
+        assert!(watcher.is_empty());
+        drop(client);
+        assert_eq!(vec!["peekAbsoluteMulti 0x10 0x1"], server.finish());
+    }
+}
@@ -0,0 +1,115 @@
+//! Builds `tests/ffi/harness.c` against the static library and runs it against a stand-in
+//! sys-botbase server.
+#![cfg(feature = "ffi")]
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+/// Answers the commands the harness sends and returns every command it received
+fn serve(listener: TcpListener) -> Vec<String> {
+    let (stream, _) = listener.accept().unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    let mut commands = vec![];
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let response = match line.as_str() {
+            "peek 0x8A3F70 0x4" => Some("01020304\n"),
+            "pointerAll 0x4C1DC58 0x18 0x10" => Some("0000000012ABCDEF\n"),
+            _ => None,
+        };
+        if let Some(response) = response {
+            writer.write_all(response.as_bytes()).unwrap();
+        }
+        commands.push(line);
+    }
+    commands
+}
+
+/// Builds the static library, which the crate does not build by default, and returns its path
+fn build_library(root: &Path, tmp: &Path) -> PathBuf {
+    let target = tmp.join("ffi");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(root)
+        .args([
+            "rustc",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+        ])
+        .arg("--target-dir")
+        .arg(&target)
+        .status()
+        .expect("Failed to run cargo");
+    assert!(status.success(), "Failed to build the library");
+    target.join("debug").join("libsysbot_rs.a")
+}
+
+#[test]
+fn should_run_c_harness() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let library = build_library(root, tmp);
+    let harness = tmp.join("harness");
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(root.join("tests/ffi/harness.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&harness)
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(status.success(), "Failed to compile the harness");
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || serve(listener));
+    let output = Command::new(&harness)
+        .arg(port.to_string())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        vec![
+            "peek 0x8A3F70 0x4",
+            "pokeMain 0x100 0xABCD",
+            "click A",
+            "press ZL",
+            "release ZL",
+            "pointerAll 0x4C1DC58 0x18 0x10",
+        ],
+        server.join().unwrap()
+    );
+}
+
+#[test]
+fn should_declare_every_function_in_header() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let source = std::fs::read_to_string(root.join("src/ffi.rs")).unwrap();
+    let header = std::fs::read_to_string(root.join("include/sysbot.h")).unwrap();
+    let functions = source
+        .split("extern \"C\" fn ")
+        .skip(1)
+        .map(|rest| &rest[..rest.find('(').unwrap()]);
+    for function in functions {
+        assert!(
+            header.contains(&format!(" {}(", function))
+                || header.contains(&format!("*{}(", function)),
+            "{} is missing from include/sysbot.h",
+            function
+        );
+    }
+}
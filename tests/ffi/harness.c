@@ -0,0 +1,61 @@
+/*
+ * Exercises the C API against a stand-in sys-botbase server.
+ *
+ * Usage: harness <port>
+ *
+ * Prints the first failed check and exits with 1, or exits with 0 when every check passes.
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "sysbot.h"
+
+#define CHECK(condition)                                                              \
+    do {                                                                              \
+        if (!(condition)) {                                                           \
+            fprintf(stderr, "%s:%d: %s (%s)\n", __FILE__, __LINE__, #condition,       \
+                    sysbot_last_error());                                             \
+            return 1;                                                                 \
+        }                                                                             \
+    } while (0)
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <port>\n", argv[0]);
+        return 2;
+    }
+    uint16_t port = (uint16_t)atoi(argv[1]);
+
+    CHECK(sysbot_connect(NULL, port) == NULL);
+    CHECK(strlen(sysbot_last_error()) > 0);
+
+    SysBot *bot = sysbot_connect("127.0.0.1", port);
+    CHECK(bot != NULL);
+
+    uint8_t buffer[4] = {0};
+    CHECK(sysbot_peek(bot, SYSBOT_REGION_HEAP, 0x8A3F70, buffer, sizeof buffer) == SYSBOT_OK);
+    CHECK(buffer[0] == 0x01 && buffer[1] == 0x02 && buffer[2] == 0x03 && buffer[3] == 0x04);
+    CHECK(sysbot_peek(bot, 7, 0x8A3F70, buffer, sizeof buffer) == SYSBOT_ERROR_ARGUMENT);
+    CHECK(sysbot_peek(bot, SYSBOT_REGION_HEAP, 0x8A3F70, NULL, 4) == SYSBOT_ERROR_ARGUMENT);
+
+    const uint8_t data[2] = {0xAB, 0xCD};
+    CHECK(sysbot_poke(bot, SYSBOT_REGION_MAIN, 0x100, data, sizeof data) == SYSBOT_OK);
+
+    CHECK(sysbot_click(bot, "A") == SYSBOT_OK);
+    CHECK(sysbot_press(bot, "ZL") == SYSBOT_OK);
+    CHECK(sysbot_release(bot, "ZL") == SYSBOT_OK);
+    CHECK(sysbot_click(bot, "NOT_A_BUTTON") == SYSBOT_ERROR_ARGUMENT);
+    CHECK(strlen(sysbot_last_error()) > 0);
+
+    const uint64_t jumps[3] = {0x4C1DC58, 0x18, 0x10};
+    uint64_t addr = 0;
+    CHECK(sysbot_pointer(bot, jumps, 3, &addr) == SYSBOT_OK);
+    CHECK(addr == 0x12ABCDEF);
+
+    CHECK(sysbot_click(NULL, "A") == SYSBOT_ERROR_ARGUMENT);
+
+    sysbot_close(bot);
+    sysbot_close(NULL);
+    return 0;
+}